@@ -0,0 +1,250 @@
+use std::sync::Arc;
+
+use bson::oid::ObjectId;
+use futures::StreamExt;
+use mongodb::Client;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::callbacks::answer_callback;
+use crate::commands;
+use crate::common::send_text;
+use crate::db_utils::{
+    self,
+    models::{AccessRequest, Permission},
+    user::User,
+};
+use crate::delivery;
+use crate::i18n::{tr, Language, Localize};
+use crate::sender::{self, Priority};
+
+pub const ACCESS_PREFIX: &str = "access";
+/// Requests with more regions can only be approved or denied as a whole.
+const MAX_REGION_BUTTONS: usize = 10;
+
+/// Sends an access request of `user` to the admins who can approve it. Returns the reply
+/// for the requester.
+pub async fn request(
+    bot: &AutoSend<Bot>,
+    user: &User,
+    name: &str,
+    regions: Vec<String>,
+    reason: String,
+    lang: Language,
+) -> String {
+    let request = match user.request_access(regions, reason).await {
+        Ok(r) => r,
+        Err(e) => return tr!(lang, "fail.request_access", error = e.localize(lang)),
+    };
+    let reviewers = reviewers(&user.client, &request.regions).await;
+    if reviewers.is_empty() {
+        log::warn!("Nobody can review the access request {}", request._id);
+    }
+    for (admin, admin_lang) in reviewers {
+        let text = tr!(
+            admin_lang,
+            "access.request",
+            name = name,
+            id = request.user_id,
+            regions = request.regions.join(", "),
+            reason = request.reason
+        );
+        let keyboard = keyboard(&request, all(&request), admin_lang);
+        if let Err(e) = sender::send(admin, Priority::Interactive, || {
+            bot.send_message(admin, text.as_str())
+                .reply_markup(keyboard.clone())
+        })
+        .await
+        {
+            log::error!("Can't send an access request to {}. Error: {}", admin, e);
+        }
+    }
+    tr!(lang, "access.sent")
+}
+
+/// Admins, and regional admins whose regions overlap with `regions`, with their languages.
+async fn reviewers(client: &Client, regions: &[String]) -> Vec<(i64, Language)> {
+    let users = match db_utils::list_users(client, vec![]).await {
+        Ok(users) => users.collect::<Vec<_>>().await,
+        Err(e) => {
+            log::error!("Can't access users. Error: {}", e);
+            return vec![];
+        }
+    };
+    users
+        .into_iter()
+        .flatten()
+        .filter(|u| {
+            u.group.has(Permission::ManageUsers)
+                || (u.group.has(Permission::ManageRegionUsers)
+                    && regions.iter().any(|r| u.admin_regions.contains(r)))
+        })
+        .map(|u| (u.id, u.profile.language))
+        .collect()
+}
+
+/// Selected regions are a bit mask over the request's regions.
+fn all(request: &AccessRequest) -> u32 {
+    match request.regions.len() {
+        n if n <= MAX_REGION_BUTTONS => (1 << n) - 1,
+        _ => u32::MAX,
+    }
+}
+
+fn selected(request: &AccessRequest, mask: u32) -> Vec<String> {
+    match request.regions.len() {
+        n if n <= MAX_REGION_BUTTONS => request
+            .regions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, r)| r.clone())
+            .collect(),
+        _ => request.regions.clone(),
+    }
+}
+
+fn keyboard(request: &AccessRequest, mask: u32, lang: Language) -> InlineKeyboardMarkup {
+    let id = request._id;
+    let mut rows = vec![];
+    if request.regions.len() <= MAX_REGION_BUTTONS {
+        for (i, region) in request.regions.iter().enumerate() {
+            let mark = if mask & (1 << i) != 0 { "✅" } else { "⬜" };
+            rows.push(vec![InlineKeyboardButton::callback(
+                format!("{} {}", mark, region),
+                format!("{}:{}:t:{}", ACCESS_PREFIX, id, mask ^ (1 << i)),
+            )]);
+        }
+    }
+    let approve = match mask == all(request) {
+        true => tr!(lang, "access.approve"),
+        false => tr!(
+            lang,
+            "access.approve_selected",
+            n = selected(request, mask).len()
+        ),
+    };
+    rows.push(vec![
+        InlineKeyboardButton::callback(approve, format!("{}:{}:a:{}", ACCESS_PREFIX, id, mask)),
+        InlineKeyboardButton::callback(
+            tr!(lang, "access.deny"),
+            format!("{}:{}:d:0", ACCESS_PREFIX, id),
+        ),
+    ]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Handles the region toggles and the approve and deny buttons of a request.
+pub async fn callback(
+    cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>,
+    client: &Arc<Client>,
+    data: &str,
+) {
+    let message = match &cx.update.message {
+        Some(m) => m,
+        None => return answer_callback(cx, None).await,
+    };
+    let mut split = data.split(':').skip(1);
+    let id = split.next().and_then(|id| id.parse::<ObjectId>().ok());
+    let op = split.next().unwrap_or_default();
+    let mask = split.next().and_then(|m| m.parse::<u32>().ok());
+    let (id, mask) = match (id, mask) {
+        (Some(id), Some(mask)) => (id, mask),
+        _ => return answer_callback(cx, None).await,
+    };
+
+    let lang = delivery::profile_for(client, cx.update.from.id)
+        .await
+        .language;
+    let admin = User::new(cx.update.from.id, Arc::clone(client));
+    let request = match admin.access_request(id).await {
+        Ok(r) => r,
+        Err(e) => return answer_callback(cx, Some(e.localize(lang).as_str())).await,
+    };
+    let regions = match op {
+        "t" => {
+            let keyboard = keyboard(&request, mask, lang);
+            if let Err(e) = sender::send(message.chat.id, Priority::Interactive, || {
+                cx.requester
+                    .edit_message_reply_markup(message.chat.id, message.id)
+                    .reply_markup(keyboard.clone())
+            })
+            .await
+            {
+                log::error!("Error while editing a keyboard: {}", e);
+            }
+            return answer_callback(cx, None).await;
+        }
+        "a" => match selected(&request, mask) {
+            regions if regions.is_empty() => {
+                return answer_callback(cx, Some(&tr!(lang, "access.nothing_selected"))).await
+            }
+            regions => regions,
+        },
+        _ => vec![],
+    };
+
+    let request = match admin.decide_access(id, regions).await {
+        Ok(r) => r,
+        Err(e) => {
+            answer_callback(cx, None).await;
+            let text = tr!(lang, "fail.decide_access", error = e.localize(lang));
+            return send_text(&cx.requester, message.chat.id, text.as_str()).await;
+        }
+    };
+    answer_callback(cx, None).await;
+    delivery::remove_keyboard(&cx.requester, message).await;
+    notify(&cx.requester, client, &request).await;
+    let text = match request.approved_regions.is_empty() {
+        true => tr!(lang, "access.denied_admin", id = request.user_id),
+        false => tr!(
+            lang,
+            "access.approved_admin",
+            id = request.user_id,
+            regions = request.approved_regions.join(", ")
+        ),
+    };
+    send_text(&cx.requester, message.chat.id, text.as_str()).await;
+}
+
+/// Tells the requester about the decision and updates their command menu.
+async fn notify(bot: &AutoSend<Bot>, client: &Arc<Client>, request: &AccessRequest) {
+    let lang = delivery::profile_for(client, request.user_id)
+        .await
+        .language;
+    if request.approved_regions.is_empty() {
+        let text = tr!(lang, "access.denied");
+        return send_text(bot, request.user_id, text.as_str()).await;
+    }
+    let mut text = tr!(
+        lang,
+        "access.approved",
+        regions = request.approved_regions.join(", ")
+    );
+    let denied = request
+        .regions
+        .iter()
+        .filter(|r| !request.approved_regions.contains(r))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !denied.is_empty() {
+        text += &format!(
+            "\n{}",
+            tr!(lang, "access.not_approved", regions = denied.join(", "))
+        );
+    }
+    send_text(bot, request.user_id, text.as_str()).await;
+    match User::new(request.user_id, Arc::clone(client))
+        .get_group()
+        .await
+    {
+        Ok(group) => commands::set_menu(bot, request.user_id, Some(&group), lang).await,
+        Err(e) => log::error!(
+            "Can't access the group of {}. Error: {}",
+            request.user_id,
+            e
+        ),
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use mongodb::Client;
+use teloxide::prelude::*;
+
+use crate::{access, delivery, listings, private_handlers, sender};
+
+/// Handles inline keyboard presses. Every query is handled in its own task, so a long
+/// delivery started by one button doesn't hold back the others.
+pub async fn handle(rx: DispatcherHandlerRx<AutoSend<Bot>, CallbackQuery>, client: Arc<Client>) {
+    let mut rx = rx;
+    while let Some(cx) = rx.recv().await {
+        let client = Arc::clone(&client);
+        tokio::spawn(async move {
+            let data = cx.update.data.clone().unwrap_or_default();
+            log::info!(
+                "Callback from user id: {}. Data: \"{}\"",
+                cx.update.from.id,
+                data
+            );
+            match data.split(':').next().unwrap_or_default() {
+                delivery::MORE_PREFIX => delivery::more(&cx, &client, &data).await,
+                delivery::STOP_PREFIX => delivery::stop(&cx, &client, &data).await,
+                private_handlers::QUERY_PREFIX => {
+                    private_handlers::confirm_query(&cx, &client, &data).await
+                }
+                private_handlers::DELETE_PREFIX => {
+                    private_handlers::confirm_deletion(&cx, &client, &data).await
+                }
+                access::ACCESS_PREFIX => access::callback(&cx, &client, &data).await,
+                listings::LIST_PREFIX => listings::callback(&cx, &client, &data).await,
+                _ => answer_callback(&cx, None).await,
+            }
+        });
+    }
+}
+
+pub async fn answer_callback(cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>, text: Option<&str>) {
+    let answer = sender::lookup(|| {
+        let answer = cx.requester.answer_callback_query(cx.update.id.clone());
+        match text {
+            Some(text) => answer.text(text),
+            None => answer,
+        }
+    });
+    if let Err(e) = answer.await {
+        log::error!("Error while answering a callback query: {}", e);
+    }
+}
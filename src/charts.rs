@@ -0,0 +1,308 @@
+//! Activity charts, rendered to PNG in-process and sent as photos.
+
+use std::collections::HashMap;
+
+use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+use plotters::coord::{ranged1d::SegmentedCoord, types::RangedCoordusize, Shift};
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+use teloxide::{prelude::*, types::InputFile};
+
+use crate::db_utils::models::DailyActivity;
+use crate::i18n::{tr, Language};
+use crate::sender::{self, Priority};
+
+lazy_static::lazy_static! {
+    /// Registers the font of the labels on first use: the `CHART_FONT` TrueType file if it
+    /// is set, the embedded DejaVu Sans otherwise. The font has to cover Cyrillic.
+    static ref FONT: Result<(), String> = {
+        let bytes: &'static [u8] = match std::env::var("CHART_FONT") {
+            Ok(path) => std::fs::read(&path)
+                .map_err(|e| format!("{}: {}", path, e))?
+                .leak(),
+            Err(_) => EMBEDDED_FONT,
+        };
+        register_font(FONT_FAMILY, FontStyle::Normal, bytes)
+            .map_err(|_| "not a TrueType font".to_string())
+    };
+}
+
+const EMBEDDED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+const FONT_FAMILY: &str = "sans-serif";
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+/// Tags stacked separately in the volume chart, the rest are summed up as other.
+const TOP_TAGS: usize = 9;
+/// Rows of the heatmap, the most active regions.
+const TOP_REGIONS: usize = 30;
+/// Width of the legend next to the volume chart.
+const LEGEND_WIDTH: u32 = 200;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ChartError {
+    #[error("Can't load the chart font: {0}")]
+    Font(String),
+    #[error("Can't draw a chart: {0}")]
+    Drawing(String),
+    #[error("Can't encode a chart: {0}")]
+    Encoding(#[from] image::ImageError),
+    #[error("Chart task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Text drawn on the charts, in the recipient's language.
+struct Labels {
+    volume: String,
+    heatmap: String,
+    untagged: String,
+    other: String,
+}
+
+/// Sends the daily volume chart and the region heatmap of `activity` to `chat_id`.
+pub async fn send_charts(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    activity: DailyActivity,
+    offset: chrono::FixedOffset,
+    lang: Language,
+) {
+    let days = activity.days.len();
+    let labels = Labels {
+        volume: tr!(lang, "charts.volume", days = days, offset = offset),
+        heatmap: tr!(lang, "charts.heatmap", days = days, offset = offset),
+        untagged: tr!(lang, "charts.untagged"),
+        other: tr!(lang, "charts.other"),
+    };
+    let total = activity.tags.iter().map(|(_, _, n)| n).sum::<usize>();
+    let max = activity.regions.iter().map(|(_, _, n)| *n).max();
+    let rendered = tokio::task::spawn_blocking(move || {
+        Ok::<_, ChartError>((volume(&activity, &labels)?, heatmap(&activity, &labels)?))
+    })
+    .await
+    .map_err(ChartError::from)
+    .and_then(|r| r);
+    let (volume, heatmap) = match rendered {
+        Ok(charts) => charts,
+        Err(e) => {
+            log::error!("Can't render charts. Error: {}", e);
+            let text = tr!(lang, "charts.failed");
+            if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+                bot.send_message(chat_id, text.as_str())
+            })
+            .await
+            {
+                log::error!("Error while sending a message: {}", e);
+            }
+            return;
+        }
+    };
+
+    let photos = [
+        (
+            "volume.png",
+            volume,
+            tr!(lang, "charts.volume_caption", total = total),
+        ),
+        (
+            "heatmap.png",
+            heatmap,
+            tr!(
+                lang,
+                "charts.heatmap_caption",
+                max = max.unwrap_or_default()
+            ),
+        ),
+    ];
+    for (file_name, png, caption) in photos {
+        if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+            bot.send_photo(chat_id, InputFile::memory(file_name, png.clone()))
+                .caption(caption.as_str())
+        })
+        .await
+        {
+            log::error!("Error while sending a chart: {}", e);
+        }
+    }
+}
+
+/// Messages per day stacked by first tag, the most used tags at the bottom.
+fn volume(activity: &DailyActivity, labels: &Labels) -> Result<Vec<u8>, ChartError> {
+    let days = &activity.days;
+    let mut tags = by_activity(&activity.tags);
+    let series = match tags.len() > TOP_TAGS {
+        true => TOP_TAGS + 1,
+        false => tags.len(),
+    };
+    tags.truncate(TOP_TAGS);
+    let mut counts = vec![vec![0; days.len()]; series];
+    for (day, tag, n) in &activity.tags {
+        if let Some(d) = days.iter().position(|x| x == day) {
+            let s = tags.iter().position(|t| t == tag).unwrap_or(tags.len());
+            counts[s][d] += n;
+        }
+    }
+    let max = (0..days.len())
+        .map(|d| counts.iter().map(|c| c[d]).sum::<usize>())
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    render(|area| {
+        let (area, legend) = area.split_horizontally(WIDTH - LEGEND_WIDTH);
+        let mut chart = ChartBuilder::on(&area)
+            .caption(&labels.volume, (FONT_FAMILY, 28))
+            .margin(16)
+            .x_label_area_size(40)
+            .y_label_area_size(56)
+            .build_cartesian_2d(segments(days.len()), 0..max + max / 10)
+            .map_err(drawing)?;
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(days.len().min(15))
+            .x_label_formatter(&|v| day_label(days, v))
+            .label_style((FONT_FAMILY, 14))
+            .draw()
+            .map_err(drawing)?;
+
+        let mut base = vec![0; days.len()];
+        for (s, counts) in counts.iter().enumerate() {
+            let color = Palette99::pick(s).to_rgba();
+            let bars = counts
+                .iter()
+                .enumerate()
+                .map(|(d, &n)| {
+                    let bottom = base[d];
+                    base[d] += n;
+                    Rectangle::new(
+                        [
+                            (SegmentValue::Exact(d), bottom),
+                            (SegmentValue::Exact(d + 1), bottom + n),
+                        ],
+                        color.filled(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            chart.draw_series(bars).map_err(drawing)?;
+
+            let label = match tags.get(s) {
+                Some(&"") => labels.untagged.as_str(),
+                Some(tag) => tag,
+                None => labels.other.as_str(),
+            };
+            let y = 60 + 24 * s as i32;
+            legend
+                .draw(&Rectangle::new([(8, y), (22, y + 14)], color.filled()))
+                .map_err(drawing)?;
+            legend
+                .draw(&Text::new(label, (30, y), (FONT_FAMILY, 16)))
+                .map_err(drawing)?;
+        }
+        Ok(())
+    })
+}
+
+/// Messages per region and day, the most active regions at the top.
+fn heatmap(activity: &DailyActivity, labels: &Labels) -> Result<Vec<u8>, ChartError> {
+    let days = &activity.days;
+    let mut regions = by_activity(&activity.regions);
+    regions.truncate(TOP_REGIONS);
+    let rows = regions.len().max(1);
+    let max = activity
+        .regions
+        .iter()
+        .map(|(_, _, n)| *n)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+
+    render(|area| {
+        let mut chart = ChartBuilder::on(area)
+            .caption(&labels.heatmap, (FONT_FAMILY, 28))
+            .margin(16)
+            .x_label_area_size(40)
+            .y_label_area_size(180)
+            .build_cartesian_2d(segments(days.len()), segments(rows))
+            .map_err(drawing)?;
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_labels(days.len().min(15))
+            .x_label_formatter(&|v| day_label(days, v))
+            .y_labels(rows)
+            .y_label_formatter(&|v| match v {
+                SegmentValue::CenterOf(r) => (rows - 1)
+                    .checked_sub(*r)
+                    .and_then(|r| regions.get(r))
+                    .map(|r| r.to_string())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            })
+            .label_style((FONT_FAMILY, 14))
+            .draw()
+            .map_err(drawing)?;
+
+        let cells = activity.regions.iter().filter_map(|(day, region, n)| {
+            let d = days.iter().position(|x| x == day)?;
+            let r = rows - 1 - regions.iter().position(|r| r == region)?;
+            let shade = (255.0 * (1.0 - *n as f64 / max as f64)) as u8;
+            Some(Rectangle::new(
+                [
+                    (SegmentValue::Exact(d), SegmentValue::Exact(r)),
+                    (SegmentValue::Exact(d + 1), SegmentValue::Exact(r + 1)),
+                ],
+                RGBColor(255, shade, shade).filled(),
+            ))
+        });
+        chart.draw_series(cells).map_err(drawing)?;
+        Ok(())
+    })
+}
+
+/// Draws a chart on a white canvas and encodes it as PNG.
+fn render(
+    draw: impl FnOnce(&DrawingArea<BitMapBackend, Shift>) -> Result<(), ChartError>,
+) -> Result<Vec<u8>, ChartError> {
+    FONT.clone().map_err(ChartError::Font)?;
+    let mut buffer = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let area = BitMapBackend::with_buffer(&mut buffer, (WIDTH, HEIGHT)).into_drawing_area();
+        area.fill(&WHITE).map_err(drawing)?;
+        draw(&area)?;
+        area.present().map_err(drawing)?;
+    }
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).write_image(&buffer, WIDTH, HEIGHT, ColorType::Rgb8)?;
+    Ok(png)
+}
+
+/// Distinct values of `counts`, the ones with the most messages first.
+fn by_activity(counts: &[(chrono::NaiveDate, String, usize)]) -> Vec<&str> {
+    let mut totals = HashMap::<&str, usize>::new();
+    for (_, value, count) in counts {
+        *totals.entry(value).or_default() += count;
+    }
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    totals.into_iter().map(|(value, _)| value).collect()
+}
+
+/// `n` segments, `0..n` would make `n + 1` of them. There are at least two, as a single one
+/// has no width.
+fn segments(n: usize) -> SegmentedCoord<RangedCoordusize> {
+    (0..n.max(2) - 1).into_segmented()
+}
+
+fn day_label(days: &[chrono::NaiveDate], value: &SegmentValue<usize>) -> String {
+    match value {
+        SegmentValue::CenterOf(d) => days
+            .get(*d)
+            .map(|d| d.format("%d.%m").to_string())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn drawing(e: impl std::error::Error) -> ChartError {
+    ChartError::Drawing(e.to_string())
+}
@@ -0,0 +1,233 @@
+//! Registry of the chats whose messages are saved. It owns both the `chats` collection and
+//! the in-memory copy, so the two change together.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use mongodb::{error::Result as DbResult, Client};
+use teloxide::{prelude::*, types::ChatMemberUpdated};
+use tokio::sync::RwLock;
+
+use crate::commands::ChatSetting;
+use crate::db_utils::{
+    self,
+    error::{Error, Result},
+    models::{Chat, ChatSettings, Permission},
+    user::User,
+};
+use crate::i18n::{tr, Language, Localize, CHAT_LANGUAGE};
+use crate::sender::{self, Priority};
+
+lazy_static::lazy_static! {
+    pub static ref CHATS: ChatRegistry = ChatRegistry::default();
+}
+
+#[derive(Default)]
+pub struct ChatRegistry {
+    chats: RwLock<HashMap<i64, Chat>>,
+}
+
+impl ChatRegistry {
+    /// Replaces the cached chats with the ones in the database.
+    pub async fn load(&self, client: &Client) -> DbResult<()> {
+        let chats = db_utils::get_chats(client).await?;
+        *self.chats.write().await = chats.into_iter().map(|c| (c.id, c)).collect();
+        Ok(())
+    }
+
+    pub async fn contains(&self, id: i64) -> bool {
+        self.chats.read().await.contains_key(&id)
+    }
+
+    pub async fn title(&self, id: i64) -> Option<String> {
+        self.chats
+            .read()
+            .await
+            .get(&id)
+            .and_then(|c| c.title.clone())
+    }
+
+    /// The language set for chat `id`, `CHAT_LANGUAGE` if none is.
+    pub async fn language(&self, id: i64) -> Language {
+        self.chats
+            .read()
+            .await
+            .get(&id)
+            .and_then(|c| c.settings.language)
+            .unwrap_or(*CHAT_LANGUAGE)
+    }
+
+    /// Settings of chat `id`, the default ones for an unregistered chat.
+    pub async fn settings(&self, id: i64) -> ChatSettings {
+        self.chats
+            .read()
+            .await
+            .get(&id)
+            .map(|c| c.settings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Changes the settings of chat `id` on behalf of `user`, see [`User::set_chat_settings`].
+    pub async fn set_settings(&self, user: &User, id: i64, settings: ChatSettings) -> Result<()> {
+        let mut chats = self.chats.write().await;
+        user.set_chat_settings(id, &settings).await?;
+        if let Some(chat) = chats.get_mut(&id) {
+            chat.settings = settings;
+        }
+        Ok(())
+    }
+
+    /// Adds `chat` on behalf of `user`, see [`User::add_chat`].
+    pub async fn add(&self, user: &User, chat: Chat) -> Result<()> {
+        let mut chats = self.chats.write().await;
+        user.add_chat(chat.clone()).await?;
+        chats.insert(chat.id, chat);
+        Ok(())
+    }
+
+    /// Deletes chat `id` on behalf of `user`, see [`User::delete_chat`].
+    pub async fn delete(&self, user: &User, id: i64) -> Result<()> {
+        let mut chats = self.chats.write().await;
+        user.delete_chat(id).await?;
+        chats.remove(&id);
+        Ok(())
+    }
+
+    /// Moves a group that became a supergroup, with its messages, to the new id.
+    pub async fn migrate(&self, client: &Client, from: i64, to: i64) -> DbResult<()> {
+        let mut chats = self.chats.write().await;
+        db_utils::migrate_chat(client, from, to).await?;
+        if let Some(mut chat) = chats.remove(&from) {
+            chat.id = to;
+            chats.insert(to, chat);
+        }
+        Ok(())
+    }
+
+    /// Forgets chat `id` after the bot left it. Returns the removed chat.
+    pub async fn remove(&self, client: &Client, id: i64) -> DbResult<Option<Chat>> {
+        let mut chats = self.chats.write().await;
+        if !chats.contains_key(&id) {
+            return Ok(None);
+        }
+        db_utils::delete_chat(client, id).await?;
+        Ok(chats.remove(&id))
+    }
+}
+
+/// Applies `setting` to chat `id` on behalf of `user`. Returns the reply, in `lang`.
+pub async fn change_settings(user: &User, id: i64, setting: ChatSetting, lang: Language) -> String {
+    let chat = CHATS.chats.read().await.get(&id).cloned();
+    let mut chat = match chat {
+        Some(chat) => chat,
+        None => return Error::NoChat(id).localize(lang),
+    };
+    let settings = &mut chat.settings;
+    let changed = match setting {
+        ChatSetting::Show => false,
+        ChatSetting::Regions(regions) => {
+            settings.default_regions = regions;
+            true
+        }
+        ChatSetting::Tags(tags) => {
+            settings.default_tags = tags;
+            true
+        }
+        ChatSetting::Language(language) => {
+            settings.language = language;
+            true
+        }
+    };
+    let r = match changed {
+        true => CHATS.set_settings(user, id, chat.settings.clone()).await,
+        false => user.require(Permission::ManageChats).await.map(|_| ()),
+    };
+    match r {
+        Ok(()) => format_settings(&chat, lang),
+        Err(e) => tr!(lang, "fail.chat_settings", error = e.localize(lang)),
+    }
+}
+
+fn format_settings(chat: &Chat, lang: Language) -> String {
+    let list = |values: &[String]| match values.is_empty() {
+        true => tr!(lang, "list.none"),
+        false => values.join(", "),
+    };
+    tr!(
+        lang,
+        "chat.settings",
+        title = chat.title.as_deref().unwrap_or_default(),
+        id = chat.id,
+        regions = list(&chat.settings.default_regions),
+        tags = list(&chat.settings.default_tags),
+        language = chat.settings.language.unwrap_or(*CHAT_LANGUAGE)
+    )
+}
+
+/// Handles changes of the bot's own membership: a registered chat the bot was removed from
+/// is forgotten and everyone who manages chats is told about it.
+pub async fn handle_membership(
+    rx: DispatcherHandlerRx<AutoSend<Bot>, ChatMemberUpdated>,
+    client: Arc<Client>,
+) {
+    let mut rx = rx;
+    while let Some(cx) = rx.recv().await {
+        let update = &cx.update;
+        if update.new_chat_member.kind.is_present() {
+            continue;
+        }
+        let chat = match CHATS.remove(&client, update.chat.id).await {
+            Ok(Some(chat)) => chat,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!("Can't remove chat {}. Error: {}", update.chat.id, e);
+                continue;
+            }
+        };
+        log::info!(
+            "Bot was removed from chat {} by user {}",
+            chat.id,
+            update.from.id
+        );
+        let title = chat.title.unwrap_or_default();
+        let name = update.from.full_name();
+        notify_managers(&cx.requester, &client, |lang| {
+            tr!(
+                lang,
+                "chat.bot_removed",
+                title = title,
+                id = chat.id,
+                name = name,
+                by = update.from.id
+            )
+        })
+        .await;
+    }
+}
+
+/// Sends everyone who may manage chats a message made by `text` in their language.
+pub async fn notify_managers(
+    bot: &AutoSend<Bot>,
+    client: &Client,
+    text: impl Fn(Language) -> String,
+) {
+    let users = match db_utils::list_users(client, vec![]).await {
+        Ok(users) => users.collect::<Vec<_>>().await,
+        Err(e) => return log::error!("Can't access users. Error: {}", e),
+    };
+    for admin in users
+        .into_iter()
+        .flatten()
+        .filter(|u| u.group.has(Permission::ManageChats))
+    {
+        let text = text(admin.profile.language);
+        if let Err(e) = sender::send(admin.id, Priority::Interactive, || {
+            bot.send_message(admin.id, text.as_str())
+        })
+        .await
+        {
+            log::error!("Can't notify {} about a chat. Error: {}", admin.id, e);
+        }
+    }
+}
@@ -0,0 +1,1163 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use futures::StreamExt;
+use mongodb::Client;
+use strum::IntoEnumIterator;
+use teloxide::{
+    prelude::*,
+    types::{BotCommand, BotCommandScope, ChatId},
+};
+
+use crate::common::{extract_regions, extract_tags, parse_offset, Regions, Tags};
+use crate::db_utils::{
+    self,
+    models::{
+        AuditFilter, Delivery, DeliveryMode, Language, Permission, QuietHours, Schedule, UserGroup,
+    },
+};
+use crate::error::{CommandError, Error};
+use crate::export::ExportFormat;
+use crate::i18n::{self, tr};
+use crate::listings::ListFilter;
+use crate::scheduler;
+use crate::sender::{self, Priority};
+use crate::ALLIAS_REGIONS;
+
+/// Usage and description of a command are the `usage.<name>` and `cmd.<name>` messages.
+struct Spec {
+    name: &'static str,
+    /// The permission the command needs, `None` for everyone.
+    permission: Option<Permission>,
+}
+
+const COMMANDS: &[Spec] = &[
+    Spec {
+        name: "start",
+        permission: None,
+    },
+    Spec {
+        name: "help",
+        permission: None,
+    },
+    Spec {
+        name: "request",
+        permission: None,
+    },
+    Spec {
+        name: "invite",
+        permission: Some(Permission::ManageRegionUsers),
+    },
+    Spec {
+        name: "list_users",
+        permission: Some(Permission::ManageRegionUsers),
+    },
+    Spec {
+        name: "add_user",
+        permission: Some(Permission::ManageRegionUsers),
+    },
+    Spec {
+        name: "del_user",
+        permission: Some(Permission::ManageUsers),
+    },
+    Spec {
+        name: "list_chats",
+        permission: Some(Permission::ManageChats),
+    },
+    Spec {
+        name: "add_chat",
+        permission: Some(Permission::ManageChats),
+    },
+    Spec {
+        name: "del_chat",
+        permission: Some(Permission::ManageChats),
+    },
+    Spec {
+        name: "chat_settings",
+        permission: Some(Permission::ManageChats),
+    },
+    Spec {
+        name: "listdb",
+        permission: Some(Permission::ManageMessages),
+    },
+    Spec {
+        name: "deldb",
+        permission: Some(Permission::ManageMessages),
+    },
+    Spec {
+        name: "cleandb",
+        permission: Some(Permission::ManageMessages),
+    },
+    Spec {
+        name: "trash",
+        permission: Some(Permission::ManageMessages),
+    },
+    Spec {
+        name: "restore",
+        permission: Some(Permission::ManageMessages),
+    },
+    Spec {
+        name: "statdb",
+        permission: Some(Permission::ViewStats),
+    },
+    Spec {
+        name: "editors",
+        permission: Some(Permission::ViewStats),
+    },
+    Spec {
+        name: "charts",
+        permission: Some(Permission::ViewStats),
+    },
+    Spec {
+        name: "add_user_regions",
+        permission: Some(Permission::ManageRegionUsers),
+    },
+    Spec {
+        name: "del_user_regions",
+        permission: Some(Permission::ManageRegionUsers),
+    },
+    Spec {
+        name: "add_admin_regions",
+        permission: Some(Permission::ManageUsers),
+    },
+    Spec {
+        name: "del_admin_regions",
+        permission: Some(Permission::ManageUsers),
+    },
+    Spec {
+        name: "audit",
+        permission: Some(Permission::ViewAudit),
+    },
+    Spec {
+        name: "subscribe",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "subscriptions",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "unsubscribe",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "pause",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "resume",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "quiet",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "add_digest",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "digests",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "del_digest",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "count",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "export",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "delivery",
+        permission: Some(Permission::Query),
+    },
+    Spec {
+        name: "profile",
+        permission: Some(Permission::Query),
+    },
+];
+
+pub enum DeliverySetting {
+    Show,
+    Default,
+    Set(Delivery),
+}
+
+pub enum ProfileSetting {
+    Show,
+    Timezone(FixedOffset),
+    Language(Language),
+    /// Default query regions, empty to turn them off.
+    Regions(Vec<String>),
+}
+
+/// A change of chat settings with `/chat_settings` or with `/settings` in the chat.
+pub enum ChatSetting {
+    Show,
+    /// Default finalize regions, empty to turn them off.
+    Regions(Vec<String>),
+    /// Default finalize tags, empty to turn them off.
+    Tags(Vec<String>),
+    /// `None` for the deployment's chat language.
+    Language(Option<Language>),
+}
+
+impl ChatSetting {
+    /// Parses the arguments of `/settings` sent in a chat.
+    pub fn parse(rest: &str) -> Result<Self, CommandError> {
+        let mut args = Args {
+            rest,
+            command: "settings",
+            offset: FixedOffset::east_opt(0).expect("Zero offset is valid"),
+        };
+        let setting = chat_setting(&mut args)?;
+        args.finish()?;
+        Ok(setting)
+    }
+}
+
+pub enum Command {
+    /// `/start`, with an invite token when opened from an invite link.
+    Start(Option<String>),
+    Help,
+    Request {
+        regions: Vec<String>,
+        reason: String,
+    },
+    Invite {
+        group: UserGroup,
+        regions: Vec<String>,
+        uses: u32,
+        term: Option<Duration>,
+    },
+    ListUsers(ListFilter),
+    AddUser {
+        id: i64,
+        group: UserGroup,
+        /// Registration term, `None` for a permanent one.
+        term: Option<Duration>,
+    },
+    DelUser(i64),
+    AddUserRegions {
+        id: i64,
+        term: Option<Duration>,
+        regions: Vec<String>,
+    },
+    DelUserRegions {
+        id: i64,
+        regions: Vec<String>,
+    },
+    AddAdminRegions {
+        id: i64,
+        regions: Vec<String>,
+    },
+    DelAdminRegions {
+        id: i64,
+        regions: Vec<String>,
+    },
+    ListChats(ListFilter),
+    AddChat(i64),
+    DelChat(i64),
+    ChatSettings {
+        id: i64,
+        setting: ChatSetting,
+    },
+    ListDb {
+        date: Option<NaiveDate>,
+        offset: FixedOffset,
+        format: Option<ExportFormat>,
+    },
+    DelDb(ObjectId),
+    CleanDb(u32),
+    Trash,
+    Restore(ObjectId),
+    StatDb {
+        days: u32,
+        offset: FixedOffset,
+    },
+    Editors {
+        days: u32,
+        offset: FixedOffset,
+    },
+    Charts {
+        days: u32,
+        offset: FixedOffset,
+    },
+    Audit(AuditFilter),
+    Subscribe {
+        regions: Vec<String>,
+        tags: Vec<String>,
+    },
+    Subscriptions,
+    Unsubscribe(usize),
+    Pause(Option<usize>),
+    Resume(Option<usize>),
+    Quiet {
+        n: usize,
+        quiet_hours: Option<QuietHours>,
+    },
+    AddDigest {
+        schedule: Schedule,
+        regions: Vec<String>,
+        tags: Vec<String>,
+    },
+    Digests,
+    DelDigest(usize),
+    Delivery(DeliverySetting),
+    Profile(ProfileSetting),
+    Count(String),
+    Export {
+        format: ExportFormat,
+        query: String,
+    },
+}
+
+impl Command {
+    /// Parses a `/command args` message. Returns `None` for text that isn't a command.
+    /// Dates and times without an explicit offset are in the `offset` timezone.
+    pub fn parse(text: &str, offset: FixedOffset) -> Option<Result<Self, CommandError>> {
+        let text = text.strip_prefix('/')?;
+        let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let name = name.split('@').next().unwrap_or_default();
+        let spec = match COMMANDS.iter().find(|s| s.name == name) {
+            Some(spec) => spec,
+            None => return Some(Err(CommandError::Unknown(name.into()))),
+        };
+        Some(Self::parse_args(
+            name,
+            Args {
+                rest,
+                command: spec.name,
+                offset,
+            },
+        ))
+    }
+
+    fn parse_args(name: &str, mut args: Args) -> Result<Self, CommandError> {
+        let command = match name {
+            "start" => Self::Start(args.next().map(String::from)),
+            "help" => Self::Help,
+            "request" => {
+                let (regions, reason) = regions_and_reason(args.required_rest("regions")?)?;
+                match reason {
+                    "" => {
+                        return Err(CommandError::MissingArgument {
+                            argument: "reason",
+                            command: args.command,
+                        })
+                    }
+                    reason => Self::Request {
+                        regions,
+                        reason: reason.into(),
+                    },
+                }
+            }
+            "invite" => {
+                let group = args
+                    .optional_if(|g| g.parse().ok().filter(|g| *g != UserGroup::Unregistered))
+                    .unwrap_or(UserGroup::Registered);
+                let mut words = args
+                    .required_rest("regions")?
+                    .split_whitespace()
+                    .collect::<Vec<_>>();
+                let term = words.last().and_then(|w| parse_term(w));
+                if term.is_some() {
+                    words.pop();
+                }
+                let uses = match words.last().and_then(|w| w.parse::<u32>().ok()) {
+                    Some(0) => return Err(args.bad("uses", "0")),
+                    Some(uses) => {
+                        words.pop();
+                        uses
+                    }
+                    None => 1,
+                };
+                Self::Invite {
+                    group,
+                    regions: regions(&words.join(" "))?,
+                    uses,
+                    term,
+                }
+            }
+            "list_users" => Self::ListUsers(list_filter(&mut args, true)?),
+            "add_user" => {
+                let id = args.id()?;
+                let group = match args.peek() {
+                    None => UserGroup::Registered,
+                    Some(term) if parse_term(term).is_some() => UserGroup::Registered,
+                    Some("Unregistered") => return Err(args.bad("role", "Unregistered")),
+                    Some(value) => {
+                        args.next();
+                        value.parse().map_err(|_| args.bad("role", value))?
+                    }
+                };
+                let term = args.optional_if(parse_term);
+                Self::AddUser { id, group, term }
+            }
+            "del_user" => Self::DelUser(args.id()?),
+            "add_user_regions" => Self::AddUserRegions {
+                id: args.id()?,
+                term: args.optional_if(parse_term),
+                regions: regions(args.required_rest("regions")?)?,
+            },
+            "del_user_regions" => Self::DelUserRegions {
+                id: args.id()?,
+                regions: regions(args.required_rest("regions")?)?,
+            },
+            "add_admin_regions" => Self::AddAdminRegions {
+                id: args.id()?,
+                regions: regions(args.required_rest("regions")?)?,
+            },
+            "del_admin_regions" => Self::DelAdminRegions {
+                id: args.id()?,
+                regions: regions(args.required_rest("regions")?)?,
+            },
+            "list_chats" => Self::ListChats(list_filter(&mut args, false)?),
+            "add_chat" => Self::AddChat(args.id()?),
+            "del_chat" => Self::DelChat(args.id()?),
+            "chat_settings" => Self::ChatSettings {
+                id: args.id()?,
+                setting: chat_setting(&mut args)?,
+            },
+            "listdb" => {
+                let date = args.optional_if(|d| NaiveDate::parse_from_str(d, "%d.%m.%y").ok());
+                let offset = args.offset()?;
+                let format = args.optional("json|csv|html")?;
+                Self::ListDb {
+                    date,
+                    offset,
+                    format,
+                }
+            }
+            "deldb" => Self::DelDb(args.required("id")?),
+            "cleandb" => Self::CleanDb(args.required("days_to_keep")?),
+            "trash" => Self::Trash,
+            "restore" => Self::Restore(args.required("batch_id")?),
+            "statdb" => Self::StatDb {
+                days: args.days()?,
+                offset: args.offset()?,
+            },
+            "editors" => Self::Editors {
+                days: args.days()?,
+                offset: args.offset()?,
+            },
+            "charts" => Self::Charts {
+                days: args.days_up_to(30, 90)?,
+                offset: args.offset()?,
+            },
+            "audit" => {
+                let mut filter = AuditFilter::default();
+                while let Some(token) = args.next() {
+                    let bad = || args.bad("actor=|target=|from=|to=", token);
+                    let (key, value) = token.split_once('=').ok_or_else(bad)?;
+                    let day = || {
+                        NaiveDate::parse_from_str(value, "%d.%m.%y")
+                            .ok()
+                            .and_then(|d| day_start(d, args.offset))
+                            .ok_or_else(bad)
+                    };
+                    match key {
+                        "actor" => filter.actor = Some(value.parse().map_err(|_| bad())?),
+                        "target" => filter.target = Some(value.parse().map_err(|_| bad())?),
+                        "from" => filter.after = Some(day()?),
+                        "to" => filter.before = Some(day()? + Duration::days(1)),
+                        _ => return Err(bad()),
+                    }
+                }
+                Self::Audit(filter)
+            }
+            "subscribe" => {
+                let (regions, tags) = regions_and_tags(args.required_rest("regions")?)?;
+                Self::Subscribe { regions, tags }
+            }
+            "subscriptions" => Self::Subscriptions,
+            "unsubscribe" => Self::Unsubscribe(args.required("number")?),
+            "pause" => Self::Pause(args.optional("number")?),
+            "resume" => Self::Resume(args.optional("number")?),
+            "quiet" => {
+                let n = args.required("number")?;
+                let period = args.next_required("quiet_hours")?;
+                let offset = args.offset()?;
+                let quiet_hours = match period {
+                    "off" => None,
+                    period => Some(
+                        parse_quiet_hours(period, offset)
+                            .ok_or_else(|| args.bad("quiet_hours", period))?,
+                    ),
+                };
+                Self::Quiet { n, quiet_hours }
+            }
+            "add_digest" => {
+                let times = args.next_required("time")?;
+                let days = args.optional_if(|d| scheduler::parse_weekdays(d).map(|_| d));
+                let offset = args.offset()?;
+                let schedule = scheduler::parse_schedule(times, days, offset)
+                    .ok_or_else(|| args.bad("time", times))?;
+                let (regions, tags) = regions_and_tags(args.required_rest("regions")?)?;
+                Self::AddDigest {
+                    schedule,
+                    regions,
+                    tags,
+                }
+            }
+            "digests" => Self::Digests,
+            "del_digest" => Self::DelDigest(args.required("number")?),
+            "delivery" => Self::Delivery(match args.next() {
+                None => DeliverySetting::Show,
+                Some("default") => DeliverySetting::Default,
+                Some(mode) => {
+                    let mode = mode
+                        .parse()
+                        .map_err(|_| args.bad("forward|copy|default", mode))?;
+                    let attribution = match args.next() {
+                        None => false,
+                        Some("footer") if mode == DeliveryMode::Copy => true,
+                        Some(value) => return Err(args.bad("footer", value)),
+                    };
+                    DeliverySetting::Set(Delivery { mode, attribution })
+                }
+            }),
+            "profile" => Self::Profile(match args.next() {
+                None => ProfileSetting::Show,
+                Some("tz") => {
+                    let tz = args.next_required("+HH:MM")?;
+                    ProfileSetting::Timezone(
+                        parse_offset(tz).ok_or_else(|| args.bad("+HH:MM", tz))?,
+                    )
+                }
+                Some("lang") => ProfileSetting::Language(args.required("ru|en")?),
+                Some("regions") => match args.required_rest("regions")? {
+                    "off" => ProfileSetting::Regions(vec![]),
+                    rest => ProfileSetting::Regions(regions(rest)?),
+                },
+                Some(value) => return Err(args.bad("tz|lang|regions", value)),
+            }),
+            "count" => Self::Count(args.required_rest("query")?.to_string()),
+            "export" => Self::Export {
+                format: args.required("json|csv|html")?,
+                query: args.required_rest("query")?.to_string(),
+            },
+            _ => return Err(CommandError::Unknown(name.into())),
+        };
+        args.finish()?;
+        Ok(command)
+    }
+}
+
+/// Arguments of a command, taken token by token.
+struct Args<'t> {
+    rest: &'t str,
+    command: &'static str,
+    /// The user's timezone.
+    offset: FixedOffset,
+}
+
+impl<'t> Args<'t> {
+    fn peek(&self) -> Option<&'t str> {
+        self.rest.split_whitespace().next()
+    }
+
+    fn next(&mut self) -> Option<&'t str> {
+        let rest = self.rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (token, rest) = rest.split_at(end);
+        self.rest = rest;
+        (!token.is_empty()).then_some(token)
+    }
+
+    fn next_required(&mut self, argument: &'static str) -> Result<&'t str, CommandError> {
+        self.next().ok_or(CommandError::MissingArgument {
+            argument,
+            command: self.command,
+        })
+    }
+
+    fn required<T: FromStr>(&mut self, argument: &'static str) -> Result<T, CommandError> {
+        let value = self.next_required(argument)?;
+        value.parse().map_err(|_| self.bad(argument, value))
+    }
+
+    fn optional<T: FromStr>(&mut self, argument: &'static str) -> Result<Option<T>, CommandError> {
+        match self.next() {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| self.bad(argument, value)),
+            None => Ok(None),
+        }
+    }
+
+    /// Takes the next token only if `parse` accepts it, so optional arguments can be skipped.
+    fn optional_if<T>(&mut self, parse: impl Fn(&'t str) -> Option<T>) -> Option<T> {
+        let value = self.peek().and_then(parse);
+        if value.is_some() {
+            self.next();
+        }
+        value
+    }
+
+    fn id(&mut self) -> Result<i64, CommandError> {
+        match self.required("id")? {
+            0 => Err(self.bad("id", "0")),
+            id => Ok(id),
+        }
+    }
+
+    /// An optional `+HH:MM` offset, the user's timezone by default.
+    fn offset(&mut self) -> Result<FixedOffset, CommandError> {
+        match self.peek() {
+            Some(o) if o.starts_with(['+', '-']) => {
+                self.next();
+                parse_offset(o).ok_or_else(|| self.bad("OFFSET", o))
+            }
+            _ => Ok(self.offset),
+        }
+    }
+
+    /// An optional number of days, a week by default.
+    fn days(&mut self) -> Result<u32, CommandError> {
+        self.days_up_to(7, u32::MAX)
+    }
+
+    /// An optional number of days, at most `max` and `default` if omitted.
+    fn days_up_to(&mut self, default: u32, max: u32) -> Result<u32, CommandError> {
+        match self.optional_if(|d| d.parse::<u32>().ok()) {
+            Some(days) if days == 0 || days > max => Err(self.bad("days", &days.to_string())),
+            days => Ok(days.unwrap_or(default)),
+        }
+    }
+
+    fn required_rest(&mut self, argument: &'static str) -> Result<&'t str, CommandError> {
+        match std::mem::take(&mut self.rest).trim() {
+            "" => Err(CommandError::MissingArgument {
+                argument,
+                command: self.command,
+            }),
+            rest => Ok(rest),
+        }
+    }
+
+    fn bad(&self, argument: &'static str, value: &str) -> CommandError {
+        CommandError::BadArgument {
+            argument,
+            value: value.into(),
+            command: self.command,
+        }
+    }
+
+    fn finish(self) -> Result<(), CommandError> {
+        match self.rest.trim() {
+            "" => Ok(()),
+            value => Err(CommandError::ExtraArguments {
+                value: value.into(),
+                command: self.command,
+            }),
+        }
+    }
+}
+
+/// A term like `12ч`, `30д` or `2н` (also `h`, `d` and `w`).
+fn parse_term(term: &str) -> Option<Duration> {
+    let unit = term.chars().last()?;
+    let n = term
+        .strip_suffix(unit)?
+        .parse::<u32>()
+        .ok()
+        .filter(|&n| n > 0)? as i64;
+    match unit {
+        'ч' | 'h' => Some(Duration::hours(n)),
+        'д' | 'd' => Some(Duration::days(n)),
+        'н' | 'w' => Some(Duration::weeks(n)),
+        _ => None,
+    }
+}
+
+/// Start of `date` in the `offset` timezone.
+fn day_start(date: NaiveDate, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    let start = offset
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .single()?;
+    Some(start.with_timezone(&Utc))
+}
+
+fn regions(regions: &str) -> Result<Vec<String>, CommandError> {
+    match extract_regions(regions) {
+        Regions::Regions(r) | Regions::Country(r) if r.is_empty() => Err(Error::NoRegions.into()),
+        Regions::Regions(r) | Regions::Country(r) => Ok(r.iter().map(|&s| s.into()).collect()),
+        Regions::BadRegion { region, matches } => Err(Error::BadRegion {
+            region: region.into(),
+            matches,
+        }
+        .into()),
+    }
+}
+
+fn chat_setting(args: &mut Args) -> Result<ChatSetting, CommandError> {
+    Ok(match args.next() {
+        None => ChatSetting::Show,
+        Some("regions") => match args.required_rest("regions")? {
+            "off" => ChatSetting::Regions(vec![]),
+            rest => ChatSetting::Regions(regions(rest)?),
+        },
+        Some("tags") => match args.required_rest("tags")? {
+            "off" => ChatSetting::Tags(vec![]),
+            rest => match extract_tags(rest) {
+                Tags::Tags(t) => ChatSetting::Tags(t.iter().map(|&s| s.into()).collect()),
+                Tags::BadTag(t) => return Err(Error::BadTag(t.into()).into()),
+            },
+        },
+        Some("lang") => match args.next_required("ru|en")? {
+            "default" => ChatSetting::Language(None),
+            lang => ChatSetting::Language(Some(lang.parse().map_err(|_| args.bad("ru|en", lang))?)),
+        },
+        Some(value) => return Err(args.bad("regions|tags|lang", value)),
+    })
+}
+
+/// Parses `key=value` filters of `/list_users` and `/list_chats`. Chats have no group.
+fn list_filter(args: &mut Args, with_group: bool) -> Result<ListFilter, CommandError> {
+    let expected = match with_group {
+        true => "group=|region=|name=",
+        false => "region=|name=",
+    };
+    let mut filter = ListFilter::default();
+    while let Some(token) = args.next() {
+        let bad = || args.bad(expected, token);
+        let (key, value) = token.split_once('=').ok_or_else(bad)?;
+        match key {
+            "group" if with_group => filter.group = Some(value.parse().map_err(|_| bad())?),
+            "region" => match regions(value)?.as_slice() {
+                [region] => filter.region = Some(region.clone()),
+                _ => return Err(bad()),
+            },
+            "name" if !value.is_empty() => filter.name = Some(value.into()),
+            _ => return Err(bad()),
+        }
+    }
+    Ok(filter)
+}
+
+/// Splits `регионы причина`: regions are the leading words that are exactly region names.
+fn regions_and_reason(args: &str) -> Result<(Vec<String>, &str), CommandError> {
+    let mut regions = Vec::new();
+    let mut rest = args;
+    while let Some(word) = rest.split_whitespace().next() {
+        let word_regions = match extract_regions(word) {
+            Regions::Country(r) => r,
+            _ => match ALLIAS_REGIONS
+                .read()
+                .unwrap()
+                .get(word.to_lowercase().as_str())
+            {
+                Some(&region) => vec![region],
+                None => break,
+            },
+        };
+        regions.extend(word_regions.into_iter().map(String::from));
+        rest = rest.trim_start()[word.len()..].trim_start();
+    }
+    match regions.is_empty() {
+        true => Err(Error::NoRegions.into()),
+        false => Ok((regions, rest)),
+    }
+}
+
+/// Splits `регионы [теги]`: regions are words, tags are single letters.
+fn regions_and_tags(args: &str) -> Result<(Vec<String>, Vec<String>), CommandError> {
+    let split = args
+        .split_whitespace()
+        .position(|t| t.chars().count() < 2)
+        .unwrap_or(usize::MAX);
+    let words = args.split_whitespace();
+    let regions = regions(&words.clone().take(split).collect::<Vec<_>>().join(" "))?;
+    let tags = match extract_tags(&words.skip(split).collect::<Vec<_>>().join(" ")) {
+        Tags::Tags(t) => t.iter().map(|&s| s.into()).collect(),
+        Tags::BadTag(t) => return Err(Error::BadTag(t.into()).into()),
+    };
+    Ok((regions, tags))
+}
+
+/// Parses quiet hours in the `ЧЧ[:ММ]-ЧЧ[:ММ]` form.
+fn parse_quiet_hours(period: &str, offset: FixedOffset) -> Option<QuietHours> {
+    let parse = |t: &str| {
+        let mut split = t.split(':');
+        let hours = split.next()?.parse::<u32>().ok()?;
+        let minutes = split.next().unwrap_or("0").parse::<u32>().ok()?;
+        (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+    };
+    let (from, to) = period.split_once('-')?;
+    let (from, to) = (parse(from)?, parse(to)?);
+    (from != to).then_some(QuietHours {
+        from,
+        to,
+        offset: offset.local_minus_utc(),
+    })
+}
+
+fn allowed(spec: &Spec, group: &UserGroup) -> bool {
+    spec.permission.is_none_or(|p| group.has(p))
+}
+
+/// Usage line of `command` in `lang`.
+pub fn usage(lang: Language, command: &str) -> String {
+    tr!(lang, &format!("usage.{}", command))
+}
+
+/// Name of a command argument in `lang`. Literal syntax like `+HH:MM` is shown as is.
+pub fn argument(lang: Language, argument: &str) -> String {
+    i18n::lookup(lang, &format!("arg.{}", argument))
+        .unwrap_or(argument)
+        .to_string()
+}
+
+/// Usage lines of the commands available to `group`.
+pub fn help(group: &UserGroup, lang: Language) -> String {
+    let mut lines = COMMANDS
+        .iter()
+        .filter(|s| s.permission.is_some() && allowed(s, group))
+        .map(|s| usage(lang, s.name))
+        .collect::<Vec<_>>();
+    lines.push(tr!(lang, "help.query"));
+    lines.join("\n")
+}
+
+fn menu(group: &UserGroup, lang: Language) -> Vec<BotCommand> {
+    COMMANDS
+        .iter()
+        .filter(|s| allowed(s, group))
+        .map(|s| BotCommand::new(s.name, tr!(lang, &format!("cmd.{}", s.name))))
+        .collect()
+}
+
+/// Sets the default menus and a menu for every known user. The requests go through
+/// [`sender`] as bulk ones, so this is meant to run in its own task next to the dispatcher.
+pub async fn register_menus(bot: AutoSend<Bot>, client: Arc<Client>) {
+    let unregistered = &UserGroup::Unregistered;
+    for lang in std::iter::once(None).chain(Language::iter().map(Some)) {
+        let r = sender::send(sender::NO_CHAT, Priority::Bulk, || {
+            let request = bot.set_my_commands(menu(unregistered, lang.unwrap_or_default()));
+            match lang {
+                Some(lang) => request.language_code(lang.to_string()),
+                None => request,
+            }
+        })
+        .await;
+        if let Err(e) = r {
+            log::error!("Can't set default commands. Error: {}", e);
+        }
+    }
+    for lang in std::iter::once(None).chain(Language::iter().map(Some)) {
+        let r = sender::send(sender::NO_CHAT, Priority::Bulk, || {
+            let request = bot
+                .set_my_commands(group_menu(lang.unwrap_or_default()))
+                .scope(BotCommandScope::AllChatAdministrators);
+            match lang {
+                Some(lang) => request.language_code(lang.to_string()),
+                None => request,
+            }
+        })
+        .await;
+        if let Err(e) = r {
+            log::error!("Can't set commands of group admins. Error: {}", e);
+        }
+    }
+    let users = match db_utils::list_users(&client, vec![]).await {
+        Ok(users) => users.collect::<Vec<_>>().await,
+        Err(e) => return log::error!("Can't access users. Error: {}", e),
+    };
+    for user in users.into_iter().flatten() {
+        let (group, lang) = (Some(&user.group), user.profile.language);
+        apply_menu(&bot, user.id, group, lang, Priority::Bulk).await;
+    }
+    log::info!("Menus are registered");
+}
+
+/// Commands shown to group admins, handled by [`crate::group_handlers`].
+fn group_menu(lang: Language) -> Vec<BotCommand> {
+    vec![
+        BotCommand::new("register", tr!(lang, "cmd.register")),
+        BotCommand::new("settings", tr!(lang, "cmd.settings")),
+    ]
+}
+
+/// Sets the menu of a private chat for `group`. `None` falls back to the default menu.
+pub async fn set_menu(
+    bot: &AutoSend<Bot>,
+    user_id: i64,
+    group: Option<&UserGroup>,
+    lang: Language,
+) {
+    apply_menu(bot, user_id, group, lang, Priority::Interactive).await;
+}
+
+async fn apply_menu(
+    bot: &AutoSend<Bot>,
+    user_id: i64,
+    group: Option<&UserGroup>,
+    lang: Language,
+    priority: Priority,
+) {
+    let scope = BotCommandScope::Chat(ChatId::Id(user_id));
+    let r = sender::send(user_id, priority, || async {
+        match group {
+            Some(group) => bot
+                .set_my_commands(menu(group, lang))
+                .scope(scope.clone())
+                .await
+                .map(|_| ()),
+            None => bot
+                .delete_my_commands()
+                .scope(scope.clone())
+                .await
+                .map(|_| ()),
+        }
+    })
+    .await;
+    if let Err(e) = r {
+        log::error!("Can't set commands for {}. Error: {}", user_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ALL_REGIONS, ALL_TAGS};
+
+    /// UTC+3, the default timezone of the tests.
+    fn msk() -> FixedOffset {
+        FixedOffset::east_opt(3 * 3600).unwrap()
+    }
+
+    fn args(rest: &str) -> Args<'_> {
+        Args {
+            rest,
+            command: "test",
+            offset: msk(),
+        }
+    }
+
+    /// Loads a few regions with their aliases and the tags.
+    fn load_regions() {
+        let mut aliases = ALLIAS_REGIONS.write().unwrap();
+        let mut regions = ALL_REGIONS.write().unwrap();
+        for (alias, region) in [
+            ("москва", "Москва"),
+            ("мск", "Москва"),
+            ("тверь", "Тверская область"),
+            ("рф", "РФ"),
+        ] {
+            aliases.insert(alias, region);
+            regions.insert(region);
+        }
+        ALL_TAGS.write().unwrap().extend(["А", "Б"]);
+    }
+
+    #[test]
+    fn tokens() {
+        let mut args = args("  1 два   3 ");
+        assert_eq!(args.required::<i64>("id").unwrap(), 1);
+        assert_eq!(args.next(), Some("два"));
+        assert_eq!(args.optional::<u32>("n").unwrap(), Some(3));
+        assert_eq!(args.optional::<u32>("n").unwrap(), None);
+        assert!(args.finish().is_ok());
+    }
+
+    #[test]
+    fn missing_argument() {
+        let err = args(" ").required::<i64>("id").unwrap_err();
+        assert!(matches!(
+            err,
+            CommandError::MissingArgument {
+                argument: "id",
+                command: "test"
+            }
+        ));
+        let err = args("").required_rest("reason").unwrap_err();
+        assert!(matches!(
+            err,
+            CommandError::MissingArgument {
+                argument: "reason",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn bad_argument() {
+        let err = args("x").required::<i64>("id").unwrap_err();
+        assert!(
+            matches!(err, CommandError::BadArgument { argument: "id", value, .. } if value == "x")
+        );
+        let err = args("0").id().unwrap_err();
+        assert!(matches!(
+            err,
+            CommandError::BadArgument { argument: "id", .. }
+        ));
+    }
+
+    #[test]
+    fn extra_arguments() {
+        let mut args = args("1 2  3");
+        args.next();
+        let err = args.finish().unwrap_err();
+        assert!(matches!(err, CommandError::ExtraArguments { value, .. } if value == "2  3"));
+    }
+
+    #[test]
+    fn offset() {
+        let mut a = args("+05:30 -2 rest");
+        assert_eq!(
+            a.offset().unwrap(),
+            FixedOffset::east_opt(5 * 3600 + 1800).unwrap()
+        );
+        assert_eq!(
+            a.offset().unwrap(),
+            FixedOffset::west_opt(2 * 3600).unwrap()
+        );
+        assert_eq!(a.offset().unwrap(), msk());
+        assert_eq!(a.next(), Some("rest"));
+        assert_eq!(args("").offset().unwrap(), msk());
+        assert!(matches!(
+            args("+ab").offset(),
+            Err(CommandError::BadArgument {
+                argument: "OFFSET",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(args("").days().unwrap(), 7);
+        assert_eq!(args("14").days_up_to(7, 30).unwrap(), 14);
+        assert_eq!(args("30").days_up_to(7, 30).unwrap(), 30);
+        assert!(args("31").days_up_to(7, 30).is_err());
+        assert!(args("0").days_up_to(7, 30).is_err());
+
+        let mut a = args("+03:00");
+        assert_eq!(a.days_up_to(7, 30).unwrap(), 7);
+        assert_eq!(a.next(), Some("+03:00"));
+    }
+
+    #[test]
+    fn optional_if() {
+        let mut a = args("пн 10");
+        assert_eq!(a.optional_if(|d| d.parse::<u32>().ok()), None);
+        assert_eq!(a.next(), Some("пн"));
+        assert_eq!(a.optional_if(|d| d.parse::<u32>().ok()), Some(10));
+        assert_eq!(a.optional_if(|d| d.parse::<u32>().ok()), None);
+    }
+
+    #[test]
+    fn required_rest() {
+        let mut a = args(" причина  в двух словах ");
+        assert_eq!(a.required_rest("reason").unwrap(), "причина  в двух словах");
+        assert!(a.finish().is_ok());
+    }
+
+    #[test]
+    fn term() {
+        assert_eq!(parse_term("12ч"), Some(Duration::hours(12)));
+        assert_eq!(parse_term("30d"), Some(Duration::days(30)));
+        assert_eq!(parse_term("2н"), Some(Duration::weeks(2)));
+        assert_eq!(parse_term("0д"), None);
+        assert_eq!(parse_term("5м"), None);
+        assert_eq!(parse_term("д"), None);
+        assert_eq!(parse_term("1д2ч"), None);
+        assert_eq!(parse_term(""), None);
+    }
+
+    #[test]
+    fn quiet_hours() {
+        let q = parse_quiet_hours("23-7", msk()).unwrap();
+        assert_eq!((q.from, q.to, q.offset), (23 * 60, 7 * 60, 3 * 3600));
+        let q = parse_quiet_hours("22:30-06:15", msk()).unwrap();
+        assert_eq!((q.from, q.to), (22 * 60 + 30, 6 * 60 + 15));
+        assert!(parse_quiet_hours("7-7", msk()).is_none());
+        assert!(parse_quiet_hours("24-7", msk()).is_none());
+        assert!(parse_quiet_hours("23:60-7", msk()).is_none());
+        assert!(parse_quiet_hours("23", msk()).is_none());
+        assert!(parse_quiet_hours("23-7-8", msk()).is_none());
+    }
+
+    #[test]
+    fn reason_after_regions() {
+        load_regions();
+        let (regions, reason) = regions_and_reason("Москва тверь нужна сводка").unwrap();
+        assert_eq!(regions, ["Москва", "Тверская область"]);
+        assert_eq!(reason, "нужна сводка");
+
+        let (regions, reason) = regions_and_reason("мск").unwrap();
+        assert_eq!(regions, ["Москва"]);
+        assert_eq!(reason, "");
+
+        assert!(matches!(
+            regions_and_reason("нужна сводка"),
+            Err(CommandError::Query(Error::NoRegions))
+        ));
+        assert!(matches!(
+            regions_and_reason(""),
+            Err(CommandError::Query(Error::NoRegions))
+        ));
+    }
+
+    #[test]
+    fn tags_after_regions() {
+        load_regions();
+        let (regions, tags) = regions_and_tags("москва тверь а б").unwrap();
+        assert_eq!(regions, ["Москва", "Тверская область"]);
+        assert_eq!(tags, ["А", "Б"]);
+
+        let (regions, tags) = regions_and_tags("рф").unwrap();
+        assert_eq!(regions, ["РФ"]);
+        assert!(tags.is_empty());
+
+        assert!(matches!(
+            regions_and_tags("а"),
+            Err(CommandError::Query(Error::NoRegions))
+        ));
+        assert!(matches!(
+            regions_and_tags("москва я"),
+            Err(CommandError::Query(Error::BadTag(t))) if t == "я"
+        ));
+    }
+
+    #[test]
+    fn command() {
+        let quiet = Command::parse("/quiet 1 23-7", msk()).unwrap();
+        assert!(matches!(
+            quiet,
+            Ok(Command::Quiet {
+                n: 1,
+                quiet_hours: Some(QuietHours {
+                    from: 1380,
+                    to: 420,
+                    ..
+                })
+            })
+        ));
+        assert!(matches!(
+            Command::parse("/quiet 1", msk()).unwrap(),
+            Err(CommandError::MissingArgument {
+                argument: "quiet_hours",
+                command: "quiet"
+            })
+        ));
+        assert!(matches!(
+            Command::parse("/quiet 1 off +03:00 extra", msk()).unwrap(),
+            Err(CommandError::ExtraArguments { value, .. }) if value == "extra"
+        ));
+        assert!(matches!(
+            Command::parse("/nope", msk()).unwrap(),
+            Err(CommandError::Unknown(name)) if name == "nope"
+        ));
+        assert!(Command::parse("text", msk()).is_none());
+    }
+}
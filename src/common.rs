@@ -1,7 +1,15 @@
-use teloxide::{prelude::*, RequestError};
+use chrono::FixedOffset;
+use teloxide::prelude::*;
 
-use crate::{db_utils, ALLIAS_REGIONS, ALL_REGIONS, ALL_TAGS};
-use std::collections::HashSet;
+use crate::db_utils::models::{DeliveryMode, Profile};
+use crate::delivery;
+use crate::i18n::tr;
+use crate::sender::{self, Priority};
+use crate::{db_utils, ALLIAS_REGIONS, ALL_REGIONS, ALL_TAGS, BOT_USER};
+use std::collections::{BTreeMap, HashSet};
+
+/// Telegram's limit on the length of a text message, in characters.
+const MAX_MESSAGE_LEN: usize = 4096;
 
 #[derive(Debug)]
 pub enum Regions<'t> {
@@ -41,6 +49,16 @@ pub fn extract_regions<'t>(regions: &'t str) -> Regions<'t> {
     Regions::Regions(res)
 }
 
+/// The bot's own user, see [`BOT_USER`].
+pub fn bot_user() -> teloxide::types::User {
+    BOT_USER
+        .read()
+        .map_err(|e| log::error!("Can't lock BOT_USER. Error: {}", e))
+        .unwrap()
+        .clone()
+        .expect("The bot user is fetched at startup")
+}
+
 pub enum Tags<'t> {
     Tags(Vec<&'static str>),
     BadTag(&'t str),
@@ -62,14 +80,34 @@ pub fn extract_tags<'t>(tags: &'t str) -> Tags<'t> {
 }
 
 pub async fn send_str(cx: &TransitionIn<AutoSend<Bot>>, str: &str) {
-    loop {
-        match cx.answer(str).await {
-            Ok(_) => break,
-            Err(RequestError::RetryAfter(secs)) => {
-                tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await
-            }
-            Err(e) => break log::error!("Error while sending a string: {}", e.to_string()),
+    send_text(&cx.requester, cx.chat_id(), str).await
+}
+
+pub async fn send_text(bot: &AutoSend<Bot>, chat_id: i64, str: &str) {
+    if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+        bot.send_message(chat_id, str)
+    })
+    .await
+    {
+        log::error!("Error while sending a string: {}", e);
+    }
+}
+
+/// Sends `paragraphs` in as few messages as fit into Telegram's length limit.
+pub async fn send_paragraphs(bot: &AutoSend<Bot>, chat_id: i64, paragraphs: &[String]) {
+    let mut text = String::new();
+    for p in paragraphs {
+        if !text.is_empty() && text.chars().count() + p.chars().count() + 2 > MAX_MESSAGE_LEN {
+            send_text(bot, chat_id, text.as_str()).await;
+            text.clear();
         }
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        text.extend(p.chars().take(MAX_MESSAGE_LEN));
+    }
+    if !text.is_empty() {
+        send_text(bot, chat_id, text.as_str()).await;
     }
 }
 
@@ -77,23 +115,134 @@ pub async fn send_messages(
     cx: &TransitionIn<AutoSend<Bot>>,
     messages: Vec<db_utils::models::Message>,
     with_id: bool,
+    profile: &Profile,
 ) {
     for message in messages {
         if with_id {
             send_str(cx, message._id.to_hex().as_str()).await;
         }
-        loop {
-            match cx
-                .requester
-                .forward_message(cx.chat_id(), message.chat_id, message.message_id)
-                .await
-            {
-                Ok(_) => break,
-                Err(RequestError::RetryAfter(secs)) => {
-                    tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await
-                }
-                Err(e) => break log::error!("Error while forwarding a message: {}", e.to_string()),
+        deliver_message(&cx.requester, cx.chat_id(), &message, profile, false).await;
+    }
+}
+
+/// Sends a saved message to `chat_id` the way the profile's delivery says: forwarded,
+/// or copied with an optional attribution footer replying to the copy. A `silent` delivery
+/// doesn't notify the recipient.
+pub async fn deliver_message(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    message: &db_utils::models::Message,
+    profile: &Profile,
+    silent: bool,
+) {
+    let delivery = profile.delivery.unwrap_or_else(delivery::default_delivery);
+    let copied = match delivery.mode {
+        DeliveryMode::Forward => sender::send(chat_id, Priority::Bulk, || {
+            bot.forward_message(chat_id, message.chat_id, message.message_id)
+                .disable_notification(silent)
+        })
+        .await
+        .map(|_| None),
+        DeliveryMode::Copy => sender::send(chat_id, Priority::Bulk, || {
+            bot.copy_message(chat_id, message.chat_id, message.message_id)
+                .disable_notification(silent)
+        })
+        .await
+        .map(|m| Some(m.id)),
+    };
+    let copied = match copied {
+        Ok(copied) => copied,
+        Err(e) => return log::error!("Error while delivering a message: {}", e),
+    };
+
+    if let (Some(copied), true) = (copied, delivery.attribution) {
+        let footer = attribution(message, profile.offset());
+        if let Err(e) = sender::send(chat_id, Priority::Bulk, || {
+            bot.send_message(chat_id, footer.as_str())
+                .reply_to_message_id(copied)
+                .disable_notification(true)
+        })
+        .await
+        {
+            log::error!("Error while sending a string: {}", e);
+        }
+    }
+}
+
+fn attribution(message: &db_utils::models::Message, offset: FixedOffset) -> String {
+    let mut footer = format!("📍 {}", message.regions.join(", "));
+    if !message.tags.is_empty() {
+        footer += format!(" · 🏷 {}", message.tags.join(", ")).as_str();
+    }
+    footer += format!(
+        " · 🕒 {}",
+        message
+            .timestamp
+            .with_timezone(&offset)
+            .format("%d.%m.%y %H:%M")
+    )
+    .as_str();
+    footer
+}
+
+/// Groups messages by region, ordering each region by the first tag and then by time.
+pub fn group_by_region<I>(messages: I) -> BTreeMap<String, Vec<db_utils::models::Message>>
+where
+    I: IntoIterator<Item = db_utils::models::Message>,
+{
+    let mut res = BTreeMap::<String, Vec<db_utils::models::Message>>::new();
+    messages.into_iter().for_each(|m| {
+        m.regions
+            .iter()
+            .for_each(|r| res.entry(r.clone()).or_default().push(m.clone()))
+    });
+
+    res.iter_mut().for_each(|(_, messages)| {
+        messages.sort_by(|a, b| {
+            use std::cmp::Ordering;
+            let date = a.timestamp.cmp(&b.timestamp);
+            let tag = a
+                .tags
+                .first()
+                .cloned()
+                .unwrap_or_default()
+                .cmp(&b.tags.first().cloned().unwrap_or_default());
+            match (tag, date) {
+                (Ordering::Equal, d) => d,
+                (t, _) => t,
             }
+        })
+    });
+    res
+}
+
+/// Forwards messages grouped by [`group_by_region`], federal news ("РФ") first.
+pub async fn send_grouped(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    messages: &BTreeMap<String, Vec<db_utils::models::Message>>,
+    profile: &Profile,
+) {
+    let federal = messages.get_key_value("РФ");
+    let regional = messages.iter().filter(|(r, _)| r.as_str() != "РФ");
+    for (region, messages) in federal.into_iter().chain(regional) {
+        let text = tr!(profile.language, "region", region = region);
+        send_text(bot, chat_id, text.as_str()).await;
+        for message in messages {
+            deliver_message(bot, chat_id, message, profile, false).await;
         }
     }
 }
+
+/// Parses an UTC offset in the `+HH:MM` form used by the bot commands.
+pub fn parse_offset(offset: &str) -> Option<chrono::FixedOffset> {
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let mut split = offset[1..].split(':');
+    let hours = split.next()?.parse::<i32>().ok()?;
+    let minutes = split.next().unwrap_or("00").parse::<i32>().ok()?;
+    chrono::FixedOffset::east_opt(sign * (hours * 60 * 60 + minutes * 60))
+}
@@ -0,0 +1,79 @@
+use bson::doc;
+use bson::oid::ObjectId;
+use mongodb::error::Result as DbResult;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::Client;
+
+use super::models::{AccessRequest, AccessStatus};
+use super::{ACCESS_REQUESTS_COLLECTION_NAME, DB_NAME};
+
+pub async fn insert_request(client: &Client, request: &AccessRequest) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<AccessRequest>(ACCESS_REQUESTS_COLLECTION_NAME)
+        .insert_one(request, None)
+        .await
+        .map(|_| ())
+}
+
+pub async fn pending_request(client: &Client, user_id: i64) -> DbResult<Option<AccessRequest>> {
+    client
+        .database(DB_NAME)
+        .collection::<AccessRequest>(ACCESS_REQUESTS_COLLECTION_NAME)
+        .find_one(
+            doc! { "user_id": user_id, "status": AccessStatus::Pending.to_string() },
+            None,
+        )
+        .await
+}
+
+pub async fn get_request(client: &Client, id: ObjectId) -> DbResult<Option<AccessRequest>> {
+    client
+        .database(DB_NAME)
+        .collection::<AccessRequest>(ACCESS_REQUESTS_COLLECTION_NAME)
+        .find_one(doc! { "_id": id }, None)
+        .await
+}
+
+/// Records the decision on a pending request. Returns `None` if the request was
+/// already decided, so two admins can't both act on it.
+pub async fn decide_request(
+    client: &Client,
+    id: ObjectId,
+    status: AccessStatus,
+    decided_by: i64,
+    approved_regions: &[String],
+) -> DbResult<Option<AccessRequest>> {
+    client
+        .database(DB_NAME)
+        .collection::<AccessRequest>(ACCESS_REQUESTS_COLLECTION_NAME)
+        .find_one_and_update(
+            doc! { "_id": id, "status": AccessStatus::Pending.to_string() },
+            doc! { "$set": {
+                "status": status.to_string(),
+                "decided_by": decided_by,
+                "approved_regions": approved_regions,
+            } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+}
+
+/// Puts a request back to pending after its approval failed.
+pub async fn reopen_request(client: &Client, id: ObjectId) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<AccessRequest>(ACCESS_REQUESTS_COLLECTION_NAME)
+        .update_one(
+            doc! { "_id": id },
+            doc! {
+                "$set": { "status": AccessStatus::Pending.to_string(), "approved_regions": [] },
+                "$unset": { "decided_by": "" },
+            },
+            None,
+        )
+        .await
+        .map(|_| ())
+}
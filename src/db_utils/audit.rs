@@ -0,0 +1,71 @@
+use bson::{doc, Document};
+use futures::StreamExt;
+use mongodb::error::Result as DbResult;
+use mongodb::options::FindOptions;
+use mongodb::Client;
+
+use super::models::{AuditEntry, AuditFilter};
+use super::{AUDIT_COLLECTION_NAME, DB_NAME};
+
+pub async fn insert_entry(client: &Client, entry: &AuditEntry) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<AuditEntry>(AUDIT_COLLECTION_NAME)
+        .insert_one(entry, None)
+        .await
+        .map(|_| ())
+}
+
+/// The latest `limit` entries matching `filter`, newest first.
+pub async fn find_entries(
+    client: &Client,
+    filter: &AuditFilter,
+    limit: i64,
+) -> DbResult<Vec<AuditEntry>> {
+    let mut query = Document::new();
+    if let Some(actor) = filter.actor {
+        query.insert("actor", actor);
+    }
+    if let Some(target) = filter.target {
+        query.insert("target", target);
+    }
+    let mut timestamp = Document::new();
+    if let Some(after) = filter.after {
+        timestamp.insert("$gte", bson::DateTime::from_chrono(after));
+    }
+    if let Some(before) = filter.before {
+        timestamp.insert("$lt", bson::DateTime::from_chrono(before));
+    }
+    if !timestamp.is_empty() {
+        query.insert("timestamp", timestamp);
+    }
+
+    client
+        .database(DB_NAME)
+        .collection::<AuditEntry>(AUDIT_COLLECTION_NAME)
+        .find(
+            query,
+            FindOptions::builder()
+                .sort(doc! { "timestamp": -1 })
+                .limit(limit)
+                .build(),
+        )
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The raw document an operation is about to change, for the before/after of an entry.
+pub async fn snapshot(
+    client: &Client,
+    collection: &str,
+    filter: Document,
+) -> DbResult<Option<Document>> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(collection)
+        .find_one(filter, None)
+        .await
+}
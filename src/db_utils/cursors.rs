@@ -0,0 +1,35 @@
+use bson::doc;
+use bson::oid::ObjectId;
+use mongodb::error::Result as DbResult;
+use mongodb::Client;
+
+use super::models::ResultCursor;
+use super::{CURSORS_COLLECTION_NAME, DB_NAME};
+
+pub async fn insert_cursor(client: &Client, cursor: &ResultCursor) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<ResultCursor>(CURSORS_COLLECTION_NAME)
+        .insert_one(cursor, None)
+        .await
+        .map(|_| ())
+}
+
+/// Takes `page` of the cursor of `user_id` for sending. Returns `None` for an unknown cursor,
+/// one of another user, or a page that is already taken.
+pub async fn claim_page(
+    client: &Client,
+    id: ObjectId,
+    user_id: i64,
+    page: usize,
+) -> DbResult<Option<ResultCursor>> {
+    client
+        .database(DB_NAME)
+        .collection::<ResultCursor>(CURSORS_COLLECTION_NAME)
+        .find_one_and_update(
+            doc! { "_id": id, "user_id": user_id, "next_page": page as i64 },
+            doc! { "$set": { "next_page": page as i64 + 1 } },
+            None,
+        )
+        .await
+}
@@ -1,9 +1,8 @@
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 
-use bson::oid::ObjectId;
 use bson::{doc, Document};
-use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
 use futures::StreamExt;
 use mongodb::options::{FindOptions, UpdateOptions};
 use mongodb::Client;
@@ -16,9 +15,12 @@ use crate::db_utils::{
     USERS_COLLECTION_NAME, USER_LATEST_REQUESTS_COLLECTION_NAME,
 };
 
-use super::models::{InsertableMessage, MessageFilter, NewMessage, UserGroup};
+use super::models::{
+    ChatSettings, DailyActivity, Delivery, EditorStat, Expiry, InsertableMessage, MessageCount,
+    MessageFilter, NewMessage, Profile, RegionGrant, UserGroup,
+};
 use super::{
-    models::{Message, Region, User},
+    models::{Chat, Message, Region, User},
     MESSAGES_COLLECTION_NAME,
 };
 
@@ -102,12 +104,8 @@ pub async fn get_tags(client: &Client) -> DbResult<HashSet<String>> {
     Ok(res)
 }
 
-pub async fn get_chats(client: &Client) -> DbResult<HashSet<i64>> {
-    #[derive(Deserialize)]
-    struct Chat {
-        id: i64,
-    }
-    Ok(client
+pub async fn get_chats(client: &Client) -> DbResult<Vec<Chat>> {
+    client
         .database(DB_NAME)
         .collection::<Chat>(CHATS_COLLECTION_NAME)
         .find(None, None)
@@ -115,15 +113,21 @@ pub async fn get_chats(client: &Client) -> DbResult<HashSet<i64>> {
         .collect::<Vec<_>>()
         .await
         .into_iter()
-        .map(|c| c.map(|c| c.id))
-        .collect::<Result<HashSet<_>, _>>()?)
+        .collect()
 }
 
-pub async fn insert_chat(client: &Client, id: i64) -> DbResult<()> {
+/// Adds `chat`, replacing the record of a chat that is already added.
+pub async fn insert_chat(client: &Client, chat: &Chat) -> DbResult<()> {
     Ok(client
         .database(DB_NAME)
-        .collection::<Document>(CHATS_COLLECTION_NAME)
-        .insert_one(mongodb::bson::doc! { "id": id }, None)
+        .collection::<Chat>(CHATS_COLLECTION_NAME)
+        .replace_one(
+            doc! { "id": chat.id },
+            chat,
+            mongodb::options::ReplaceOptions::builder()
+                .upsert(true)
+                .build(),
+        )
         .await
         .map(|_| ())?)
 }
@@ -140,7 +144,7 @@ pub async fn delete_chat(client: &Client, id: i64) -> DbResult<()> {
 pub async fn list_users(client: &Client, groups: Vec<UserGroup>) -> DbResult<Cursor<User>> {
     let filter = if !groups.is_empty() {
         Some(mongodb::bson::doc! {
-            "groups": {
+            "group": {
                 "$in": groups.iter().map(|g| g.as_ref()).collect::<Vec<&str>>()
             }
         })
@@ -154,15 +158,43 @@ pub async fn list_users(client: &Client, groups: Vec<UserGroup>) -> DbResult<Cur
         .find(
             filter,
             FindOptions::builder()
-                .sort(mongodb::bson::doc! {
-                    "regions": 1,
-                    "timestamp": 1,
-                })
+                .sort(mongodb::bson::doc! { "id": 1 })
                 .build(),
         )
         .await
 }
 
+/// Time of the last saved message of every chat, only counting messages of `region` if given.
+pub async fn last_ingestions(
+    client: &Client,
+    region: Option<&str>,
+) -> DbResult<HashMap<i64, DateTime<Utc>>> {
+    #[derive(Deserialize)]
+    struct Last {
+        _id: i64,
+        #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+        last: DateTime<Utc>,
+    }
+
+    let mut pipeline = vec![];
+    if let Some(region) = region {
+        pipeline.push(doc! { "$match": { "regions": region } });
+    }
+    pipeline.push(doc! { "$group": { "_id": "$chat_id", "last": { "$max": "$timestamp" } } });
+
+    let mut res = HashMap::new();
+    let mut cursor = client
+        .database(DB_NAME)
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?;
+    while let Some(doc) = cursor.next().await {
+        let last = bson::from_document::<Last>(doc?).map_err(mongodb::error::Error::from)?;
+        res.insert(last._id, last.last);
+    }
+    Ok(res)
+}
+
 pub async fn add_user(client: &Client, user: User) -> DbResult<()> {
     client
         .database(DB_NAME)
@@ -186,17 +218,30 @@ pub async fn delete_user(client: &Client, id: i64) -> DbResult<()> {
         .map(|_| ())
 }
 
-pub async fn get_user_group(client: &Client, id: i64) -> DbResult<UserGroup> {
+/// Registers `id` in `group` with no regions. What is left of an expired registration is
+/// renewed in place: its regions, grants and expiry are dropped, the profile is kept.
+pub async fn register_user(client: &Client, id: i64, group: &UserGroup) -> DbResult<()> {
     client
         .database(DB_NAME)
         .collection::<Document>(USERS_COLLECTION_NAME)
-        .find_one(
-            mongodb::bson::doc! {
-                "id": id
+        .update_one(
+            doc! { "id": id },
+            doc! {
+                "$set": { "group": group.as_ref(), "allowed_regions": [] },
+                "$unset": { "admin_regions": "", "expiry": "", "grants": "" },
             },
-            None,
+            UpdateOptions::builder().upsert(true).build(),
         )
         .await
+        .map(|_| ())
+}
+
+pub async fn get_user_group(client: &Client, id: i64) -> DbResult<UserGroup> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME)
+        .find_one(active_user(id, Utc::now()), None)
+        .await
         .map(|d| {
             d.map_or(UserGroup::Unregistered, |d| {
                 d.get("group")
@@ -209,26 +254,12 @@ pub async fn get_user_group(client: &Client, id: i64) -> DbResult<UserGroup> {
         })
 }
 
-pub async fn delete_message(client: &Client, id: ObjectId) -> DbResult<()> {
-    client
-        .database(DB_NAME)
-        .collection::<Document>(MESSAGES_COLLECTION_NAME)
-        .delete_one(
-            mongodb::bson::doc! {
-                "_id": id
-            },
-            None,
-        )
-        .await
-        .map(|_| ())
-}
-
-pub async fn delete_messages_period(
-    client: &Client,
+/// Filter of messages saved within the period. Without bounds it matches everything saved until now.
+pub(super) fn period_filter(
     after: Option<DateTime<Utc>>,
     before: Option<DateTime<Utc>>,
-) -> DbResult<()> {
-    let filter = match (after, before) {
+) -> Document {
+    match (after, before) {
         (Some(a), Some(b)) => mongodb::bson::doc! {
             "timestamp": {
                 "$gte": mongodb::bson::DateTime::from_chrono(a),
@@ -250,13 +281,7 @@ pub async fn delete_messages_period(
                 "$lte": mongodb::bson::DateTime::now(),
             }
         },
-    };
-    client
-        .database(DB_NAME)
-        .collection::<Document>(MESSAGES_COLLECTION_NAME)
-        .delete_many(filter, None)
-        .await
-        .map(|_| ())
+    }
 }
 
 pub async fn list_messages(
@@ -266,29 +291,7 @@ pub async fn list_messages(
     after: Option<DateTime<Utc>>,
     before: Option<DateTime<Utc>>,
 ) -> DbResult<Cursor<Message>> {
-    let mut filter = match (after, before) {
-        (Some(a), Some(b)) => mongodb::bson::doc! {
-            "timestamp": {
-                "$gte": mongodb::bson::DateTime::from_chrono(a),
-                "$lte": mongodb::bson::DateTime::from_chrono(b),
-            }
-        },
-        (None, Some(b)) => mongodb::bson::doc! {
-            "timestamp": {
-                "$lte": mongodb::bson::DateTime::from_chrono(b),
-            }
-        },
-        (Some(a), None) => mongodb::bson::doc! {
-            "timestamp": {
-                "$gte": mongodb::bson::DateTime::from_chrono(a),
-            }
-        },
-        (None, None) => mongodb::bson::doc! {
-            "timestamp": {
-                "$lte": mongodb::bson::DateTime::now(),
-            }
-        },
-    };
+    let mut filter = period_filter(after, before);
 
     if !tags.is_empty() {
         filter.insert(
@@ -323,107 +326,222 @@ pub async fn list_messages(
         .await
 }
 
-pub async fn stat(client: &Client, offset: chrono::offset::FixedOffset) -> DbResult<DbStat> {
-    let secs = offset.local_minus_utc();
-    let today = Utc::today()
-        .and_time(NaiveTime::from_hms(0, 0, 0))
-        .unwrap_or_else(Utc::now)
-        .checked_sub_signed(chrono::Duration::seconds(secs as i64))
-        .unwrap_or_else(Utc::now);
-    let yesterday = {
-        let a = today.checked_sub_signed(Duration::days(1)).unwrap();
-        let b = today.clone();
-        (a, b)
-    };
-    let before_yesterday = {
-        let a = today.checked_sub_signed(Duration::days(2)).unwrap();
-        let b = today.checked_sub_signed(Duration::days(1)).unwrap();
-        (a, b)
-    };
-    let week = {
-        let a = today.checked_sub_signed(Duration::days(7)).unwrap();
-        let b = today.clone();
-        (a, b)
-    };
-    let month = {
-        let a = today.checked_sub_signed(Duration::days(30)).unwrap();
-        let b = today.clone();
-        (a, b)
-    };
-    let earlier = today.checked_sub_signed(Duration::days(30)).unwrap();
-    let filter_today = mongodb::bson::doc! {
-        "timestamp": {
-            "$gte": mongodb::bson::DateTime::from_chrono(today),
-        }
-    };
-    let filter_yesterday = mongodb::bson::doc! {
-        "timestamp": {
-            "$gte": mongodb::bson::DateTime::from_chrono(yesterday.0),
-            "$lte": mongodb::bson::DateTime::from_chrono(yesterday.1),
-        }
+/// Message counts for fixed periods and, over the last `days` days, per region, tag and
+/// chat. Days start at midnight in `offset`. Computed with a single aggregation.
+pub async fn stat(
+    client: &Client,
+    days: u32,
+    offset: FixedOffset,
+    all_regions: &HashSet<&'static str>,
+) -> DbResult<DbStat> {
+    let local_midnight = local_midnight(offset);
+    let day = |n: i64| bson::DateTime::from_chrono(local_midnight - Duration::days(n));
+    let since = local_midnight - Duration::days(i64::from(days) - 1);
+    let count_if = |condition: Document| doc! { "$sum": { "$cond": [condition, 1, 0] } };
+    // Buckets are half-open, a message at midnight only counts for the day it starts.
+    let between = |from: bson::DateTime, to: bson::DateTime| {
+        doc! { "$and": [
+            { "$gte": ["$timestamp", from] },
+            { "$lt": ["$timestamp", to] },
+        ] }
     };
-    let filter_before_yesterday = mongodb::bson::doc! {
-        "timestamp": {
-            "$gte": mongodb::bson::DateTime::from_chrono(before_yesterday.0),
-            "$lte": mongodb::bson::DateTime::from_chrono(before_yesterday.1),
-        }
+    let in_period =
+        doc! { "$match": { "timestamp": { "$gte": bson::DateTime::from_chrono(since) } } };
+    let by = |field: &str| {
+        vec![
+            in_period.clone(),
+            doc! { "$group": { "_id": field, "n": { "$sum": 1 } } },
+            doc! { "$sort": { "n": -1, "_id": 1 } },
+        ]
     };
-    let filter_week = mongodb::bson::doc! {
-        "timestamp": {
-            "$gte": mongodb::bson::DateTime::from_chrono(week.0),
-            "$lte": mongodb::bson::DateTime::from_chrono(week.1),
-        }
+    let unwound = |field: &str| {
+        let mut stages = by(field);
+        stages.insert(1, doc! { "$unwind": field });
+        stages
     };
-    let filter_month = mongodb::bson::doc! {
-        "timestamp": {
-            "$gte": mongodb::bson::DateTime::from_chrono(month.0),
-            "$lte": mongodb::bson::DateTime::from_chrono(month.1),
+
+    let pipeline = vec![doc! {
+        "$facet": {
+            "periods": [ {
+                "$group": {
+                    "_id": null,
+                    "today": count_if(doc! { "$gte": ["$timestamp", day(0)] }),
+                    "yesterday": count_if(between(day(1), day(0))),
+                    "before_yesterday": count_if(between(day(2), day(1))),
+                    "week": count_if(between(day(7), day(0))),
+                    "month": count_if(between(day(30), day(0))),
+                    "earlier": count_if(doc! { "$lt": ["$timestamp", day(30)] }),
+                }
+            } ],
+            "total": [ in_period.clone(), { "$count": "n" } ],
+            "regions": unwound("$regions"),
+            "tags": unwound("$tags"),
+            "chats": by("$chat_id"),
         }
+    }];
+
+    #[derive(Deserialize, Default)]
+    struct Periods {
+        today: usize,
+        yesterday: usize,
+        before_yesterday: usize,
+        week: usize,
+        month: usize,
+        earlier: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Total {
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Group<T> {
+        _id: T,
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Facets {
+        periods: Vec<Periods>,
+        total: Vec<Total>,
+        regions: Vec<Group<String>>,
+        tags: Vec<Group<String>>,
+        chats: Vec<Group<i64>>,
+    }
+
+    let facets = match client
+        .database(DB_NAME)
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .next()
+        .await
+    {
+        Some(doc) => bson::from_document::<Facets>(doc?).map_err(mongodb::error::Error::from)?,
+        None => return Err(mongodb::error::Error::custom("Empty $facet result")),
     };
-    let filter_earlier = mongodb::bson::doc! {
-        "timestamp": {
-            "$lte": mongodb::bson::DateTime::from_chrono(earlier),
+
+    let periods = facets.periods.into_iter().next().unwrap_or_default();
+    let mut idle_regions = all_regions
+        .iter()
+        .filter(|&&r| !facets.regions.iter().any(|g| g._id == r))
+        .map(|&r| r.to_string())
+        .collect::<Vec<_>>();
+    idle_regions.sort_unstable();
+    Ok(DbStat {
+        today: periods.today,
+        yesterday: periods.yesterday,
+        before_yesterday: periods.before_yesterday,
+        week: periods.week,
+        month: periods.month,
+        earlier: periods.earlier,
+        since,
+        total: facets.total.first().map(|t| t.n).unwrap_or_default(),
+        regions: facets.regions.into_iter().map(|g| (g._id, g.n)).collect(),
+        tags: facets.tags.into_iter().map(|g| (g._id, g.n)).collect(),
+        chats: facets.chats.into_iter().map(|g| (g._id, g.n)).collect(),
+        idle_regions,
+    })
+}
+
+/// Today's midnight in `offset`.
+fn local_midnight(offset: FixedOffset) -> DateTime<Utc> {
+    Utc::now()
+        .with_timezone(&offset)
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|d| offset.from_local_datetime(&d).single())
+        .map(|d| d.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now)
+}
+
+/// Messages per day over the last `days` days, by first tag and by region. Days start at
+/// midnight in `offset`.
+pub async fn daily_activity(
+    client: &Client,
+    days: u32,
+    offset: FixedOffset,
+) -> DbResult<DailyActivity> {
+    #[derive(Deserialize)]
+    struct Key {
+        day: String,
+        value: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Group {
+        _id: Key,
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Facets {
+        tags: Vec<Group>,
+        regions: Vec<Group>,
+    }
+
+    let since = local_midnight(offset) - Duration::days(i64::from(days) - 1);
+    let day = doc! {
+        "$dateToString": {
+            "format": "%Y-%m-%d",
+            "date": "$timestamp",
+            "timezone": offset.to_string(),
         }
     };
+    let pipeline = vec![
+        doc! { "$match": { "timestamp": { "$gte": bson::DateTime::from_chrono(since) } } },
+        doc! {
+            "$facet": {
+                "tags": [ {
+                    "$group": {
+                        "_id": {
+                            "day": day.clone(),
+                            "value": { "$ifNull": [{ "$arrayElemAt": ["$tags", 0] }, ""] },
+                        },
+                        "n": { "$sum": 1 },
+                    }
+                } ],
+                "regions": [
+                    { "$unwind": "$regions" },
+                    { "$group": { "_id": { "day": day, "value": "$regions" }, "n": { "$sum": 1 } } },
+                ],
+            }
+        },
+    ];
 
-    let today = client
-        .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_today, None)
-        .await? as usize;
-    let yesterday = client
+    let facets = match client
         .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_yesterday, None)
-        .await? as usize;
-    let before_yesterday = client
-        .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_before_yesterday, None)
-        .await? as usize;
-    let week = client
-        .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_week, None)
-        .await? as usize;
-    let month = client
-        .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_month, None)
-        .await? as usize;
-    let earlier = client
-        .database(DB_NAME)
-        .collection::<Message>(MESSAGES_COLLECTION_NAME)
-        .count_documents(filter_earlier, None)
-        .await? as usize;
-
-    Ok(DbStat {
-        today,
-        yesterday,
-        before_yesterday,
-        week,
-        month,
-        earlier,
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .next()
+        .await
+    {
+        Some(doc) => bson::from_document::<Facets>(doc?).map_err(mongodb::error::Error::from)?,
+        None => Facets {
+            tags: vec![],
+            regions: vec![],
+        },
+    };
+    let parse = |groups: Vec<Group>| {
+        let mut counts = groups
+            .into_iter()
+            .filter_map(|g| {
+                let day = NaiveDate::parse_from_str(&g._id.day, "%Y-%m-%d").ok()?;
+                Some((day, g._id.value, g.n))
+            })
+            .collect::<Vec<_>>();
+        counts.sort_unstable();
+        counts
+    };
+    let first = since.with_timezone(&offset).date_naive();
+    Ok(DailyActivity {
+        days: (0..i64::from(days))
+            .map(|n| first + Duration::days(n))
+            .collect(),
+        tags: parse(facets.tags),
+        regions: parse(facets.regions),
     })
 }
 
@@ -432,7 +550,7 @@ pub async fn insert_messages(
     all_regions: &HashSet<&'static str>,
     all_tags: &HashSet<&'static str>,
     messages: Vec<NewMessage>,
-) -> super::error::Result<()> {
+) -> super::error::Result<Vec<Message>> {
     if let Some(bad) = messages
         .iter()
         .find_map(|m| m.regions.iter().find(|r| !all_regions.contains(r.as_str())))
@@ -447,36 +565,130 @@ pub async fn insert_messages(
         return Err(super::error::Error::BadTag(bad.into()));
     }
 
-    Ok(client
+    let messages = messages
+        .into_iter()
+        .map(|msg| InsertableMessage {
+            timestamp: Utc::now(),
+            regions: msg.regions,
+            tags: msg.tags,
+            message_id: msg.message_id,
+            chat_id: msg.chat_id,
+            text: msg.text,
+            posted_by: msg.posted_by,
+            saved_by: msg.saved_by,
+        })
+        .collect::<Vec<_>>();
+
+    let inserted = client
         .database(DB_NAME)
         .collection::<InsertableMessage>(MESSAGES_COLLECTION_NAME)
-        .insert_many(
-            messages
-                .into_iter()
-                .map(|msg| InsertableMessage {
-                    timestamp: Utc::now(),
+        .insert_many(&messages, None)
+        .await?
+        .inserted_ids;
+
+    Ok(messages
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, msg)| {
+            inserted
+                .get(&i)
+                .and_then(|id| id.as_object_id())
+                .map(|_id| Message {
+                    _id,
+                    timestamp: msg.timestamp,
                     regions: msg.regions,
-                    tags: msg.tags,
-                    message_id: msg.message_id,
                     chat_id: msg.chat_id,
+                    message_id: msg.message_id,
+                    tags: msg.tags,
+                    text: msg.text,
+                    posted_by: msg.posted_by,
+                    saved_by: msg.saved_by,
                 })
-                .collect::<Vec<_>>(),
-            None,
-        )
-        .await
-        .map(|_| ())?)
+        })
+        .collect())
 }
 
-pub async fn add_user_regions(client: &Client, id: i64, regions: Vec<String>) -> DbResult<()> {
-    client
+/// Filter of user `id` whose registration hasn't expired by `now`.
+pub(super) fn active_user(id: i64, now: DateTime<Utc>) -> Document {
+    doc! {
+        "id": id,
+        "expiry.expires_at": { "$not": { "$lte": bson::DateTime::from_chrono(now) } },
+    }
+}
+
+/// Regions open to the user, without expired grants.
+pub async fn get_allowed_regions(client: &Client, id: i64) -> DbResult<HashSet<String>> {
+    #[derive(Deserialize, Default)]
+    struct Allowed {
+        #[serde(default)]
+        pub allowed_regions: HashSet<String>,
+        #[serde(default)]
+        pub grants: Vec<RegionGrant>,
+    }
+
+    let now = Utc::now();
+    let Allowed {
+        allowed_regions,
+        grants,
+    } = client
         .database(DB_NAME)
-        .collection::<mongodb::bson::Document>(USERS_COLLECTION_NAME)
+        .collection::<Allowed>(USERS_COLLECTION_NAME)
+        .find_one(active_user(id, now), None)
+        .await?
+        .unwrap_or_default();
+    Ok(allowed_regions
+        .into_iter()
+        .filter(|r| {
+            !grants
+                .iter()
+                .any(|g| &g.region == r && g.expiry.expires_at <= now)
+        })
+        .collect())
+}
+
+/// Opens `regions` to the user. With `expiry` the regions are granted until then,
+/// otherwise permanently, replacing earlier grants of the same regions.
+pub async fn add_user_regions(
+    client: &Client,
+    id: i64,
+    regions: Vec<String>,
+    expiry: Option<Expiry>,
+) -> DbResult<()> {
+    let users = client
+        .database(DB_NAME)
+        .collection::<mongodb::bson::Document>(USERS_COLLECTION_NAME);
+    users
         .update_one(
             mongodb::bson::doc! { "id": id },
-            mongodb::bson::doc! { "$push": { "allowed_regions": { "$each": regions } } },
+            mongodb::bson::doc! { "$push": { "allowed_regions": { "$each": &regions } } },
             UpdateOptions::builder().upsert(true).build(),
         )
         .await?;
+    users
+        .update_one(
+            doc! { "id": id },
+            doc! { "$pull": { "grants": { "region": { "$in": &regions } } } },
+            None,
+        )
+        .await?;
+    if let Some(expiry) = expiry {
+        let grants = regions
+            .into_iter()
+            .map(|region| {
+                bson::to_bson(&RegionGrant {
+                    region,
+                    expiry: expiry.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        users
+            .update_one(
+                doc! { "id": id },
+                doc! { "$push": { "grants": { "$each": grants } } },
+                None,
+            )
+            .await?;
+    }
     Ok(())
 }
 
@@ -486,17 +698,130 @@ pub async fn del_user_regions(client: &Client, id: i64, regions: Vec<String>) ->
         .collection::<mongodb::bson::Document>(USERS_COLLECTION_NAME)
         .update_one(
             mongodb::bson::doc! { "id": id },
-            mongodb::bson::doc! { "$pull": { "allowed_regions": { "$in": regions } } },
+            mongodb::bson::doc! { "$pull": {
+                "allowed_regions": { "$in": &regions },
+                "grants": { "region": { "$in": &regions } },
+            } },
             UpdateOptions::builder().upsert(true).build(),
         )
         .await?;
     Ok(())
 }
 
-pub async fn get_messages(
+/// Regions a regional admin may grant and revoke.
+pub async fn get_admin_regions(client: &Client, id: i64) -> DbResult<HashSet<String>> {
+    #[derive(Deserialize, Default)]
+    struct Scope {
+        #[serde(default)]
+        pub admin_regions: HashSet<String>,
+    }
+
+    Ok(client
+        .database(DB_NAME)
+        .collection::<Scope>(USERS_COLLECTION_NAME)
+        .find_one(doc! { "id": id }, None)
+        .await?
+        .unwrap_or_default()
+        .admin_regions)
+}
+
+pub async fn add_admin_regions(client: &Client, id: i64, regions: Vec<String>) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME)
+        .update_one(
+            doc! { "id": id },
+            doc! { "$addToSet": { "admin_regions": { "$each": regions } } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn del_admin_regions(client: &Client, id: i64, regions: Vec<String>) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME)
+        .update_one(
+            doc! { "id": id },
+            doc! { "$pull": { "admin_regions": { "$in": regions } } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn get_profile(client: &Client, id: i64) -> DbResult<Profile> {
+    #[derive(Deserialize, Default)]
+    struct UserProfile {
+        #[serde(default)]
+        pub profile: Profile,
+    }
+
+    Ok(client
+        .database(DB_NAME)
+        .collection::<UserProfile>(USERS_COLLECTION_NAME)
+        .find_one(doc! { "id": id }, None)
+        .await?
+        .unwrap_or_default()
+        .profile)
+}
+
+pub async fn set_profile(client: &Client, id: i64, profile: &Profile) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME)
+        .update_one(
+            doc! { "id": id },
+            doc! { "$set": { "profile": bson::to_bson(profile)? } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Stores the settings of chat `id`. Returns whether the chat is registered.
+pub async fn set_chat_settings(
     client: &Client,
-    filter: MessageFilter,
-) -> super::error::Result<HashSet<Message>> {
+    id: i64,
+    settings: &ChatSettings,
+) -> DbResult<bool> {
+    let r = client
+        .database(DB_NAME)
+        .collection::<Document>(CHATS_COLLECTION_NAME)
+        .update_one(
+            doc! { "id": id },
+            doc! { "$set": { "settings": bson::to_bson(settings)? } },
+            None,
+        )
+        .await?;
+    Ok(r.matched_count > 0)
+}
+
+pub async fn get_delivery(client: &Client, id: i64) -> DbResult<Option<Delivery>> {
+    Ok(get_profile(client, id).await?.delivery)
+}
+
+/// Stores the user's delivery settings. `None` falls back to the deployment default.
+pub async fn set_delivery(client: &Client, id: i64, delivery: Option<Delivery>) -> DbResult<()> {
+    let update = match delivery {
+        Some(d) => doc! { "$set": { "profile.delivery": bson::to_bson(&d)? } },
+        None => doc! { "$unset": { "profile.delivery": "" } },
+    };
+    client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME)
+        .update_one(doc! { "id": id }, update, None)
+        .await?;
+    Ok(())
+}
+
+/// Builds a filter per region the user is allowed to read. Without an explicit period each
+/// region is queried since the user's latest request for it, or since today's midnight.
+async fn region_filters(
+    client: &Client,
+    filter: &MessageFilter,
+) -> super::error::Result<Vec<(String, Document)>> {
     let last_time = client
         .database(DB_NAME)
         .collection::<LatestRequests>(USER_LATEST_REQUESTS_COLLECTION_NAME)
@@ -513,28 +838,11 @@ pub async fn get_messages(
         .map(|r| (r.region, r.timestamp))
         .collect::<HashMap<_, _>>();
 
-    #[derive(Deserialize, Default)]
-    struct Allowed {
-        pub allowed_regions: HashSet<String>,
-    }
-
-    let regions = client
-        .database(DB_NAME)
-        .collection::<Allowed>(USERS_COLLECTION_NAME)
-        .find_one(
-            mongodb::bson::doc! {
-                "id": filter.user_id
-            },
-            None,
-        )
-        .await?
-        .unwrap_or_default()
-        .allowed_regions;
-    let f_regions = filter.regions.into_iter().collect::<HashSet<_>>();
+    let regions = get_allowed_regions(client, filter.user_id).await?;
+    let f_regions = filter.regions.iter().cloned().collect::<HashSet<_>>();
     let regions = regions.intersection(&f_regions).collect::<Vec<_>>();
 
-    let now = Utc::now();
-    let mut result = HashSet::with_capacity(16);
+    let mut filters = Vec::with_capacity(regions.len());
     for (region, timestamp) in regions.iter().map(|r| {
         let today = *last_time.get(*r).unwrap_or(&Utc::today().and_hms(0, 0, 0));
         (r, today)
@@ -566,7 +874,7 @@ pub async fn get_messages(
                 "$gte": after,
                 "$lte": before
             },
-            "regions": region
+            "regions": *region
         };
 
         if !filter.tags.is_empty() {
@@ -578,10 +886,67 @@ pub async fn get_messages(
             );
         }
 
+        filters.push(((*region).clone(), doc));
+    }
+
+    Ok(filters)
+}
+
+/// Messages matching `filter`, moving the user's latest request of every queried region to
+/// now, so the next query without a period starts from here.
+pub async fn get_messages(
+    client: &Client,
+    filter: MessageFilter,
+) -> super::error::Result<HashSet<Message>> {
+    let (messages, regions) = query_messages(client, &filter).await?;
+
+    let now = Utc::now();
+    for region in regions {
+        client
+            .database(DB_NAME)
+            .collection::<Document>(USER_LATEST_REQUESTS_COLLECTION_NAME)
+            .update_one(
+                doc! { "id": filter.user_id },
+                doc! { "$pull": { "requests": { "region": &region } } },
+                None,
+            )
+            .await?;
+        client
+            .database(DB_NAME)
+            .collection::<Document>(USER_LATEST_REQUESTS_COLLECTION_NAME)
+            .update_one(
+                doc! { "id": filter.user_id },
+                doc! { "$push": { "requests": { "region": region, "timestamp": now } } },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+    }
+
+    Ok(messages)
+}
+
+/// Messages matching `filter`, without touching the user's latest requests.
+pub async fn find_messages(
+    client: &Client,
+    filter: &MessageFilter,
+) -> super::error::Result<HashSet<Message>> {
+    Ok(query_messages(client, filter).await?.0)
+}
+
+/// Messages matching `filter` and the regions queried.
+async fn query_messages(
+    client: &Client,
+    filter: &MessageFilter,
+) -> super::error::Result<(HashSet<Message>, Vec<String>)> {
+    let filters = region_filters(client, filter).await?;
+    let regions = filters.iter().map(|(r, _)| r).collect::<Vec<_>>();
+
+    let mut result = HashSet::with_capacity(16);
+    for (_, doc) in filters.iter() {
         let res = client
             .database(DB_NAME)
             .collection::<Message>(MESSAGES_COLLECTION_NAME)
-            .find(doc, None)
+            .find(doc.clone(), None)
             .await?
             .collect::<Vec<_>>()
             .await
@@ -598,26 +963,194 @@ pub async fn get_messages(
                 messages
             })?;
         result.extend(res.into_iter());
+    }
 
-        client
-            .database(DB_NAME)
-            .collection::<Document>(USER_LATEST_REQUESTS_COLLECTION_NAME)
-            .update_one(
-                doc! { "id": filter.user_id },
-                doc! { "$pull": { "requests": { "region": region } } },
-                None,
-            )
-            .await?;
-        client
-            .database(DB_NAME)
-            .collection::<Document>(USER_LATEST_REQUESTS_COLLECTION_NAME)
-            .update_one(
-                doc! { "id": filter.user_id },
-                doc! { "$push": { "requests": { "region": region, "timestamp": now } } },
-                UpdateOptions::builder().upsert(true).build(),
-            )
-            .await?;
+    Ok((result, regions.into_iter().cloned().collect()))
+}
+
+/// Counts what [`get_messages`] would return for `filter` with a single aggregation,
+/// without touching the user's latest requests.
+pub async fn count_messages(
+    client: &Client,
+    filter: &MessageFilter,
+) -> super::error::Result<MessageCount> {
+    let filters = region_filters(client, filter)
+        .await?
+        .into_iter()
+        .map(|(_, doc)| doc)
+        .collect::<Vec<_>>();
+    if filters.is_empty() {
+        return Ok(MessageCount::default());
+    }
+
+    #[derive(Deserialize)]
+    struct Group {
+        _id: String,
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Total {
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Facets {
+        total: Vec<Total>,
+        regions: Vec<Group>,
+        tags: Vec<Group>,
+    }
+
+    let pipeline = vec![
+        doc! { "$match": { "$or": &filters } },
+        doc! {
+            "$facet": {
+                "total": [ { "$count": "n" } ],
+                "regions": [
+                    { "$unwind": "$regions" },
+                    { "$match": { "$or": &filters } },
+                    { "$group": { "_id": "$regions", "n": { "$sum": 1 } } },
+                    { "$sort": { "n": -1, "_id": 1 } },
+                ],
+                "tags": [
+                    { "$unwind": "$tags" },
+                    { "$group": { "_id": "$tags", "n": { "$sum": 1 } } },
+                    { "$sort": { "n": -1, "_id": 1 } },
+                ],
+            }
+        },
+    ];
+
+    let facets = match client
+        .database(DB_NAME)
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .next()
+        .await
+    {
+        Some(doc) => bson::from_document::<Facets>(doc?).map_err(mongodb::error::Error::from)?,
+        None => return Ok(MessageCount::default()),
+    };
+
+    Ok(MessageCount {
+        total: facets.total.first().map(|t| t.n).unwrap_or_default(),
+        regions: facets.regions.into_iter().map(|g| (g._id, g.n)).collect(),
+        tags: facets.tags.into_iter().map(|g| (g._id, g.n)).collect(),
+    })
+}
+
+/// Messages saved since `since` by every editor, most active first. Days are in `offset`.
+pub async fn editor_stats(
+    client: &Client,
+    since: DateTime<Utc>,
+    offset: FixedOffset,
+) -> DbResult<Vec<EditorStat>> {
+    #[derive(Deserialize)]
+    struct Key {
+        editor: i64,
+        value: String,
     }
 
-    Ok(result)
+    #[derive(Deserialize)]
+    struct Group {
+        _id: Key,
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Total {
+        _id: i64,
+        n: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct Facets {
+        total: Vec<Total>,
+        days: Vec<Group>,
+        regions: Vec<Group>,
+    }
+
+    let since = bson::DateTime::from_chrono(since);
+    let pipeline = vec![
+        doc! { "$match": { "timestamp": { "$gte": since }, "saved_by": { "$exists": true } } },
+        doc! {
+            "$facet": {
+                "total": [
+                    { "$group": { "_id": "$saved_by", "n": { "$sum": 1 } } },
+                    { "$sort": { "n": -1, "_id": 1 } },
+                ],
+                "days": [
+                    {
+                        "$group": {
+                            "_id": {
+                                "editor": "$saved_by",
+                                "value": {
+                                    "$dateToString": {
+                                        "format": "%Y-%m-%d",
+                                        "date": "$timestamp",
+                                        "timezone": offset.to_string(),
+                                    }
+                                },
+                            },
+                            "n": { "$sum": 1 },
+                        }
+                    },
+                    { "$sort": { "_id.value": 1 } },
+                ],
+                "regions": [
+                    { "$unwind": "$regions" },
+                    {
+                        "$group": {
+                            "_id": { "editor": "$saved_by", "value": "$regions" },
+                            "n": { "$sum": 1 },
+                        }
+                    },
+                    { "$sort": { "n": -1, "_id.value": 1 } },
+                ],
+            }
+        },
+    ];
+
+    let facets = match client
+        .database(DB_NAME)
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .next()
+        .await
+    {
+        Some(doc) => bson::from_document::<Facets>(doc?).map_err(mongodb::error::Error::from)?,
+        None => return Ok(vec![]),
+    };
+
+    let mut stats = facets
+        .total
+        .into_iter()
+        .map(|t| EditorStat {
+            editor: t._id,
+            total: t.n,
+            days: vec![],
+            regions: vec![],
+        })
+        .collect::<Vec<_>>();
+    for stat in &mut stats {
+        let editor = stat.editor;
+        stat.days = facets
+            .days
+            .iter()
+            .filter(|g| g._id.editor == editor)
+            .filter_map(|g| {
+                let day = NaiveDate::parse_from_str(&g._id.value, "%Y-%m-%d").ok()?;
+                Some((day, g.n))
+            })
+            .collect();
+        stat.regions = facets
+            .regions
+            .iter()
+            .filter(|g| g._id.editor == editor)
+            .map(|g| (g._id.value.clone(), g.n))
+            .collect();
+    }
+    Ok(stats)
 }
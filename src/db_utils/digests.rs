@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use bson::oid::ObjectId;
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use mongodb::error::Result as DbResult;
+use mongodb::options::FindOptions;
+use mongodb::Client;
+
+use super::models::{Digest, DigestRun, Message};
+use super::{
+    DB_NAME, DIGESTS_COLLECTION_NAME, DIGEST_RUNS_COLLECTION_NAME, MESSAGES_COLLECTION_NAME,
+};
+
+pub async fn insert_digest(client: &Client, digest: &Digest) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Digest>(DIGESTS_COLLECTION_NAME)
+        .insert_one(digest, None)
+        .await
+        .map(|_| ())
+}
+
+/// Digests of `user_id`, or of every user when `user_id` is `None`.
+pub async fn list_digests(client: &Client, user_id: Option<i64>) -> DbResult<Vec<Digest>> {
+    let filter = user_id.map(|id| doc! { "user_id": id });
+    client
+        .database(DB_NAME)
+        .collection::<Digest>(DIGESTS_COLLECTION_NAME)
+        .find(
+            filter,
+            FindOptions::builder().sort(doc! { "_id": 1 }).build(),
+        )
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+pub async fn delete_digest(client: &Client, user_id: i64, id: ObjectId) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(DIGESTS_COLLECTION_NAME)
+        .delete_one(doc! { "_id": id, "user_id": user_id }, None)
+        .await
+        .map(|_| ())
+}
+
+/// Deletes every digest of `user_ids`, once they are no longer users. Past runs are kept.
+pub async fn delete_user_digests(client: &Client, user_ids: &[i64]) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(DIGESTS_COLLECTION_NAME)
+        .delete_many(doc! { "user_id": { "$in": user_ids } }, None)
+        .await
+        .map(|_| ())
+}
+
+/// Records a finished run and moves the digest's `last_run` to `run.ran_at`.
+pub async fn insert_digest_run(client: &Client, run: &DigestRun) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<DigestRun>(DIGEST_RUNS_COLLECTION_NAME)
+        .insert_one(run, None)
+        .await?;
+    client
+        .database(DB_NAME)
+        .collection::<Document>(DIGESTS_COLLECTION_NAME)
+        .update_one(
+            doc! { "_id": run.digest_id },
+            doc! { "$set": { "last_run": bson::DateTime::from_chrono(run.ran_at) } },
+            None,
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Messages of the digest saved in `(after, before]`, restricted to `allowed` regions.
+pub async fn get_digest_messages(
+    client: &Client,
+    digest: &Digest,
+    allowed: &HashSet<String>,
+    after: DateTime<Utc>,
+    before: DateTime<Utc>,
+) -> DbResult<Vec<Message>> {
+    let regions = digest
+        .regions
+        .iter()
+        .filter(|r| allowed.contains(*r))
+        .cloned()
+        .collect::<Vec<_>>();
+    if regions.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut filter = doc! {
+        "timestamp": {
+            "$gt": bson::DateTime::from_chrono(after),
+            "$lte": bson::DateTime::from_chrono(before),
+        },
+        "regions": { "$in": &regions },
+    };
+    if !digest.tags.is_empty() {
+        filter.insert("tags", doc! { "$in": &digest.tags });
+    }
+
+    let mut messages = client
+        .database(DB_NAME)
+        .collection::<Message>(MESSAGES_COLLECTION_NAME)
+        .find(filter, None)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<DbResult<Vec<_>>>()?;
+    for message in &mut messages {
+        message.regions.retain(|r| regions.contains(r));
+    }
+    Ok(messages)
+}
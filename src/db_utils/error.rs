@@ -1,21 +1,70 @@
-use super::models::UserGroup;
+use super::models::{Permission, UserGroup};
+use crate::i18n::{tr, Language, Localize};
 use smartstring::{LazyCompact, SmartString};
 
 type String = SmartString<LazyCompact>;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Display gives the catalogue key, [`Localize`] renders the message.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("{0}")]
+    #[error("error.db_driver")]
     DbError(#[from] mongodb::error::Error),
-    #[error("Вы должны принадлежать группе {desired}, чтобы выполнить эту команду. Текущая группа: {current}")]
+    #[error("error.privilege")]
     PrivlegeError {
-        desired: UserGroup,
+        permission: Permission,
         current: UserGroup,
     },
-    #[error("Непонятный тег \"{0}\"")]
+    #[error("error.regions_out_of_scope")]
+    RegionsOutOfScope {
+        current: UserGroup,
+        regions: Vec<std::string::String>,
+    },
+    #[error("error.db_bad_tag")]
     BadTag(String),
-    #[error("Непонятный регион \"{0}\"")]
+    #[error("error.db_bad_region")]
     BadRegion(String),
+    #[error("error.no_allowed_regions")]
+    NoAllowedRegions,
+    #[error("error.no_subscription")]
+    NoSubscription(usize),
+    #[error("error.no_digest")]
+    NoDigest(usize),
+    #[error("error.no_trash_batch")]
+    NoTrashBatch(bson::oid::ObjectId),
+    #[error("error.bad_invite")]
+    BadInvite,
+    #[error("error.pending_access_request")]
+    PendingAccessRequest,
+    #[error("error.no_access_request")]
+    NoAccessRequest(bson::oid::ObjectId),
+    #[error("error.no_chat")]
+    NoChat(i64),
+}
+
+impl Localize for Error {
+    fn localize(&self, lang: Language) -> std::string::String {
+        let key = self.to_string();
+        match self {
+            Error::DbError(e) => tr!(lang, &key, error = e),
+            Error::PrivlegeError {
+                permission,
+                current,
+            } => tr!(
+                lang,
+                &key,
+                current = current,
+                permission = permission.localize(lang)
+            ),
+            Error::RegionsOutOfScope { current, regions } => {
+                tr!(lang, &key, current = current, regions = regions.join(", "))
+            }
+            Error::BadTag(value) | Error::BadRegion(value) => tr!(lang, &key, value = value),
+            Error::NoSubscription(n) | Error::NoDigest(n) => tr!(lang, &key, n = n),
+            Error::NoTrashBatch(id) | Error::NoAccessRequest(id) => tr!(lang, &key, id = id),
+            Error::NoChat(id) => tr!(lang, &key, id = id),
+            _ => tr!(lang, &key),
+        }
+    }
 }
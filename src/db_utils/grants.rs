@@ -0,0 +1,98 @@
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use mongodb::error::Result as DbResult;
+use mongodb::options::UpdateOptions;
+use mongodb::Client;
+
+use super::models::User;
+use super::{DB_NAME, USERS_COLLECTION_NAME};
+
+/// Users whose registration or a region grant expires by `until` and who weren't warned yet.
+pub async fn find_expiring(client: &Client, until: DateTime<Utc>) -> DbResult<Vec<User>> {
+    let until = bson::DateTime::from_chrono(until);
+    let filter = doc! { "$or": [
+        { "expiry.expires_at": { "$lte": until }, "expiry.notified": false },
+        { "grants": { "$elemMatch": { "expires_at": { "$lte": until }, "notified": false } } },
+    ] };
+    client
+        .database(DB_NAME)
+        .collection::<User>(USERS_COLLECTION_NAME)
+        .find(filter, None)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Marks everything of user `id` that expires by `until` as warned about.
+pub async fn mark_notified(client: &Client, id: i64, until: DateTime<Utc>) -> DbResult<()> {
+    let users = client
+        .database(DB_NAME)
+        .collection::<Document>(USERS_COLLECTION_NAME);
+    let until = bson::DateTime::from_chrono(until);
+    users
+        .update_one(
+            doc! { "id": id, "expiry.expires_at": { "$lte": until } },
+            doc! { "$set": { "expiry.notified": true } },
+            None,
+        )
+        .await?;
+    users
+        .update_one(
+            doc! { "id": id },
+            doc! { "$set": { "grants.$[g].notified": true } },
+            UpdateOptions::builder()
+                .array_filters(vec![doc! { "g.expires_at": { "$lte": until } }])
+                .build(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Deletes users whose registration expired, with their subscriptions and digests, and closes
+/// expired region grants.
+/// Returns the ids of the deleted users.
+pub async fn purge_expired(client: &Client, now: DateTime<Utc>) -> DbResult<Vec<i64>> {
+    let users = client
+        .database(DB_NAME)
+        .collection::<User>(USERS_COLLECTION_NAME);
+    let now = bson::DateTime::from_chrono(now);
+
+    let expired = doc! { "expiry.expires_at": { "$lte": now } };
+    let deleted = users
+        .find(expired.clone(), None)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .map(|u| u.map(|u| u.id))
+        .collect::<DbResult<Vec<_>>>()?;
+    users.delete_many(expired, None).await?;
+    super::subscriptions::delete_user_subscriptions(client, &deleted).await?;
+    super::digests::delete_user_digests(client, &deleted).await?;
+
+    let mut granted = users
+        .find(doc! { "grants.expires_at": { "$lte": now } }, None)
+        .await?;
+    while let Some(user) = granted.next().await.transpose()? {
+        let regions = user
+            .grants
+            .iter()
+            .filter(|g| g.expiry.expires_at <= now.to_chrono())
+            .map(|g| g.region.as_str())
+            .collect::<Vec<_>>();
+        users
+            .update_one(
+                doc! { "id": user.id },
+                doc! { "$pull": {
+                    "allowed_regions": { "$in": regions },
+                    "grants": { "expires_at": { "$lte": now } },
+                } },
+                None,
+            )
+            .await?;
+    }
+    Ok(deleted)
+}
@@ -0,0 +1,37 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use mongodb::error::Result as DbResult;
+use mongodb::Client;
+
+use super::models::Invite;
+use super::{DB_NAME, INVITES_COLLECTION_NAME};
+
+pub async fn insert_invite(client: &Client, invite: &Invite) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Invite>(INVITES_COLLECTION_NAME)
+        .insert_one(invite, None)
+        .await
+        .map(|_| ())
+}
+
+/// Takes one use of a valid invite. Returns `None` for an unknown, used up or expired token.
+pub async fn redeem_invite(
+    client: &Client,
+    token: &str,
+    now: DateTime<Utc>,
+) -> DbResult<Option<Invite>> {
+    client
+        .database(DB_NAME)
+        .collection::<Invite>(INVITES_COLLECTION_NAME)
+        .find_one_and_update(
+            doc! {
+                "_id": token,
+                "uses_left": { "$gt": 0 },
+                "expires_at": { "$not": { "$lte": bson::DateTime::from_chrono(now) } },
+            },
+            doc! { "$inc": { "uses_left": -1 } },
+            None,
+        )
+        .await
+}
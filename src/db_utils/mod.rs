@@ -1,10 +1,25 @@
+mod access;
+mod audit;
+mod cursors;
 mod db;
+mod digests;
 pub mod error;
+mod grants;
+mod invites;
 pub mod models;
+mod subscriptions;
+mod trash;
 pub mod user;
 mod validate;
 
-pub use db::{get_chats, get_messages, get_regions, get_tags, insert_messages, migrate_chat};
+pub use cursors::{claim_page, insert_cursor};
+pub use db::{
+    count_messages, delete_chat, find_messages, get_allowed_regions, get_chats, get_messages,
+    get_profile, get_regions, get_tags, insert_messages, list_users, migrate_chat,
+};
+pub use digests::{get_digest_messages, insert_digest_run, list_digests};
+pub use grants::{find_expiring, mark_notified, purge_expired};
+pub use subscriptions::find_subscriptions;
 pub use validate::validate_db;
 
 pub(self) const DB_NAME: &str = "messages_db";
@@ -14,3 +29,11 @@ pub(self) const REGIONS_COLLECTION_NAME: &str = "regions";
 pub(self) const USERS_COLLECTION_NAME: &str = "users";
 pub(self) const CHATS_COLLECTION_NAME: &str = "chats";
 pub(self) const USER_LATEST_REQUESTS_COLLECTION_NAME: &str = "user_latest_requests";
+pub(self) const SUBSCRIPTIONS_COLLECTION_NAME: &str = "subscriptions";
+pub(self) const DIGESTS_COLLECTION_NAME: &str = "digests";
+pub(self) const DIGEST_RUNS_COLLECTION_NAME: &str = "digest_runs";
+pub(self) const CURSORS_COLLECTION_NAME: &str = "result_cursors";
+pub(self) const TRASH_COLLECTION_NAME: &str = "trash";
+pub(self) const AUDIT_COLLECTION_NAME: &str = "audit_log";
+pub(self) const ACCESS_REQUESTS_COLLECTION_NAME: &str = "access_requests";
+pub(self) const INVITES_COLLECTION_NAME: &str = "invites";
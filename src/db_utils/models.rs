@@ -9,6 +9,15 @@ pub struct DbStat {
     pub week: usize,
     pub month: usize,
     pub earlier: usize,
+    /// Start of the period the breakdowns below cover.
+    pub since: chrono::DateTime<chrono::Utc>,
+    pub total: usize,
+    /// Counts of the period, most active first.
+    pub regions: Vec<(String, usize)>,
+    pub tags: Vec<(String, usize)>,
+    pub chats: Vec<(i64, usize)>,
+    /// Known regions without messages in the period.
+    pub idle_regions: Vec<String>,
 }
 
 #[derive(
@@ -24,15 +33,177 @@ pub struct DbStat {
 )]
 pub enum UserGroup {
     Admin,
+    /// Manages users within their administrative regions.
+    RegionalAdmin,
+    /// A reader who can also save messages in groups.
+    Editor,
+    /// Read-only access to statistics and the audit log.
+    Auditor,
+    /// A reader: searches, subscriptions and digests.
     Registered,
     Unregistered,
 }
 
+impl UserGroup {
+    pub fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+        match self {
+            UserGroup::Admin => &[
+                Query,
+                Finalize,
+                ManageUsers,
+                ManageRegionUsers,
+                ManageChats,
+                ManageMessages,
+                ViewStats,
+                ViewAudit,
+            ],
+            UserGroup::RegionalAdmin => &[Query, ManageRegionUsers],
+            UserGroup::Editor => &[Query, Finalize],
+            UserGroup::Auditor => &[Query, ViewStats, ViewAudit],
+            UserGroup::Registered => &[Query],
+            UserGroup::Unregistered => &[],
+        }
+    }
+
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+/// Display gives the snake_case name used in catalogue keys.
+#[derive(strum::Display, PartialEq, Eq, Debug, Clone, Copy)]
+#[strum(serialize_all = "snake_case")]
+pub enum Permission {
+    Query,
+    Finalize,
+    ManageUsers,
+    ManageRegionUsers,
+    ManageChats,
+    ManageMessages,
+    ViewStats,
+    ViewAudit,
+}
+
+impl crate::i18n::Localize for Permission {
+    fn localize(&self, lang: Language) -> String {
+        crate::i18n::get(lang, &format!("permission.{}", self))
+    }
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct User {
     pub id: i64,
     pub group: UserGroup,
     pub allowed_regions: Vec<String>,
+    /// Regions a regional admin may grant and revoke.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub admin_regions: Vec<String>,
+    #[serde(default)]
+    pub profile: Profile,
+    /// When the registration ends, `None` for a permanent one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<Expiry>,
+    /// Regions of `allowed_regions` granted for a limited time.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grants: Vec<RegionGrant>,
+}
+
+/// Moscow time, the offset used when a user hasn't set a timezone.
+pub const DEFAULT_TIMEZONE: i32 = 3 * 3600;
+
+#[derive(
+    Deserialize,
+    Serialize,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
+    PartialEq,
+    Eq,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Language {
+    #[default]
+    Ru,
+    En,
+}
+
+/// Settings a user changes with `/profile` and `/delivery`.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct Profile {
+    /// UTC offset in seconds, [`DEFAULT_TIMEZONE`] when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<i32>,
+    #[serde(default)]
+    pub language: Language,
+    /// Regions of queries that name only a period or tags.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_regions: Vec<String>,
+    /// `None` falls back to the deployment default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<Delivery>,
+}
+
+impl Profile {
+    pub fn offset(&self) -> chrono::FixedOffset {
+        self.timezone
+            .and_then(chrono::FixedOffset::east_opt)
+            .or_else(|| chrono::FixedOffset::east_opt(DEFAULT_TIMEZONE))
+            .expect("DEFAULT_TIMEZONE is a valid offset")
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Expiry {
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub granted_by: i64,
+    /// Whether the user and the granting admin were warned about the expiry.
+    #[serde(default)]
+    pub notified: bool,
+}
+
+impl Expiry {
+    /// Access granted by `granted_by` for `term` from now.
+    pub fn after(term: Duration, granted_by: i64) -> Self {
+        Self {
+            expires_at: chrono::Utc::now() + term,
+            granted_by,
+            notified: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RegionGrant {
+    pub region: String,
+    #[serde(flatten)]
+    pub expiry: Expiry,
+}
+
+#[derive(
+    Deserialize, Serialize, strum::Display, strum::EnumString, PartialEq, Eq, Debug, Clone, Copy,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum DeliveryMode {
+    /// Forwards messages, showing the source chat.
+    Forward,
+    /// Copies messages without a link to the source chat.
+    Copy,
+}
+
+/// How found messages are sent to a user.
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Delivery {
+    pub mode: DeliveryMode,
+    /// Replies to each copied message with its regions, tags and saved time.
+    pub attribution: bool,
 }
 
 #[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Debug, Clone)]
@@ -41,25 +212,80 @@ pub struct Region {
     pub aliases: Vec<String>,
 }
 
+/// A chat whose messages are saved.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Chat {
+    pub id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Who registered the chat, `None` for chats added before it was recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registered_by: Option<i64>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub registered_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub settings: ChatSettings,
+}
+
+impl Chat {
+    /// A chat registered by `registered_by` now, with default settings.
+    pub fn new(id: i64, title: Option<String>, registered_by: i64) -> Self {
+        Self {
+            id,
+            title,
+            registered_by: Some(registered_by),
+            registered_at: Some(chrono::Utc::now()),
+            settings: ChatSettings::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Debug, Clone)]
+pub struct ChatSettings {
+    /// Language of replies in the chat, `None` for the deployment's `CHAT_LANGUAGE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    /// Regions of finalize lines that name none, empty to require regions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_regions: Vec<String>,
+    /// Tags of finalize lines that name none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_tags: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NewMessage {
     pub regions: Vec<String>,
     pub chat_id: i64,
     pub message_id: i32,
     pub tags: Vec<String>,
+    pub text: Option<String>,
+    /// Author of the message, `None` for posts on behalf of a chat.
+    pub posted_by: Option<i64>,
+    /// Who finalized the batch with the message.
+    pub saved_by: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Message {
     pub _id: ObjectId,
-    #[serde(
-        deserialize_with = "bson::serde_helpers::chrono_datetime_as_bson_datetime::deserialize"
-    )]
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub regions: Vec<String>,
     pub chat_id: i64,
     pub message_id: i32,
     pub tags: Vec<String>,
+    /// Text or caption of the message at the moment it was archived.
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub posted_by: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_by: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -70,14 +296,48 @@ pub struct InsertableMessage {
     pub chat_id: i64,
     pub message_id: i32,
     pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posted_by: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_by: Option<i64>,
 }
 
+#[derive(Clone)]
 pub struct MessageFilter {
     pub user_id: i64,
     pub period: Option<(Duration, Duration)>,
     pub regions: Vec<String>,
     pub tags: Vec<String>,
 }
+/// Messages saved by one editor over a period, broken down by day and region.
+pub struct EditorStat {
+    pub editor: i64,
+    pub total: usize,
+    pub days: Vec<(chrono::NaiveDate, usize)>,
+    pub regions: Vec<(String, usize)>,
+}
+
+/// Messages per day over a period, broken down by tag and by region.
+pub struct DailyActivity {
+    /// Days of the period, oldest first.
+    pub days: Vec<chrono::NaiveDate>,
+    /// Counts by day and first tag, `""` for untagged messages, so the counts of a day add
+    /// up to its volume.
+    pub tags: Vec<(chrono::NaiveDate, String, usize)>,
+    /// Counts by day and region. A message with several regions counts in each.
+    pub regions: Vec<(chrono::NaiveDate, String, usize)>,
+}
+
+/// Number of messages matching a [`MessageFilter`], broken down by region and tag.
+#[derive(Default)]
+pub struct MessageCount {
+    pub total: usize,
+    pub regions: Vec<(String, usize)>,
+    pub tags: Vec<(String, usize)>,
+}
+
 #[derive(Deserialize, Serialize, Default)]
 pub struct LatestRequests {
     pub requests: Vec<LatestRequest>,
@@ -89,3 +349,212 @@ pub struct LatestRequest {
     #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct QuietHours {
+    /// Start of the quiet period in minutes since local midnight.
+    pub from: u32,
+    /// End of the quiet period in minutes since local midnight.
+    pub to: u32,
+    /// Offset of the subscriber's local time from UTC in seconds.
+    pub offset: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Subscription {
+    pub _id: ObjectId,
+    pub user_id: i64,
+    pub regions: Vec<String>,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Schedule {
+    /// Delivery times in minutes since local midnight.
+    pub times: Vec<u32>,
+    /// Days of week the digest runs on, `0` is Monday. Empty means every day.
+    #[serde(default)]
+    pub weekdays: Vec<u32>,
+    /// Offset of the user's local time from UTC in seconds.
+    pub offset: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Digest {
+    pub _id: ObjectId,
+    pub user_id: i64,
+    pub regions: Vec<String>,
+    pub tags: Vec<String>,
+    pub schedule: Schedule,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub last_run: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DigestRun {
+    pub digest_id: ObjectId,
+    pub user_id: i64,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub scheduled_for: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub ran_at: chrono::DateTime<chrono::Utc>,
+    /// `true` when the run happened late, e.g. after the bot was down.
+    pub caught_up: bool,
+    pub n_messages: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CursorEntry {
+    pub region: String,
+    pub message: Message,
+}
+
+/// A snapshot of query results delivered page by page.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ResultCursor {
+    pub _id: ObjectId,
+    pub user_id: i64,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created: chrono::DateTime<chrono::Utc>,
+    pub entries: Vec<CursorEntry>,
+    /// The page the "Ещё" button may request next, the earlier ones are taken.
+    #[serde(default)]
+    pub next_page: usize,
+}
+
+/// Messages an admin asked to delete.
+#[derive(Clone, Copy, Debug)]
+pub enum Deletion {
+    Message(ObjectId),
+    /// Everything saved before the instant.
+    Before(chrono::DateTime<chrono::Utc>),
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct DeletionPreview {
+    pub count: usize,
+    #[serde(
+        default,
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub first: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(
+        default,
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub last: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Messages moved to the trash by one deletion.
+#[derive(Deserialize, Debug)]
+pub struct TrashBatch {
+    #[serde(rename = "_id")]
+    pub batch: ObjectId,
+    pub count: usize,
+    pub trashed_by: i64,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub trashed_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(
+    Deserialize, Serialize, strum::Display, strum::EnumString, PartialEq, Eq, Debug, Clone, Copy,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditAction {
+    AddUser,
+    DeleteUser,
+    AddUserRegions,
+    DelUserRegions,
+    AddAdminRegions,
+    DelAdminRegions,
+    AddChat,
+    DeleteChat,
+    DeleteMessages,
+    RestoreMessages,
+    SetDelivery,
+    Subscribe,
+    Unsubscribe,
+    PauseSubscriptions,
+    SetQuietHours,
+    AddDigest,
+    DeleteDigest,
+    RequestAccess,
+    DenyAccess,
+    SetProfile,
+    CreateInvite,
+    RedeemInvite,
+    SetChatSettings,
+}
+
+/// A mutating operation done through [`crate::db_utils::user::User`].
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AuditEntry {
+    pub _id: ObjectId,
+    pub actor: i64,
+    pub action: AuditAction,
+    /// The user or chat the operation was applied to.
+    pub target: Option<i64>,
+    pub args: bson::Document,
+    pub before: Option<bson::Document>,
+    pub after: Option<bson::Document>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub actor: Option<i64>,
+    pub target: Option<i64>,
+    pub after: Option<chrono::DateTime<chrono::Utc>>,
+    pub before: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize, Serialize, strum::Display, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum AccessStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+/// A request of access to regions sent with `/request`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AccessRequest {
+    pub _id: ObjectId,
+    pub user_id: i64,
+    pub regions: Vec<String>,
+    pub reason: String,
+    pub status: AccessStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<i64>,
+    /// The part of `regions` an admin approved.
+    #[serde(default)]
+    pub approved_regions: Vec<String>,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// An invite link token. Whoever opens the link is registered with `group` and `regions`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Invite {
+    #[serde(rename = "_id")]
+    pub token: String,
+    pub created_by: i64,
+    pub group: UserGroup,
+    pub regions: Vec<String>,
+    pub uses_left: u32,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bson::serde_helpers::chrono_datetime_as_bson_datetime_optional"
+    )]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
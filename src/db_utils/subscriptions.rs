@@ -0,0 +1,112 @@
+use bson::oid::ObjectId;
+use bson::{doc, Document};
+use futures::StreamExt;
+use mongodb::error::Result as DbResult;
+use mongodb::options::FindOptions;
+use mongodb::Client;
+
+use super::models::{QuietHours, Subscription};
+use super::{DB_NAME, SUBSCRIPTIONS_COLLECTION_NAME};
+
+pub async fn insert_subscription(client: &Client, subscription: &Subscription) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Subscription>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .insert_one(subscription, None)
+        .await
+        .map(|_| ())
+}
+
+pub async fn list_subscriptions(client: &Client, user_id: i64) -> DbResult<Vec<Subscription>> {
+    client
+        .database(DB_NAME)
+        .collection::<Subscription>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .find(
+            doc! { "user_id": user_id },
+            FindOptions::builder().sort(doc! { "_id": 1 }).build(),
+        )
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+pub async fn delete_subscription(client: &Client, user_id: i64, id: ObjectId) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .delete_one(doc! { "_id": id, "user_id": user_id }, None)
+        .await
+        .map(|_| ())
+}
+
+/// Deletes every subscription of `user_ids`, once they are no longer users.
+pub async fn delete_user_subscriptions(client: &Client, user_ids: &[i64]) -> DbResult<()> {
+    client
+        .database(DB_NAME)
+        .collection::<Document>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .delete_many(doc! { "user_id": { "$in": user_ids } }, None)
+        .await
+        .map(|_| ())
+}
+
+/// Pauses or resumes a single subscription, or every subscription of the user when `id` is `None`.
+pub async fn set_subscriptions_paused(
+    client: &Client,
+    user_id: i64,
+    id: Option<ObjectId>,
+    paused: bool,
+) -> DbResult<()> {
+    let mut filter = doc! { "user_id": user_id };
+    if let Some(id) = id {
+        filter.insert("_id", id);
+    }
+    client
+        .database(DB_NAME)
+        .collection::<Document>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .update_many(filter, doc! { "$set": { "paused": paused } }, None)
+        .await
+        .map(|_| ())
+}
+
+pub async fn set_quiet_hours(
+    client: &Client,
+    user_id: i64,
+    id: ObjectId,
+    quiet_hours: Option<QuietHours>,
+) -> DbResult<()> {
+    let quiet_hours = bson::to_bson(&quiet_hours)?;
+    client
+        .database(DB_NAME)
+        .collection::<Document>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .update_one(
+            doc! { "_id": id, "user_id": user_id },
+            doc! { "$set": { "quiet_hours": quiet_hours } },
+            None,
+        )
+        .await
+        .map(|_| ())
+}
+
+/// Active subscriptions that cover at least one of `regions`.
+pub async fn find_subscriptions(
+    client: &Client,
+    regions: Vec<String>,
+) -> DbResult<Vec<Subscription>> {
+    client
+        .database(DB_NAME)
+        .collection::<Subscription>(SUBSCRIPTIONS_COLLECTION_NAME)
+        .find(
+            doc! {
+                "paused": false,
+                "regions": { "$in": regions },
+            },
+            None,
+        )
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,170 @@
+use bson::oid::ObjectId;
+use bson::{doc, Document};
+use chrono::{Duration, Utc};
+use futures::StreamExt;
+use mongodb::error::{BulkWriteFailure, ErrorKind, Result as DbResult};
+use mongodb::options::InsertManyOptions;
+use mongodb::Client;
+
+use super::models::{Deletion, DeletionPreview, TrashBatch};
+use super::{DB_NAME, MESSAGES_COLLECTION_NAME, TRASH_COLLECTION_NAME};
+
+lazy_static::lazy_static! {
+    static ref TRASH_RETENTION_DAYS: i64 = std::env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse TRASH_RETENTION_DAYS as i64"))
+        .unwrap_or(30);
+}
+
+const CHUNK_SIZE: usize = 1000;
+const DUPLICATE_KEY: i32 = 11000;
+
+fn filter(deletion: Deletion) -> Document {
+    match deletion {
+        Deletion::Message(id) => doc! { "_id": id },
+        Deletion::Before(before) => super::db::period_filter(None, Some(before)),
+    }
+}
+
+pub async fn preview_deletion(client: &Client, deletion: Deletion) -> DbResult<DeletionPreview> {
+    let pipeline = vec![
+        doc! { "$match": filter(deletion) },
+        doc! { "$group": {
+            "_id": null,
+            "count": { "$sum": 1 },
+            "first": { "$min": "$timestamp" },
+            "last": { "$max": "$timestamp" },
+        } },
+    ];
+    let preview = client
+        .database(DB_NAME)
+        .collection::<Document>(MESSAGES_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .next()
+        .await
+        .transpose()?;
+    match preview {
+        Some(p) => Ok(bson::from_document(p)?),
+        None => Ok(DeletionPreview::default()),
+    }
+}
+
+/// Moves the matching messages to the trash as one batch, in chunks so a large
+/// deletion doesn't have to fit in memory.
+pub async fn trash_messages(
+    client: &Client,
+    deletion: Deletion,
+    trashed_by: i64,
+) -> DbResult<TrashBatch> {
+    let db = client.database(DB_NAME);
+    let messages = db.collection::<Document>(MESSAGES_COLLECTION_NAME);
+    let trash = db.collection::<Document>(TRASH_COLLECTION_NAME);
+
+    let now = Utc::now();
+    let batch = TrashBatch {
+        batch: ObjectId::new(),
+        count: 0,
+        trashed_by,
+        trashed_at: now,
+        expires_at: now + Duration::days(*TRASH_RETENTION_DAYS),
+    };
+    let mut count = 0;
+    let mut cursor = messages.find(filter(deletion), None).await?;
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    loop {
+        let message = cursor.next().await.transpose()?;
+        if let Some(message) = message {
+            chunk.push(message);
+            if chunk.len() < CHUNK_SIZE {
+                continue;
+            }
+        }
+        if chunk.is_empty() {
+            break;
+        }
+
+        let ids = chunk
+            .iter()
+            .filter_map(|m| m.get_object_id("_id").ok())
+            .collect::<Vec<_>>();
+        let trashed = chunk.drain(..).map(|message| {
+            doc! {
+                "batch": batch.batch,
+                "trashed_by": trashed_by,
+                "trashed_at": bson::DateTime::from_chrono(batch.trashed_at),
+                "expires_at": bson::DateTime::from_chrono(batch.expires_at),
+                "message": message,
+            }
+        });
+        trash.insert_many(trashed, None).await?;
+        count += messages
+            .delete_many(doc! { "_id": { "$in": ids } }, None)
+            .await?
+            .deleted_count as usize;
+    }
+    Ok(TrashBatch { count, ..batch })
+}
+
+pub async fn list_trash(client: &Client) -> DbResult<Vec<TrashBatch>> {
+    let pipeline = vec![
+        doc! { "$group": {
+            "_id": "$batch",
+            "count": { "$sum": 1 },
+            "trashed_by": { "$first": "$trashed_by" },
+            "trashed_at": { "$first": "$trashed_at" },
+            "expires_at": { "$first": "$expires_at" },
+        } },
+        doc! { "$sort": { "trashed_at": -1 } },
+    ];
+    client
+        .database(DB_NAME)
+        .collection::<Document>(TRASH_COLLECTION_NAME)
+        .aggregate(pipeline, None)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .map(|d| Ok(bson::from_document(d?)?))
+        .collect()
+}
+
+/// Moves a batch back to the messages. Returns the number of restored messages.
+pub async fn restore_batch(client: &Client, batch: ObjectId) -> DbResult<usize> {
+    let db = client.database(DB_NAME);
+    let messages = db.collection::<Document>(MESSAGES_COLLECTION_NAME);
+    let trash = db.collection::<Document>(TRASH_COLLECTION_NAME);
+
+    let restored = trash
+        .find(doc! { "batch": batch }, None)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .map(|d| Ok(d?.get_document("message").cloned().unwrap_or_default()))
+        .collect::<DbResult<Vec<_>>>()?;
+    if restored.is_empty() {
+        return Ok(0);
+    }
+    let mut count = restored.len();
+    // Messages that are already back in the archive are skipped, any other failure
+    // keeps the batch in the trash.
+    if let Err(e) = messages
+        .insert_many(
+            restored,
+            InsertManyOptions::builder().ordered(false).build(),
+        )
+        .await
+    {
+        match *e.kind {
+            ErrorKind::BulkWrite(BulkWriteFailure {
+                write_errors: Some(ref errors),
+                write_concern_error: None,
+                ..
+            }) if errors.iter().all(|e| e.code == DUPLICATE_KEY) => count -= errors.len(),
+            _ => return Err(e),
+        }
+    }
+    trash.delete_many(doc! { "batch": batch }, None).await?;
+    Ok(count)
+}
@@ -1,17 +1,36 @@
 use bson::oid::ObjectId;
-use chrono::{DateTime, Utc};
+use bson::{doc, Document};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
 use futures::StreamExt;
 use mongodb::Client;
-use std::collections::HashSet;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use super::models::Message;
-use super::models::UserGroup;
+use super::models::{
+    AccessRequest, AccessStatus, AuditAction, AuditEntry, AuditFilter, ChatSettings, DailyActivity,
+    Deletion, DeletionPreview, Delivery, Digest, EditorStat, Expiry, Invite, Message, Profile,
+    QuietHours, Schedule, Subscription, TrashBatch,
+};
+use super::models::{Permission, UserGroup};
+use super::{CHATS_COLLECTION_NAME, SUBSCRIPTIONS_COLLECTION_NAME, USERS_COLLECTION_NAME};
 use crate::db_utils::models::DbStat;
+use crate::i18n::{tr, Language};
 
 type Error = crate::db_utils::error::Error;
 type Result<T> = std::result::Result<T, Error>;
 
+/// How many entries `/audit` shows at most.
+const AUDIT_LIMIT: i64 = 50;
+const INVITE_TOKEN_LEN: usize = 24;
+
+fn deletion_args(deletion: Deletion) -> Document {
+    match deletion {
+        Deletion::Message(id) => doc! { "message": id },
+        Deletion::Before(before) => doc! { "before": bson::DateTime::from_chrono(before) },
+    }
+}
+
 #[derive(Clone)]
 pub struct User {
     pub client: Arc<Client>,
@@ -27,56 +46,165 @@ impl User {
         Ok(super::db::get_user_group(&self.client, self.id).await?)
     }
 
-    async fn try_admin(&self) -> Result<()> {
+    /// Checks that the user's role grants `permission`.
+    pub async fn require(&self, permission: Permission) -> Result<UserGroup> {
         let group = self.get_group().await?;
-        match group {
-            UserGroup::Admin => Ok(()),
-            current => Err(Error::PrivlegeError {
-                desired: UserGroup::Admin,
+        match group.has(permission) {
+            true => Ok(group),
+            false => Err(Error::PrivlegeError {
+                permission,
+                current: group,
+            }),
+        }
+    }
+
+    /// Checks that the user may grant or revoke `regions` of user `id`.
+    /// A regional admin can't change other admins.
+    async fn require_scope(&self, id: i64, regions: &[String]) -> Result<()> {
+        let current = self.require_regions(regions).await?;
+        if !current.has(Permission::ManageUsers)
+            && super::db::get_user_group(&self.client, id)
+                .await?
+                .has(Permission::ManageRegionUsers)
+        {
+            return Err(Error::PrivlegeError {
+                permission: Permission::ManageUsers,
                 current,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the user may grant `regions`: anywhere with `ManageUsers`, or within
+    /// their administrative regions with `ManageRegionUsers`.
+    async fn require_regions(&self, regions: &[String]) -> Result<UserGroup> {
+        let current = self.require(Permission::ManageRegionUsers).await?;
+        if current.has(Permission::ManageUsers) {
+            return Ok(current);
+        }
+        let scope = super::db::get_admin_regions(&self.client, self.id).await?;
+        let rejected = regions
+            .iter()
+            .filter(|r| !scope.contains(*r))
+            .cloned()
+            .collect::<Vec<_>>();
+        match rejected.is_empty() {
+            true => Ok(current),
+            false => Err(Error::RegionsOutOfScope {
+                current,
+                regions: rejected,
             }),
         }
     }
 
-    pub fn start(&self) -> Result<&'static str> {
-        Ok("Hello world!")
+    /// Records a mutating operation. The operation has already been done, so a failed
+    /// write is only logged.
+    async fn audit(
+        &self,
+        action: AuditAction,
+        target: Option<i64>,
+        args: Document,
+        before: Option<Document>,
+        after: Option<Document>,
+    ) {
+        let entry = AuditEntry {
+            _id: ObjectId::new(),
+            actor: self.id,
+            action,
+            target,
+            args,
+            before,
+            after,
+            timestamp: Utc::now(),
+        };
+        if let Err(e) = super::audit::insert_entry(&self.client, &entry).await {
+            log::error!("Can't write an audit entry for {}. Error: {}", action, e);
+        }
     }
 
-    pub async fn help(&self) -> Result<&'static str> {
+    async fn snapshot(&self, collection: &str, filter: Document) -> Result<Option<Document>> {
+        Ok(super::audit::snapshot(&self.client, collection, filter).await?)
+    }
+
+    async fn user_snapshot(&self, id: i64) -> Result<Option<Document>> {
+        self.snapshot(USERS_COLLECTION_NAME, doc! { "id": id })
+            .await
+    }
+
+    async fn subscription_snapshot(&self, id: ObjectId) -> Result<Option<Document>> {
+        self.snapshot(SUBSCRIPTIONS_COLLECTION_NAME, doc! { "_id": id })
+            .await
+    }
+
+    pub fn start(&self, lang: Language) -> Result<String> {
+        Ok(tr!(lang, "start"))
+    }
+
+    pub async fn help(&self, lang: Language) -> Result<String> {
         let group = super::db::get_user_group(&self.client, self.id).await?;
         match group {
-            UserGroup::Admin => Ok("/list_users\n\
-                /add_user <id> [Admin]\n\
-                /del_user <id>\n\
-                /list_chats\n\
-                /add_chat <id>\n\
-                /del_chat <id>\n\
-                /listdb <DD.MM.YY> [OFFSET, по умолчанию \'+03:00\' (Мск)]\n\
-                /deldb <id>\n\
-                /cleandb <суток оставить>\n\
-                /statdb [OFFSET, по умолчанию \'+03:00\' (Мск)]\n\
-                /add_user_regions <id> <регионы через пробел или страна>\n\
-                /del_user_regions <id> <регионы через пробел или страна>\n\
-                Регионы [часов назад] [количество часов (можно опустить)] [теги]"),
-            UserGroup::Registered => {
-                Ok("Регионы [часов] [количество часов (можно опустить)] [теги]")
-            }
-            UserGroup::Unregistered => Ok("Тестовый эхо-бот"),
+            UserGroup::Unregistered => Ok(tr!(lang, "help.unregistered")),
+            group => Ok(crate::commands::help(&group, lang)),
         }
     }
 
-    pub async fn add_user_regions(&self, id: i64, regions: Vec<String>) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::add_user_regions(&self.client, id, regions).await?)
+    /// Opens `regions` to user `id`, for `term` from now or permanently.
+    pub async fn add_user_regions(
+        &self,
+        id: i64,
+        regions: Vec<String>,
+        term: Option<Duration>,
+    ) -> Result<()> {
+        self.require_scope(id, &regions).await?;
+        let before = self.user_snapshot(id).await?;
+        let expiry = term.map(|t| Expiry::after(t, self.id));
+        let args = doc! {
+            "regions": &regions,
+            "expires_at": expiry.as_ref().map(|e| bson::DateTime::from_chrono(e.expires_at)),
+        };
+        super::db::add_user_regions(&self.client, id, regions, expiry).await?;
+        let after = self.user_snapshot(id).await?;
+        self.audit(AuditAction::AddUserRegions, Some(id), args, before, after)
+            .await;
+        Ok(())
     }
 
     pub async fn del_user_regions(&self, id: i64, regions: Vec<String>) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::del_user_regions(&self.client, id, regions).await?)
+        self.require_scope(id, &regions).await?;
+        let before = self.user_snapshot(id).await?;
+        let args = doc! { "regions": &regions };
+        super::db::del_user_regions(&self.client, id, regions).await?;
+        let after = self.user_snapshot(id).await?;
+        self.audit(AuditAction::DelUserRegions, Some(id), args, before, after)
+            .await;
+        Ok(())
+    }
+
+    /// Sets the regions user `id` may grant and revoke as a regional admin.
+    pub async fn add_admin_regions(&self, id: i64, regions: Vec<String>) -> Result<()> {
+        self.require(Permission::ManageUsers).await?;
+        let before = self.user_snapshot(id).await?;
+        let args = doc! { "regions": &regions };
+        super::db::add_admin_regions(&self.client, id, regions).await?;
+        let after = self.user_snapshot(id).await?;
+        self.audit(AuditAction::AddAdminRegions, Some(id), args, before, after)
+            .await;
+        Ok(())
+    }
+
+    pub async fn del_admin_regions(&self, id: i64, regions: Vec<String>) -> Result<()> {
+        self.require(Permission::ManageUsers).await?;
+        let before = self.user_snapshot(id).await?;
+        let args = doc! { "regions": &regions };
+        super::db::del_admin_regions(&self.client, id, regions).await?;
+        let after = self.user_snapshot(id).await?;
+        self.audit(AuditAction::DelAdminRegions, Some(id), args, before, after)
+            .await;
+        Ok(())
     }
 
     pub async fn list_users(&self, groups: Vec<UserGroup>) -> Result<Vec<super::models::User>> {
-        self.try_admin().await?;
+        self.require(Permission::ManageRegionUsers).await?;
         Ok(super::db::list_users(&self.client, groups)
             .await?
             .collect::<Vec<_>>()
@@ -86,42 +214,153 @@ impl User {
     }
 
     pub async fn add_user(&self, user: super::models::User) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::add_user(&self.client, user).await?)
+        // Regional admins may only register readers.
+        match self.require(Permission::ManageRegionUsers).await? {
+            group if group.has(Permission::ManageUsers) => {}
+            _ if user.group == UserGroup::Registered => {}
+            current => {
+                return Err(Error::PrivlegeError {
+                    permission: Permission::ManageUsers,
+                    current,
+                })
+            }
+        }
+        let id = user.id;
+        let before = self.user_snapshot(id).await?;
+        let args = doc! {
+            "group": user.group.to_string(),
+            "expires_at": user.expiry.as_ref().map(|e| bson::DateTime::from_chrono(e.expires_at)),
+        };
+        super::db::add_user(&self.client, user).await?;
+        let after = self.user_snapshot(id).await?;
+        self.audit(AuditAction::AddUser, Some(id), args, before, after)
+            .await;
+        Ok(())
     }
 
     pub async fn list_chats(&self) -> Result<HashSet<i64>> {
-        self.try_admin().await?;
-        Ok(super::db::get_chats(&self.client).await?)
+        self.require(Permission::ManageChats).await?;
+        Ok(super::db::get_chats(&self.client)
+            .await?
+            .into_iter()
+            .map(|c| c.id)
+            .collect())
     }
 
-    pub async fn add_chat(&self, id: i64) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::insert_chat(&self.client, id).await?)
+    /// Time of the last saved message of every chat, see [`super::db::last_ingestions`].
+    pub async fn last_ingestions(
+        &self,
+        region: Option<&str>,
+    ) -> Result<HashMap<i64, DateTime<Utc>>> {
+        self.require(Permission::ManageChats).await?;
+        Ok(super::db::last_ingestions(&self.client, region).await?)
+    }
+
+    pub async fn add_chat(&self, chat: super::models::Chat) -> Result<()> {
+        self.require(Permission::ManageChats).await?;
+        let id = chat.id;
+        super::db::insert_chat(&self.client, &chat).await?;
+        let after = self
+            .snapshot(CHATS_COLLECTION_NAME, doc! { "id": id })
+            .await?;
+        self.audit(AuditAction::AddChat, Some(id), doc! {}, None, after)
+            .await;
+        Ok(())
+    }
+
+    pub async fn set_chat_settings(&self, id: i64, settings: &ChatSettings) -> Result<()> {
+        self.require(Permission::ManageChats).await?;
+        let before = self
+            .snapshot(CHATS_COLLECTION_NAME, doc! { "id": id })
+            .await?;
+        if !super::db::set_chat_settings(&self.client, id, settings).await? {
+            return Err(Error::NoChat(id));
+        }
+        let after = self
+            .snapshot(CHATS_COLLECTION_NAME, doc! { "id": id })
+            .await?;
+        self.audit(
+            AuditAction::SetChatSettings,
+            Some(id),
+            doc! {},
+            before,
+            after,
+        )
+        .await;
+        Ok(())
     }
 
     pub async fn delete_chat(&self, id: i64) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::delete_chat(&self.client, id).await?)
+        self.require(Permission::ManageChats).await?;
+        let before = self
+            .snapshot(CHATS_COLLECTION_NAME, doc! { "id": id })
+            .await?;
+        super::db::delete_chat(&self.client, id).await?;
+        self.audit(AuditAction::DeleteChat, Some(id), doc! {}, before, None)
+            .await;
+        Ok(())
     }
 
     pub async fn delete_user(&self, user_id: i64) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::delete_user(&self.client, user_id).await?)
+        self.require(Permission::ManageUsers).await?;
+        let before = self.user_snapshot(user_id).await?;
+        super::db::delete_user(&self.client, user_id).await?;
+        super::subscriptions::delete_user_subscriptions(&self.client, &[user_id]).await?;
+        super::digests::delete_user_digests(&self.client, &[user_id]).await?;
+        self.audit(
+            AuditAction::DeleteUser,
+            Some(user_id),
+            doc! {},
+            before,
+            None,
+        )
+        .await;
+        Ok(())
     }
 
-    pub async fn delete_message(&self, id: ObjectId) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::delete_message(&self.client, id).await?)
+    pub async fn preview_deletion(&self, deletion: Deletion) -> Result<DeletionPreview> {
+        self.require(Permission::ManageMessages).await?;
+        Ok(super::trash::preview_deletion(&self.client, deletion).await?)
     }
 
-    pub async fn delete_messages_period(
-        &self,
-        after: Option<DateTime<Utc>>,
-        before: Option<DateTime<Utc>>,
-    ) -> Result<()> {
-        self.try_admin().await?;
-        Ok(super::db::delete_messages_period(&self.client, after, before).await?)
+    /// Moves messages to the trash, from where they can be restored until the batch expires.
+    pub async fn delete_messages(&self, deletion: Deletion) -> Result<TrashBatch> {
+        self.require(Permission::ManageMessages).await?;
+        let batch = super::trash::trash_messages(&self.client, deletion, self.id).await?;
+        let after = doc! { "batch": batch.batch, "count": batch.count as i64 };
+        self.audit(
+            AuditAction::DeleteMessages,
+            None,
+            deletion_args(deletion),
+            None,
+            Some(after),
+        )
+        .await;
+        Ok(batch)
+    }
+
+    pub async fn list_trash(&self) -> Result<Vec<TrashBatch>> {
+        self.require(Permission::ManageMessages).await?;
+        Ok(super::trash::list_trash(&self.client).await?)
+    }
+
+    pub async fn restore(&self, batch: ObjectId) -> Result<usize> {
+        self.require(Permission::ManageMessages).await?;
+        match super::trash::restore_batch(&self.client, batch).await? {
+            0 => Err(Error::NoTrashBatch(batch)),
+            n => {
+                let after = doc! { "count": n as i64 };
+                self.audit(
+                    AuditAction::RestoreMessages,
+                    None,
+                    doc! { "batch": batch },
+                    None,
+                    Some(after),
+                )
+                .await;
+                Ok(n)
+            }
+        }
     }
 
     pub async fn list_messages(
@@ -131,7 +370,7 @@ impl User {
         after: Option<DateTime<Utc>>,
         before: Option<DateTime<Utc>>,
     ) -> Result<Vec<Message>> {
-        self.try_admin().await?;
+        self.require(Permission::ManageMessages).await?;
         Ok(
             super::db::list_messages(&self.client, regions, tags, after, before)
                 .await?
@@ -142,8 +381,390 @@ impl User {
         )
     }
 
-    pub async fn stat(&self, offset: chrono::offset::FixedOffset) -> Result<DbStat> {
-        self.try_admin().await?;
-        Ok(super::db::stat(&self.client, offset).await?)
+    pub async fn stat(
+        &self,
+        days: u32,
+        offset: FixedOffset,
+        all_regions: &HashSet<&'static str>,
+    ) -> Result<DbStat> {
+        self.require(Permission::ViewStats).await?;
+        Ok(super::db::stat(&self.client, days, offset, all_regions).await?)
+    }
+
+    /// Messages saved by every editor over the last `days` days, see
+    /// [`super::db::editor_stats`].
+    pub async fn editor_stats(&self, days: u32, offset: FixedOffset) -> Result<Vec<EditorStat>> {
+        self.require(Permission::ViewStats).await?;
+        let since = Utc::now() - Duration::days(days.into());
+        Ok(super::db::editor_stats(&self.client, since, offset).await?)
+    }
+
+    /// Messages per day over the last `days` days, see [`super::db::daily_activity`].
+    pub async fn daily_activity(&self, days: u32, offset: FixedOffset) -> Result<DailyActivity> {
+        self.require(Permission::ViewStats).await?;
+        Ok(super::db::daily_activity(&self.client, days, offset).await?)
+    }
+
+    /// The user's own settings. Every user has a profile, registered or not.
+    pub async fn profile(&self) -> Result<Profile> {
+        Ok(super::db::get_profile(&self.client, self.id).await?)
+    }
+
+    pub async fn set_profile(&self, profile: Profile) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let before = self.user_snapshot(self.id).await?;
+        super::db::set_profile(&self.client, self.id, &profile).await?;
+        let after = self.user_snapshot(self.id).await?;
+        self.audit(
+            AuditAction::SetProfile,
+            Some(self.id),
+            doc! {},
+            before,
+            after,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// The user's own delivery settings, `None` if the deployment default is used.
+    pub async fn delivery(&self) -> Result<Option<Delivery>> {
+        self.require(Permission::Query).await?;
+        Ok(super::db::get_delivery(&self.client, self.id).await?)
+    }
+
+    pub async fn set_delivery(&self, delivery: Option<Delivery>) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let before = super::db::get_delivery(&self.client, self.id).await?;
+        super::db::set_delivery(&self.client, self.id, delivery).await?;
+        let snapshot = |d: Option<Delivery>| d.and_then(|d| bson::to_document(&d).ok());
+        self.audit(
+            AuditAction::SetDelivery,
+            Some(self.id),
+            doc! {},
+            snapshot(before),
+            snapshot(delivery),
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Subscribes the user to new messages in `regions`. Regions the user has no access to are dropped.
+    pub async fn subscribe(&self, regions: Vec<String>, tags: Vec<String>) -> Result<Subscription> {
+        self.require(Permission::Query).await?;
+        let allowed = super::db::get_allowed_regions(&self.client, self.id).await?;
+        let regions = regions
+            .into_iter()
+            .filter(|r| allowed.contains(r))
+            .collect::<Vec<_>>();
+        if regions.is_empty() {
+            return Err(Error::NoAllowedRegions);
+        }
+        let subscription = Subscription {
+            _id: ObjectId::new(),
+            user_id: self.id,
+            regions,
+            tags,
+            quiet_hours: None,
+            paused: false,
+        };
+        super::subscriptions::insert_subscription(&self.client, &subscription).await?;
+        self.audit(
+            AuditAction::Subscribe,
+            Some(self.id),
+            doc! {},
+            None,
+            bson::to_document(&subscription).ok(),
+        )
+        .await;
+        Ok(subscription)
+    }
+
+    pub async fn list_subscriptions(&self) -> Result<Vec<Subscription>> {
+        self.require(Permission::Query).await?;
+        Ok(super::subscriptions::list_subscriptions(&self.client, self.id).await?)
+    }
+
+    /// Resolves the 1-based number shown by `/subscriptions` into a subscription id.
+    async fn nth_subscription(&self, n: usize) -> Result<ObjectId> {
+        super::subscriptions::list_subscriptions(&self.client, self.id)
+            .await?
+            .get(n.wrapping_sub(1))
+            .map(|s| s._id)
+            .ok_or(Error::NoSubscription(n))
+    }
+
+    pub async fn unsubscribe(&self, n: usize) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let id = self.nth_subscription(n).await?;
+        let before = self.subscription_snapshot(id).await?;
+        super::subscriptions::delete_subscription(&self.client, self.id, id).await?;
+        self.audit(
+            AuditAction::Unsubscribe,
+            Some(self.id),
+            doc! {},
+            before,
+            None,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Pauses or resumes subscription number `n`, or all of them when `n` is `None`.
+    pub async fn set_subscriptions_paused(&self, n: Option<usize>, paused: bool) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let id = match n {
+            Some(n) => Some(self.nth_subscription(n).await?),
+            None => None,
+        };
+        let before = match id {
+            Some(id) => self.subscription_snapshot(id).await?,
+            None => None,
+        };
+        super::subscriptions::set_subscriptions_paused(&self.client, self.id, id, paused).await?;
+        let after = match id {
+            Some(id) => self.subscription_snapshot(id).await?,
+            None => None,
+        };
+        let args = doc! { "subscription": id, "paused": paused };
+        self.audit(
+            AuditAction::PauseSubscriptions,
+            Some(self.id),
+            args,
+            before,
+            after,
+        )
+        .await;
+        Ok(())
+    }
+
+    pub async fn set_quiet_hours(&self, n: usize, quiet_hours: Option<QuietHours>) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let id = self.nth_subscription(n).await?;
+        let before = self.subscription_snapshot(id).await?;
+        super::subscriptions::set_quiet_hours(&self.client, self.id, id, quiet_hours).await?;
+        let after = self.subscription_snapshot(id).await?;
+        self.audit(
+            AuditAction::SetQuietHours,
+            Some(self.id),
+            doc! {},
+            before,
+            after,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Creates a digest over `regions` the user has access to. The first run covers messages saved after now.
+    pub async fn add_digest(
+        &self,
+        regions: Vec<String>,
+        tags: Vec<String>,
+        schedule: Schedule,
+    ) -> Result<Digest> {
+        self.require(Permission::Query).await?;
+        let allowed = super::db::get_allowed_regions(&self.client, self.id).await?;
+        let regions = regions
+            .into_iter()
+            .filter(|r| allowed.contains(r))
+            .collect::<Vec<_>>();
+        if regions.is_empty() {
+            return Err(Error::NoAllowedRegions);
+        }
+        let digest = Digest {
+            _id: ObjectId::new(),
+            user_id: self.id,
+            regions,
+            tags,
+            schedule,
+            last_run: Utc::now(),
+        };
+        super::digests::insert_digest(&self.client, &digest).await?;
+        self.audit(
+            AuditAction::AddDigest,
+            Some(self.id),
+            doc! {},
+            None,
+            bson::to_document(&digest).ok(),
+        )
+        .await;
+        Ok(digest)
+    }
+
+    pub async fn list_digests(&self) -> Result<Vec<Digest>> {
+        self.require(Permission::Query).await?;
+        Ok(super::digests::list_digests(&self.client, Some(self.id)).await?)
+    }
+
+    pub async fn delete_digest(&self, n: usize) -> Result<()> {
+        self.require(Permission::Query).await?;
+        let digest = super::digests::list_digests(&self.client, Some(self.id))
+            .await?
+            .into_iter()
+            .nth(n.wrapping_sub(1))
+            .ok_or(Error::NoDigest(n))?;
+        super::digests::delete_digest(&self.client, self.id, digest._id).await?;
+        self.audit(
+            AuditAction::DeleteDigest,
+            Some(self.id),
+            doc! {},
+            bson::to_document(&digest).ok(),
+            None,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// The latest audit entries matching `filter`, newest first.
+    pub async fn audit_log(&self, filter: AuditFilter) -> Result<Vec<AuditEntry>> {
+        self.require(Permission::ViewAudit).await?;
+        Ok(super::audit::find_entries(&self.client, &filter, AUDIT_LIMIT).await?)
+    }
+
+    /// Asks admins for access to `regions`. A user may have one pending request.
+    pub async fn request_access(
+        &self,
+        regions: Vec<String>,
+        reason: String,
+    ) -> Result<AccessRequest> {
+        if super::access::pending_request(&self.client, self.id)
+            .await?
+            .is_some()
+        {
+            return Err(Error::PendingAccessRequest);
+        }
+        let request = AccessRequest {
+            _id: ObjectId::new(),
+            user_id: self.id,
+            regions,
+            reason,
+            status: AccessStatus::Pending,
+            decided_by: None,
+            approved_regions: Vec::new(),
+            created_at: Utc::now(),
+        };
+        super::access::insert_request(&self.client, &request).await?;
+        let args = doc! { "request": request._id, "regions": &request.regions };
+        self.audit(AuditAction::RequestAccess, Some(self.id), args, None, None)
+            .await;
+        Ok(request)
+    }
+
+    pub async fn access_request(&self, id: ObjectId) -> Result<AccessRequest> {
+        self.require(Permission::ManageRegionUsers).await?;
+        super::access::get_request(&self.client, id)
+            .await?
+            .ok_or(Error::NoAccessRequest(id))
+    }
+
+    /// Approves `regions` of request `id`, registering the requester if needed, or
+    /// denies the request when `regions` is empty.
+    pub async fn decide_access(&self, id: ObjectId, regions: Vec<String>) -> Result<AccessRequest> {
+        let status = match regions.is_empty() {
+            true => AccessStatus::Denied,
+            false => AccessStatus::Approved,
+        };
+        let request = self.access_request(id).await?;
+        if status == AccessStatus::Approved {
+            self.require_scope(request.user_id, &regions).await?;
+        }
+        let request = super::access::decide_request(&self.client, id, status, self.id, &regions)
+            .await?
+            .ok_or(Error::NoAccessRequest(id))?;
+        if status == AccessStatus::Denied {
+            let args = doc! { "request": id, "regions": &request.regions };
+            self.audit(
+                AuditAction::DenyAccess,
+                Some(request.user_id),
+                args,
+                None,
+                None,
+            )
+            .await;
+            return Ok(request);
+        }
+
+        let user_id = request.user_id;
+        let approved = async {
+            if super::db::get_user_group(&self.client, user_id).await? == UserGroup::Unregistered {
+                let group = UserGroup::Registered;
+                let before = self.user_snapshot(user_id).await?;
+                let args = doc! { "group": group.to_string() };
+                super::db::register_user(&self.client, user_id, &group).await?;
+                let after = self.user_snapshot(user_id).await?;
+                self.audit(AuditAction::AddUser, Some(user_id), args, before, after)
+                    .await;
+            }
+            self.add_user_regions(user_id, regions, None).await
+        };
+        if let Err(e) = approved.await {
+            super::access::reopen_request(&self.client, id).await?;
+            return Err(e);
+        }
+        Ok(request)
+    }
+
+    /// Creates an invite into `regions` for `uses` registrations. Regional admins may
+    /// only invite readers into their administrative regions.
+    pub async fn create_invite(
+        &self,
+        group: UserGroup,
+        regions: Vec<String>,
+        uses: u32,
+        term: Option<Duration>,
+    ) -> Result<Invite> {
+        let current = self.require_regions(&regions).await?;
+        if group != UserGroup::Registered && !current.has(Permission::ManageUsers) {
+            return Err(Error::PrivlegeError {
+                permission: Permission::ManageUsers,
+                current,
+            });
+        }
+        let invite = Invite {
+            token: rand::thread_rng()
+                .sample_iter(&rand::distributions::Alphanumeric)
+                .take(INVITE_TOKEN_LEN)
+                .map(char::from)
+                .collect(),
+            created_by: self.id,
+            group,
+            regions,
+            uses_left: uses,
+            expires_at: term.map(|t| Utc::now() + t),
+        };
+        super::invites::insert_invite(&self.client, &invite).await?;
+        self.audit(
+            AuditAction::CreateInvite,
+            None,
+            doc! { "token": &invite.token, "uses": uses as i64 },
+            None,
+            bson::to_document(&invite).ok(),
+        )
+        .await;
+        Ok(invite)
+    }
+
+    /// Registers the user with the group and regions of invite `token`. A registered
+    /// user keeps their group and only gets the regions.
+    pub async fn redeem_invite(&self, token: &str) -> Result<Invite> {
+        let current = self.get_group().await?;
+        let invite = super::invites::redeem_invite(&self.client, token, Utc::now())
+            .await?
+            .ok_or(Error::BadInvite)?;
+        let before = self.user_snapshot(self.id).await?;
+        if current == UserGroup::Unregistered {
+            super::db::register_user(&self.client, self.id, &invite.group).await?;
+        }
+        super::db::add_user_regions(&self.client, self.id, invite.regions.clone(), None).await?;
+        let after = self.user_snapshot(self.id).await?;
+        let args = doc! { "token": token, "created_by": invite.created_by };
+        self.audit(
+            AuditAction::RedeemInvite,
+            Some(self.id),
+            args,
+            before,
+            after,
+        )
+        .await;
+        Ok(invite)
     }
 }
@@ -4,7 +4,12 @@ use bson::{doc, Document};
 use mongodb::Client;
 use mongodb::{error::Result, IndexModel};
 
-use crate::db_utils::{CHATS_COLLECTION_NAME, MESSAGES_COLLECTION_NAME};
+use crate::db_utils::{
+    ACCESS_REQUESTS_COLLECTION_NAME, AUDIT_COLLECTION_NAME, CHATS_COLLECTION_NAME,
+    CURSORS_COLLECTION_NAME, DIGESTS_COLLECTION_NAME, DIGEST_RUNS_COLLECTION_NAME,
+    INVITES_COLLECTION_NAME, MESSAGES_COLLECTION_NAME, SUBSCRIPTIONS_COLLECTION_NAME,
+    TRASH_COLLECTION_NAME,
+};
 
 use super::{DB_NAME, USERS_COLLECTION_NAME};
 
@@ -62,9 +67,70 @@ pub async fn validate_db(client: &Client) -> Result<()> {
         h
     };
 
+    let subscriptions = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(SUBSCRIPTIONS_INDEX_NAME, subscriptions_index_build);
+        h
+    };
+
+    let digests = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(USER_ID_INDEX_NAME, user_id_index_build);
+        h
+    };
+
+    let digest_runs = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(DIGEST_RUNS_INDEX_NAME, digest_runs_index_build);
+        h
+    };
+
+    let cursors = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(CURSORS_TTL_INDEX_NAME, cursors_ttl_index_build);
+        h
+    };
+
+    let trash = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(TRASH_BATCH_INDEX_NAME, trash_batch_index_build);
+        h.insert(TRASH_TTL_INDEX_NAME, trash_ttl_index_build);
+        h
+    };
+
+    let audit = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(AUDIT_ACTOR_INDEX_NAME, audit_actor_index_build);
+        h.insert(AUDIT_TARGET_INDEX_NAME, audit_target_index_build);
+        h
+    };
+
+    let access_requests = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(
+            ACCESS_REQUEST_USER_INDEX_NAME,
+            access_request_user_index_build,
+        );
+        h
+    };
+
+    let invites = {
+        let mut h = HashMap::<_, fn() -> IndexModel>::with_capacity(4);
+        h.insert(INVITES_TTL_INDEX_NAME, invites_ttl_index_build);
+        h
+    };
+
     validate_col(client, MESSAGES_COLLECTION_NAME, messages).await?;
     validate_col(client, USERS_COLLECTION_NAME, users).await?;
     validate_col(client, CHATS_COLLECTION_NAME, chats).await?;
+    validate_col(client, SUBSCRIPTIONS_COLLECTION_NAME, subscriptions).await?;
+    validate_col(client, DIGESTS_COLLECTION_NAME, digests).await?;
+    validate_col(client, DIGEST_RUNS_COLLECTION_NAME, digest_runs).await?;
+    validate_col(client, CURSORS_COLLECTION_NAME, cursors).await?;
+    validate_col(client, TRASH_COLLECTION_NAME, trash).await?;
+    validate_col(client, AUDIT_COLLECTION_NAME, audit).await?;
+    validate_col(client, ACCESS_REQUESTS_COLLECTION_NAME, access_requests).await?;
+    validate_col(client, INVITES_COLLECTION_NAME, invites).await?;
 
     log::info!("Database {} is valid", DB_NAME);
 
@@ -73,6 +139,16 @@ pub async fn validate_db(client: &Client) -> Result<()> {
 
 const ID_INDEX_NAME: &str = "id_index";
 const MESSAGES_INDEX_NAME: &str = "messages_index";
+const SUBSCRIPTIONS_INDEX_NAME: &str = "subscriptions_index";
+const USER_ID_INDEX_NAME: &str = "user_id_index";
+const DIGEST_RUNS_INDEX_NAME: &str = "digest_runs_index";
+const CURSORS_TTL_INDEX_NAME: &str = "cursors_ttl_index";
+const TRASH_BATCH_INDEX_NAME: &str = "trash_batch_index";
+const TRASH_TTL_INDEX_NAME: &str = "trash_ttl_index";
+const AUDIT_ACTOR_INDEX_NAME: &str = "audit_actor_index";
+const AUDIT_TARGET_INDEX_NAME: &str = "audit_target_index";
+const ACCESS_REQUEST_USER_INDEX_NAME: &str = "access_request_user_index";
+const INVITES_TTL_INDEX_NAME: &str = "invites_ttl_index";
 
 fn id_index_build() -> IndexModel {
     mongodb::IndexModel::builder()
@@ -100,3 +176,124 @@ fn messages_index_build() -> IndexModel {
         )
         .build()
 }
+
+fn subscriptions_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! {
+            "regions": 1,
+            "paused": 1,
+        })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(SUBSCRIPTIONS_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+fn user_id_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "user_id": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(USER_ID_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+fn digest_runs_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! {
+            "digest_id": 1,
+            "ran_at": -1,
+        })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(DIGEST_RUNS_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+/// Result cursors are only needed while the user pages through results.
+fn cursors_ttl_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "created": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(CURSORS_TTL_INDEX_NAME.to_string())
+                .expire_after(std::time::Duration::from_secs(24 * 60 * 60))
+                .build(),
+        )
+        .build()
+}
+
+fn trash_batch_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "batch": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(TRASH_BATCH_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+/// Trashed messages are purged once their `expires_at` has passed.
+fn trash_ttl_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(TRASH_TTL_INDEX_NAME.to_string())
+                .expire_after(std::time::Duration::from_secs(0))
+                .build(),
+        )
+        .build()
+}
+
+fn audit_actor_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "actor": 1, "timestamp": -1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(AUDIT_ACTOR_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+fn audit_target_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "target": 1, "timestamp": -1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(AUDIT_TARGET_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+fn access_request_user_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "user_id": 1, "status": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(ACCESS_REQUEST_USER_INDEX_NAME.to_string())
+                .build(),
+        )
+        .build()
+}
+
+fn invites_ttl_index_build() -> IndexModel {
+    mongodb::IndexModel::builder()
+        .keys(doc! { "expires_at": 1 })
+        .options(
+            mongodb::options::IndexOptions::builder()
+                .name(INVITES_TTL_INDEX_NAME.to_string())
+                .expire_after(std::time::Duration::from_secs(0))
+                .build(),
+        )
+        .build()
+}
@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bson::oid::ObjectId;
+use chrono::Utc;
+use mongodb::Client;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::callbacks::answer_callback;
+use crate::common::{deliver_message, send_text};
+use crate::db_utils::{
+    self,
+    models::{CursorEntry, Delivery, DeliveryMode, Message, Profile, ResultCursor},
+};
+use crate::i18n::{tr, Language};
+use crate::sender::{self, Priority};
+
+lazy_static::lazy_static! {
+    static ref PAGE_SIZE: usize = std::env::var("PAGE_SIZE")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse PAGE_SIZE as usize"))
+        .unwrap_or(20);
+    /// Deployment default, overridden per user with /delivery.
+    static ref DEFAULT_DELIVERY: Delivery = Delivery {
+        mode: std::env::var("DELIVERY_MODE")
+            .ok()
+            .map(|s| s.parse().expect("Can't parse DELIVERY_MODE, expected forward or copy"))
+            .unwrap_or(DeliveryMode::Forward),
+        attribution: std::env::var("DELIVERY_ATTRIBUTION")
+            .ok()
+            .map(|s| s.parse().expect("Can't parse DELIVERY_ATTRIBUTION as bool"))
+            .unwrap_or(false),
+    };
+    /// Pages being sent, by cursor id: the user who may stop them and the stop flag.
+    static ref ACTIVE: Mutex<HashMap<ObjectId, (i64, Arc<AtomicBool>)>> = Mutex::new(HashMap::new());
+}
+
+pub const MORE_PREFIX: &str = "more";
+pub const STOP_PREFIX: &str = "stop";
+/// Pages shorter than this are sent without a progress message.
+const PROGRESS_MIN: usize = 5;
+/// The progress message is edited on every region change and every this many messages.
+const PROGRESS_STEP: usize = 5;
+
+pub fn default_delivery() -> Delivery {
+    *DEFAULT_DELIVERY
+}
+
+/// The profile of `user_id`, the default one when it can't be read.
+pub async fn profile_for(client: &Client, user_id: i64) -> Profile {
+    match db_utils::get_profile(client, user_id).await {
+        Ok(profile) => profile,
+        Err(e) => {
+            log::error!("Can't access the profile of {}. Error: {}", user_id, e);
+            Profile::default()
+        }
+    }
+}
+
+/// Saves grouped results as a server-side cursor and sends the first page.
+/// Later pages are requested with the "Ещё" button and come from the same snapshot.
+pub async fn deliver(
+    bot: &AutoSend<Bot>,
+    client: &Client,
+    chat_id: i64,
+    user_id: i64,
+    messages: BTreeMap<String, Vec<Message>>,
+) {
+    let mut messages = messages;
+    let federal = messages.remove_entry("РФ");
+    let entries = federal
+        .into_iter()
+        .chain(messages)
+        .flat_map(|(region, messages)| {
+            messages.into_iter().map(move |message| CursorEntry {
+                region: region.clone(),
+                message,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let cursor = ResultCursor {
+        _id: ObjectId::new(),
+        user_id,
+        created: Utc::now(),
+        entries,
+        next_page: 1,
+    };
+    let profile = profile_for(client, user_id).await;
+    if let Err(e) = db_utils::insert_cursor(client, &cursor).await {
+        log::error!("Can't save result cursor. Error: {}", e);
+        let lang = profile.language;
+        let text = tr!(lang, "fail.save_cursor", error = e);
+        send_text(bot, chat_id, text.as_str()).await;
+        return;
+    }
+    send_page(bot, chat_id, &cursor, 0, &profile).await;
+}
+
+/// Handles a press of the "Ещё" button with data `more:<cursor id>:<page>`.
+pub async fn more(cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>, client: &Client, data: &str) {
+    let mut split = data.split(':').skip(1);
+    let id = split.next().and_then(|id| id.parse::<ObjectId>().ok());
+    let page = split.next().and_then(|p| p.parse::<usize>().ok());
+    let message = match &cx.update.message {
+        Some(m) => m,
+        None => return,
+    };
+
+    let profile = profile_for(client, cx.update.from.id).await;
+    let lang = profile.language;
+    // Taking the page in the same update keeps a double tap from sending it twice.
+    let cursor = match (id, page) {
+        (Some(id), Some(page)) => db_utils::claim_page(client, id, cx.update.from.id, page)
+            .await
+            .map(|cursor| cursor.map(|cursor| (cursor, page))),
+        _ => Ok(None),
+    };
+    let (cursor, text) = match cursor {
+        Ok(Some(cursor)) => (Some(cursor), None),
+        Ok(None) => (None, Some(tr!(lang, "results.stale"))),
+        Err(e) => {
+            log::error!("Can't access result cursor. Error: {}", e);
+            (None, Some(tr!(lang, "results.db_error")))
+        }
+    };
+    answer_callback(cx, text.as_deref()).await;
+
+    if let Some((cursor, page)) = cursor {
+        remove_keyboard(&cx.requester, message).await;
+        send_page(&cx.requester, message.chat.id, &cursor, page, &profile).await;
+    }
+}
+
+async fn send_page(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    cursor: &ResultCursor,
+    page: usize,
+    profile: &Profile,
+) {
+    let lang = profile.language;
+    let total = cursor.entries.len();
+    let pages = total.div_ceil(*PAGE_SIZE);
+    let entries = cursor
+        .entries
+        .iter()
+        .skip(page * *PAGE_SIZE)
+        .take(*PAGE_SIZE)
+        .collect::<Vec<_>>();
+
+    if pages > 1 {
+        let text = tr!(
+            lang,
+            "results.page",
+            total = total,
+            page = page + 1,
+            pages = pages
+        );
+        send_text(bot, chat_id, text.as_str()).await;
+    }
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    ACTIVE
+        .lock()
+        .unwrap()
+        .insert(cursor._id, (cursor.user_id, Arc::clone(&stopped)));
+    let progress = if entries.len() >= PROGRESS_MIN {
+        send_progress(bot, chat_id, cursor._id, lang).await
+    } else {
+        None
+    };
+
+    let first = page * *PAGE_SIZE;
+    let mut sent = 0;
+    let mut region = None;
+    for entry in &entries {
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        let new_region = region != Some(&entry.region);
+        if let (Some(id), true) = (progress, new_region || sent % PROGRESS_STEP == 0) {
+            let text = tr!(
+                lang,
+                "results.progress",
+                region = entry.region,
+                sent = first + sent,
+                total = total
+            );
+            edit_progress(bot, chat_id, id, text, Some(cursor._id), lang).await;
+        }
+        if new_region {
+            region = Some(&entry.region);
+            send_text(
+                bot,
+                chat_id,
+                tr!(lang, "region", region = entry.region).as_str(),
+            )
+            .await;
+        }
+        deliver_message(bot, chat_id, &entry.message, profile, false).await;
+        sent += 1;
+    }
+    ACTIVE.lock().unwrap().remove(&cursor._id);
+
+    let stopped = stopped.load(Ordering::Relaxed);
+    if let Some(id) = progress {
+        let key = match stopped {
+            true => "results.stopped",
+            false => "results.sent",
+        };
+        let text = tr!(lang, key, sent = first + sent, total = total);
+        edit_progress(bot, chat_id, id, text, None, lang).await;
+    }
+    if stopped {
+        return;
+    }
+
+    if page + 1 < pages {
+        let remaining = total - (page + 1) * *PAGE_SIZE;
+        let keyboard =
+            InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+                tr!(lang, "results.more"),
+                format!("{}:{}:{}", MORE_PREFIX, cursor._id.to_hex(), page + 1),
+            )]);
+        if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+            bot.send_message(chat_id, tr!(lang, "results.remaining", n = remaining))
+                .reply_markup(keyboard.clone())
+        })
+        .await
+        {
+            log::error!("Error while sending a string: {}", e);
+        }
+    } else {
+        send_text(bot, chat_id, tr!(lang, "results.end").as_str()).await;
+    }
+}
+
+/// Handles a press of the "Стоп" button with data `stop:<cursor id>`.
+pub async fn stop(cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>, client: &Client, data: &str) {
+    let id = data
+        .split(':')
+        .nth(1)
+        .and_then(|id| id.parse::<ObjectId>().ok());
+    let stopped = id.and_then(|id| match ACTIVE.lock().unwrap().get(&id) {
+        Some((user_id, stopped)) if *user_id == cx.update.from.id => {
+            stopped.store(true, Ordering::Relaxed);
+            Some(())
+        }
+        _ => None,
+    });
+    let lang = profile_for(client, cx.update.from.id).await.language;
+    let text = match stopped {
+        Some(_) => tr!(lang, "results.stopping"),
+        None => tr!(lang, "results.finished"),
+    };
+    answer_callback(cx, Some(&text)).await;
+}
+
+async fn send_progress(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    id: ObjectId,
+    lang: Language,
+) -> Option<i32> {
+    let keyboard = stop_keyboard(id, lang);
+    let text = tr!(lang, "results.starting");
+    match sender::send(chat_id, Priority::Interactive, || {
+        bot.send_message(chat_id, text.as_str())
+            .reply_markup(keyboard.clone())
+    })
+    .await
+    {
+        Ok(m) => Some(m.id),
+        Err(e) => {
+            log::error!("Error while sending a string: {}", e);
+            None
+        }
+    }
+}
+
+/// Edits the progress message. Without `stop` the "Стоп" button is removed.
+async fn edit_progress(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    message_id: i32,
+    text: String,
+    stop: Option<ObjectId>,
+    lang: Language,
+) {
+    let keyboard = stop.map(|id| stop_keyboard(id, lang));
+    if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+        let edit = bot.edit_message_text(chat_id, message_id, text.as_str());
+        match &keyboard {
+            Some(k) => edit.reply_markup(k.clone()),
+            None => edit,
+        }
+    })
+    .await
+    {
+        log::error!("Error while editing a progress message: {}", e);
+    }
+}
+
+fn stop_keyboard(id: ObjectId, lang: Language) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::default().append_row(vec![InlineKeyboardButton::callback(
+        tr!(lang, "results.stop"),
+        format!("{}:{}", STOP_PREFIX, id.to_hex()),
+    )])
+}
+
+pub async fn remove_keyboard(bot: &AutoSend<Bot>, message: &teloxide::types::Message) {
+    if let Err(e) = sender::send(message.chat.id, Priority::Interactive, || {
+        bot.edit_message_reply_markup(message.chat.id, message.id)
+    })
+    .await
+    {
+        log::error!("Error while removing a keyboard: {}", e);
+    }
+}
@@ -1,40 +1,123 @@
 use chrono::Duration;
 use smartstring::{LazyCompact, SmartString};
 type String = SmartString<LazyCompact>;
+use crate::commands;
+use crate::i18n::{tr, Language, Localize};
 use crate::ALL_TAGS;
 
+/// Display gives the catalogue key, [`Localize`] renders the message.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
-    #[error("Не указаны регионы 🗺❌")]
+    #[error("error.no_regions")]
     NoRegions,
 
-    #[error("Непонятная продолжительность 🕒❌")]
+    #[error("error.duration")]
     DurationParseError(#[from] std::num::ParseIntError),
 
-    #[error("⚠️‼️ Непонятный регион ‼️⚠️\n\"{region}\".\nСовпадения: {matches:?}")]
+    #[error("error.bad_region")]
     BadRegion {
         region: String,
         matches: Vec<&'static str>,
     },
 
-    #[error("По такому запросу нет сообщений 🔎❌")]
+    #[error("error.no_messages")]
     NoMessages {
         regions: Vec<String>,
         period: Option<(Duration, Duration)>,
         tags: Vec<String>,
     },
 
-    #[error("⚠️‼️ Непонятный тег ‼️⚠️\n\"{0}\". Допустимые теги: [ {} ]", 
-        ALL_TAGS
-            .read()
-            .map_err(|e| log::error!("Can't lock ALL_TAGS. Error: {}", e.to_string()))
-            .unwrap()
-            .iter()
-            .copied()
-            .collect::<Vec<_>>()
-            .join(", "))]
+    #[error("error.bad_tag")]
     BadTag(String),
 
-    #[error("⚠️‼️ База данных вернула ошибку ‼️⚠️ 🧑‍💻\n{0}")]
+    #[error("error.db")]
     DbError(#[from] crate::db_utils::error::Error),
 }
+
+impl Localize for Error {
+    fn localize(&self, lang: Language) -> std::string::String {
+        let key = self.to_string();
+        match self {
+            Error::BadRegion { region, matches } => tr!(
+                lang,
+                &key,
+                region = region,
+                matches = format!("{:?}", matches)
+            ),
+            Error::BadTag(tag) => {
+                let tags = ALL_TAGS
+                    .read()
+                    .map_err(|e| log::error!("Can't lock ALL_TAGS. Error: {}", e.to_string()))
+                    .unwrap()
+                    .iter()
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                tr!(lang, &key, tag = tag, tags = tags)
+            }
+            Error::DbError(e) => tr!(lang, &key, error = e.localize(lang)),
+            _ => tr!(lang, &key),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CommandError {
+    #[error("error.unknown_command")]
+    Unknown(String),
+
+    #[error("error.missing_argument")]
+    MissingArgument {
+        argument: &'static str,
+        command: &'static str,
+    },
+
+    #[error("error.bad_argument")]
+    BadArgument {
+        argument: &'static str,
+        value: String,
+        command: &'static str,
+    },
+
+    #[error("error.extra_arguments")]
+    ExtraArguments {
+        value: String,
+        command: &'static str,
+    },
+
+    #[error(transparent)]
+    Query(#[from] Error),
+}
+
+impl Localize for CommandError {
+    fn localize(&self, lang: Language) -> std::string::String {
+        let key = self.to_string();
+        match self {
+            CommandError::Unknown(name) => tr!(lang, &key, name = name),
+            CommandError::MissingArgument { argument, command } => tr!(
+                lang,
+                &key,
+                argument = commands::argument(lang, argument),
+                usage = commands::usage(lang, command)
+            ),
+            CommandError::BadArgument {
+                argument,
+                value,
+                command,
+            } => tr!(
+                lang,
+                &key,
+                argument = commands::argument(lang, argument),
+                value = value,
+                usage = commands::usage(lang, command)
+            ),
+            CommandError::ExtraArguments { value, command } => tr!(
+                lang,
+                &key,
+                value = value,
+                usage = commands::usage(lang, command)
+            ),
+            CommandError::Query(e) => e.localize(lang),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use mongodb::Client;
+use teloxide::prelude::*;
+
+use crate::commands;
+use crate::common::send_text;
+use crate::db_utils::{
+    self,
+    models::{Profile, User},
+};
+use crate::delivery::profile_for;
+use crate::i18n::tr;
+
+lazy_static::lazy_static! {
+    /// How long before an expiry the user and the granting admin are warned.
+    static ref EXPIRY_NOTICE_HOURS: i64 = std::env::var("EXPIRY_NOTICE_HOURS")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse EXPIRY_NOTICE_HOURS as i64"))
+        .unwrap_or(24);
+}
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// Warns about expiring registrations and region grants and purges expired ones.
+pub async fn run(bot: AutoSend<Bot>, client: Arc<Client>) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        notify(&bot, &client, now + Duration::hours(*EXPIRY_NOTICE_HOURS)).await;
+        match db_utils::purge_expired(&client, now).await {
+            Ok(deleted) => {
+                for id in deleted {
+                    log::info!("Registration of {} expired", id);
+                    commands::set_menu(&bot, id, None, Default::default()).await;
+                }
+            }
+            Err(e) => log::error!("Can't purge expired grants. Error: {}", e),
+        }
+    }
+}
+
+async fn notify(bot: &AutoSend<Bot>, client: &Client, until: DateTime<Utc>) {
+    let users = match db_utils::find_expiring(client, until).await {
+        Ok(users) => users,
+        Err(e) => return log::error!("Can't access expiring grants. Error: {}", e),
+    };
+    for user in users {
+        // What expires, by the admin who granted it: the region, `None` for the registration.
+        let mut expiring = BTreeMap::<i64, Vec<Expiring>>::new();
+        if let Some(e) = user
+            .expiry
+            .as_ref()
+            .filter(|e| !e.notified && e.expires_at <= until)
+        {
+            expiring
+                .entry(e.granted_by)
+                .or_default()
+                .push((None, e.expires_at));
+        }
+        for g in &user.grants {
+            if !g.expiry.notified && g.expiry.expires_at <= until {
+                expiring
+                    .entry(g.expiry.granted_by)
+                    .or_default()
+                    .push((Some(g.region.as_str()), g.expiry.expires_at));
+            }
+        }
+        send_notices(bot, client, &user, &expiring).await;
+        if let Err(e) = db_utils::mark_notified(client, user.id, until).await {
+            log::error!("Can't mark grants of {} as notified. Error: {}", user.id, e);
+        }
+    }
+}
+
+type Expiring<'u> = (Option<&'u str>, DateTime<Utc>);
+
+async fn send_notices(
+    bot: &AutoSend<Bot>,
+    client: &Client,
+    user: &User,
+    expiring: &BTreeMap<i64, Vec<Expiring<'_>>>,
+) {
+    let all = expiring.values().flatten().copied().collect::<Vec<_>>();
+    if all.is_empty() {
+        return;
+    }
+    let lang = user.profile.language;
+    let lines = format_lines(&all, &user.profile);
+    let text = tr!(lang, "expiry.user", expiring = lines);
+    send_text(bot, user.id, text.as_str()).await;
+    for (admin, items) in expiring {
+        let profile = profile_for(client, *admin).await;
+        let text = tr!(
+            profile.language,
+            "expiry.admin",
+            id = user.id,
+            expiring = format_lines(items, &profile)
+        );
+        send_text(bot, *admin, text.as_str()).await;
+    }
+}
+
+/// One line per expiring item, in the language and timezone of the recipient.
+fn format_lines(items: &[Expiring], profile: &Profile) -> String {
+    let lang = profile.language;
+    items
+        .iter()
+        .map(|&(region, expires_at)| {
+            let expires_at = format_time(expires_at, profile.offset());
+            match region {
+                Some(region) => tr!(
+                    lang,
+                    "expiry.region",
+                    region = region,
+                    expires_at = expires_at
+                ),
+                None => tr!(lang, "expiry.registration", expires_at = expires_at),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_time(time: DateTime<Utc>, offset: FixedOffset) -> String {
+    time.with_timezone(&offset)
+        .format("%d.%m.%y %H:%M")
+        .to_string()
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use chrono::FixedOffset;
+use serde::Serialize;
+use teloxide::{prelude::*, types::InputFile};
+
+use crate::db_utils::models::Message;
+use crate::sender::{self, Priority};
+
+#[derive(strum::EnumString, strum::Display, Clone, Copy, Debug, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Html,
+}
+
+#[derive(Serialize)]
+struct Record {
+    timestamp: String,
+    regions: Vec<String>,
+    tags: Vec<String>,
+    chat_id: i64,
+    chat: String,
+    link: Option<String>,
+    text: Option<String>,
+}
+
+/// Sends `messages` to `chat_id` as a document in `format`. Timestamps are shown in `offset`.
+pub async fn send_export(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    messages: &[Message],
+    format: ExportFormat,
+    offset: FixedOffset,
+    title: &str,
+) {
+    let records = records(bot, messages, offset).await;
+    let data = match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(&records).unwrap_or_default(),
+        ExportFormat::Csv => csv(&records).into_bytes(),
+        ExportFormat::Html => html(&records, title).into_bytes(),
+    };
+    let file_name = format!(
+        "export_{}.{}",
+        chrono::Utc::now()
+            .with_timezone(&offset)
+            .format("%Y%m%d_%H%M"),
+        format
+    );
+
+    let caption = format!("{} ({})", title, records.len());
+    if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+        bot.send_document(chat_id, InputFile::memory(file_name.clone(), data.clone()))
+            .caption(caption.clone())
+    })
+    .await
+    {
+        log::error!("Error while sending a document: {}", e);
+    }
+}
+
+async fn records(bot: &AutoSend<Bot>, messages: &[Message], offset: FixedOffset) -> Vec<Record> {
+    let mut chats = HashMap::<i64, (String, Option<String>)>::new();
+    for message in messages {
+        if chats.contains_key(&message.chat_id) {
+            continue;
+        }
+        let chat = match sender::lookup(|| bot.get_chat(message.chat_id)).await {
+            Ok(c) => (
+                c.title().unwrap_or_default().to_string(),
+                c.username().map(|u| u.to_string()),
+            ),
+            Err(e) => {
+                log::error!("Can't get chat {}. Error: {}", message.chat_id, e);
+                (String::new(), None)
+            }
+        };
+        chats.insert(message.chat_id, chat);
+    }
+
+    messages
+        .iter()
+        .map(|m| {
+            let (chat, username) = chats.get(&m.chat_id).cloned().unwrap_or_default();
+            Record {
+                timestamp: m.timestamp.with_timezone(&offset).to_rfc3339(),
+                regions: m.regions.clone(),
+                tags: m.tags.clone(),
+                chat_id: m.chat_id,
+                chat,
+                link: message_link(m.chat_id, username.as_deref(), m.message_id),
+                text: m.text.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Public chats are linked by username, supergroups and channels by their internal id.
+/// Messages of basic groups can't be linked.
+fn message_link(chat_id: i64, username: Option<&str>, message_id: i32) -> Option<String> {
+    match username {
+        Some(username) => Some(format!("https://t.me/{}/{}", username, message_id)),
+        None => chat_id
+            .to_string()
+            .strip_prefix("-100")
+            .map(|id| format!("https://t.me/c/{}/{}", id, message_id)),
+    }
+}
+
+fn csv(records: &[Record]) -> String {
+    let field = |s: &str| {
+        if s.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut res = String::from("timestamp,regions,tags,chat_id,chat,link,text\r\n");
+    for r in records {
+        let row = [
+            r.timestamp.clone(),
+            r.regions.join("; "),
+            r.tags.join("; "),
+            r.chat_id.to_string(),
+            r.chat.clone(),
+            r.link.clone().unwrap_or_default(),
+            r.text.clone().unwrap_or_default(),
+        ];
+        res += row
+            .iter()
+            .map(|f| field(f))
+            .collect::<Vec<_>>()
+            .join(",")
+            .as_str();
+        res += "\r\n";
+    }
+    res
+}
+
+fn html(records: &[Record], title: &str) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    let mut rows = String::new();
+    for r in records {
+        let link = match &r.link {
+            Some(l) => format!("<a href=\"{0}\">{0}</a>", escape(l)),
+            None => String::new(),
+        };
+        rows += format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"text\">{}</td></tr>\n",
+            escape(&r.timestamp),
+            escape(&r.regions.join(", ")),
+            escape(&r.tags.join(", ")),
+            escape(&r.chat),
+            link,
+            escape(r.text.as_deref().unwrap_or_default()),
+        )
+        .as_str();
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"ru\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+        <style>\n\
+        body {{ font-family: sans-serif; margin: 2em; }}\n\
+        table {{ border-collapse: collapse; width: 100%; }}\n\
+        th, td {{ border: 1px solid #ccc; padding: 4px 8px; vertical-align: top; text-align: left; }}\n\
+        th {{ background: #f0f0f0; }}\n\
+        td.text {{ white-space: pre-wrap; }}\n\
+        </style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>Сообщений: {n}</p>\n<table>\n\
+        <tr><th>Время</th><th>Регионы</th><th>Теги</th><th>Чат</th><th>Ссылка</th><th>Текст</th></tr>\n\
+        {rows}</table>\n</body>\n</html>\n",
+        title = escape(title),
+        n = records.len(),
+        rows = rows,
+    )
+}
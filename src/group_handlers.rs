@@ -1,11 +1,24 @@
-use crate::{common::*, db_utils};
+use crate::chats::{self, CHATS};
+use crate::commands::ChatSetting;
+use crate::db_utils::{
+    models::{self, Permission},
+    user::User,
+};
+use crate::i18n::{tr, Localize};
+use crate::sender::{self, Priority};
+use crate::{common::*, db_utils, subscriptions};
 use crate::{error::Error, Dialogue, ALLIAS_REGIONS, ALL_REGIONS, ALL_TAGS};
 use mongodb::Client;
-use std::sync::Arc;
-use std::time::Duration;
-use teloxide::prelude::*;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use teloxide::{prelude::*, types::MessageKind};
 
 lazy_static::lazy_static! {
+    /// Only users whose role grants `Finalize` may save messages in groups.
+    static ref FINALIZE_REQUIRES_ROLE: bool = std::env::var("FINALIZE_REQUIRES_ROLE")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse FINALIZE_REQUIRES_ROLE as bool"))
+        .unwrap_or(false);
     static ref FINALIZE_REGEX: regex::Regex =
         regex::Regex::new(r"^(?P<regions>([\p{L}-]{2,}\s*)+)?\s*(?P<tags>(\p{L}\s+)*\p{L}$)?$").expect("Cant create a regex");
 }
@@ -33,14 +46,36 @@ async fn chat(
     cx: TransitionIn<AutoSend<Bot>>,
     _: String,
 ) -> TransitionOut<Dialogue> {
-    let chat = cx.requester.get_chat(cx.chat_id()).await?;
+    let chat = sender::lookup(|| cx.requester.get_chat(cx.chat_id())).await?;
+    let lang = CHATS.language(chat.id).await;
     let text = cx.update.text();
-    let (text, respond_to, pin) = match handle_chat(&mut state, chat.id, text, cx.update.id).await {
+    let archived = text.or_else(|| cx.update.caption()).map(|t| t.to_string());
+    // Messages sent on behalf of a chat have no author.
+    let from = match cx.update.sender_chat() {
+        Some(_) => None,
+        None => cx.update.from().map(|u| u.id),
+    };
+    let editor = FINALIZE_REQUIRES_ROLE
+        .then(|| User::new(from.unwrap_or_default(), Arc::clone(&state.client)));
+    let message_id = cx.update.id;
+    let (text, respond_to, pin) = match handle_chat(
+        &mut state, chat.id, text, archived, message_id, from, editor,
+    )
+    .await
+    {
         Ok(HandleChat::Saved {
             n_messages,
             regions,
             tags,
+            inserted,
         }) => {
+            if !inserted.is_empty() {
+                tokio::spawn(subscriptions::notify(
+                    cx.requester.clone(),
+                    Arc::clone(&state.client),
+                    inserted,
+                ));
+            }
             let n = state.n;
             state.n = 0;
             let regions = if regions.len() > 1 {
@@ -54,70 +89,67 @@ async fn chat(
                 String::new()
             };
             if n == 0 {
-                (
-                    Some("⚠️Нет сообщений для сохранения⚠️".to_string()),
-                    None,
-                    true,
-                )
+                (Some(tr!(lang, "chat.nothing_to_save")), None, true)
             } else {
-                (
-                    Some(format!("Сохранено [{}]\n{}{}", n_messages, regions, tags)),
-                    None,
-                    false,
-                )
+                let text = tr!(
+                    lang,
+                    "chat.saved",
+                    n = n_messages,
+                    regions = regions,
+                    tags = tags
+                );
+                (Some(text), None, false)
             }
         }
         Ok(HandleChat::Remembered(id)) => {
             state.n += 1;
-            (Some(format!("Принял {}", state.n)), Some(id), false)
+            (
+                Some(tr!(lang, "chat.remembered", n = state.n)),
+                Some(id),
+                false,
+            )
+        }
+        Ok(HandleChat::Ignored(id)) => (Some(tr!(lang, "chat.ignored")), Some(id), true),
+        Err(e @ Error::BadRegion { .. }) => (Some(e.localize(lang)), Some(cx.update.id), true),
+        Err(e @ Error::BadTag(_)) => (Some(e.localize(lang)), Some(cx.update.id), true),
+        Err(Error::DbError(e @ db_utils::error::Error::PrivlegeError { .. })) => {
+            (Some(e.localize(lang)), Some(cx.update.id), false)
         }
-        Ok(HandleChat::Ignored(id)) => (Some("⚠️Проигнорированно⚠️".to_string()), Some(id), true),
-        Err(e @ Error::BadRegion { .. }) => (Some(e.to_string()), Some(cx.update.id), true),
-        Err(e @ Error::BadTag(_)) => (Some(e.to_string()), Some(cx.update.id), true),
         Err(e) => {
             log::error!(
-                "Unreachable branch while handling chat message: {:?}. Error: {}",
+                "Unreachable branch while handling chat message: {:?}. Error: {:?}",
                 text,
-                e.to_string()
+                e
             );
-            (Some(e.to_string()), Some(cx.update.id), true)
+            (Some(e.localize(lang)), Some(cx.update.id), true)
         }
     };
 
     let id = match (text, respond_to) {
-        (Some(t), Some(_)) => loop {
-            match cx.reply_to(&t).await {
-                Ok(m) => break Some(m.id),
-                Err(teloxide::RequestError::RetryAfter(secs)) => {
-                    tokio::time::sleep(Duration::from_secs(secs as u64)).await
-                }
-                Err(e) => {
-                    log::error!("{}", e.to_string());
-                    break None;
-                }
-            }
-        },
-        (Some(t), None) => loop {
-            match cx.answer(&t).await {
-                Ok(m) => break Some(m.id),
-                Err(teloxide::RequestError::RetryAfter(secs)) => {
-                    tokio::time::sleep(Duration::from_secs(secs as u64)).await
-                }
-                Err(e) => {
-                    log::error!("{}", e.to_string());
-                    break None;
-                }
-            }
-        },
+        (Some(t), Some(_)) => {
+            sender::send(cx.chat_id(), Priority::Interactive, || cx.reply_to(&t)).await
+        }
+        (Some(t), None) => {
+            sender::send(cx.chat_id(), Priority::Interactive, || cx.answer(&t)).await
+        }
         _ => unreachable!(),
     };
+    let id = match id {
+        Ok(m) => Some(m.id),
+        Err(e) => {
+            log::error!("{}", e.to_string());
+            None
+        }
+    };
 
     if pin {
         if let Some(id) = id {
-            while let Err(teloxide::RequestError::RetryAfter(secs)) =
-                cx.requester.pin_chat_message(cx.update.chat_id(), id).await
+            if let Err(e) = sender::send(cx.chat_id(), Priority::Interactive, || {
+                cx.requester.pin_chat_message(cx.update.chat_id(), id)
+            })
+            .await
             {
-                tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await;
+                log::error!("{}", e.to_string());
             }
         }
     }
@@ -125,12 +157,126 @@ async fn chat(
     next(state)
 }
 
+/// Commands handled in groups instead of being saved.
+const GROUP_COMMANDS: &[&str] = &["register", "settings"];
+
+/// The name and arguments of a group command in `text`, either bare or addressed to this
+/// bot.
+pub fn command(text: Option<&str>) -> Option<(&str, &str)> {
+    let text = text?.strip_prefix('/')?;
+    let (command, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let (name, addressed) = match command.split_once('@') {
+        Some((name, bot_name)) => (name, Some(bot_name)),
+        None => (command, None),
+    };
+    if !GROUP_COMMANDS.contains(&name) {
+        return None;
+    }
+    if let Some(bot_name) = addressed {
+        if bot_user().username.as_deref() != Some(bot_name) {
+            return None;
+        }
+    }
+    Some((name, rest))
+}
+
+/// Handles `/settings` sent in a group, see [`chats::change_settings`].
+pub async fn settings(cx: &UpdateWithCx<AutoSend<Bot>, Message>, client: &Arc<Client>, args: &str) {
+    let chat = &cx.update.chat;
+    let lang = CHATS.language(chat.id).await;
+    let text = match (cx.update.from(), ChatSetting::parse(args)) {
+        (Some(from), Ok(setting)) => {
+            let user = User::new(from.id, Arc::clone(client));
+            chats::change_settings(&user, chat.id, setting, lang).await
+        }
+        (Some(_), Err(e)) => e.localize(lang),
+        (None, _) => return,
+    };
+    if let Err(e) = sender::send(chat.id, Priority::Interactive, || cx.reply_to(&text)).await {
+        log::error!("Error while replying to /settings: {}", e);
+    }
+}
+
+/// Handles `/register` sent in a group: adds the chat if the sender may manage chats.
+pub async fn register(cx: &UpdateWithCx<AutoSend<Bot>, Message>, client: &Arc<Client>) {
+    let chat = &cx.update.chat;
+    let lang = CHATS.language(chat.id).await;
+    let text = match cx.update.from() {
+        _ if CHATS.contains(chat.id).await => tr!(lang, "chat.already_registered"),
+        Some(from) => {
+            let user = User::new(from.id, Arc::clone(client));
+            let title = chat.title().map(str::to_string);
+            match CHATS
+                .add(&user, models::Chat::new(chat.id, title, from.id))
+                .await
+            {
+                Ok(()) => tr!(lang, "chat.registered"),
+                Err(e) => tr!(lang, "fail.add_chat", error = e.localize(lang)),
+            }
+        }
+        None => return,
+    };
+    if let Err(e) = sender::send(chat.id, Priority::Interactive, || cx.reply_to(&text)).await {
+        log::error!("Error while replying to /register: {}", e);
+    }
+}
+
+/// Tells everyone who may manage chats that the bot was added to the unregistered chat of
+/// `cx`. Other service messages are ignored.
+pub async fn notify_added(cx: &UpdateWithCx<AutoSend<Bot>, Message>, client: &Client) {
+    let chat = &cx.update.chat;
+    match &cx.update.kind {
+        MessageKind::NewChatMembers(m) => {
+            let me = bot_user().id;
+            if !m.new_chat_members.iter().any(|u| u.id == me) {
+                return;
+            }
+        }
+        MessageKind::GroupChatCreated(_) | MessageKind::SupergroupChatCreated(_) => {}
+        _ => return,
+    }
+    if CHATS.contains(chat.id).await {
+        return;
+    }
+
+    let title = chat.title().unwrap_or_default();
+    let (name, by) = cx
+        .update
+        .from()
+        .map(|u| (u.full_name(), u.id))
+        .unwrap_or_default();
+    chats::notify_managers(&cx.requester, client, |lang| {
+        tr!(
+            lang,
+            "chat.bot_added",
+            title = title,
+            id = chat.id,
+            name = name,
+            by = by
+        )
+    })
+    .await;
+}
+
+/// The names of `values` in `all`, skipping the ones that no longer exist.
+fn known(all: &RwLock<HashSet<&'static str>>, values: &[String]) -> Vec<&'static str> {
+    let all = all
+        .read()
+        .map_err(|e| log::error!("Can't lock a name set. Error: {}", e))
+        .unwrap();
+    values
+        .iter()
+        .filter_map(|v| all.get(v.as_str()).copied())
+        .collect()
+}
+
 enum HandleChat<'r, 't> {
     Remembered(i32),
     Saved {
         n_messages: usize,
         regions: Vec<&'r str>,
         tags: Vec<&'t str>,
+        inserted: Vec<db_utils::models::Message>,
     },
     Ignored(i32),
 }
@@ -139,15 +285,25 @@ async fn handle_chat<'t>(
     state: &mut Chat,
     id: i64,
     text: Option<&'t str>,
+    archived: Option<String>,
     message_id: i32,
+    from: Option<i64>,
+    editor: Option<User>,
 ) -> Result<HandleChat<'t, 't>, Error> {
+    let defaults = CHATS.settings(id).await;
+    let bare = matches!(
+        text.unwrap_or_default().trim().to_lowercase().as_str(),
+        "+" | "ок" | "ok"
+    );
     let (regions, tags) = match FINALIZE_REGEX.captures(text.unwrap_or_default()) {
+        _ if bare => (None, None),
         Some(c) => (
             c.name("regions").map(|r| r.as_str()),
             c.name("tags").map(|t| t.as_str()),
         ),
         None => (None, None),
     };
+    let regions_absent = regions.is_none();
 
     let regions = match regions {
         Some(regions) => match extract_regions(regions) {
@@ -156,7 +312,11 @@ async fn handle_chat<'t>(
             Regions::Regions(_) => None,
             Regions::BadRegion { .. } => None,
         },
-        _ => None,
+        // A line of only tags, or a bare "+", is saved with the chat's default regions.
+        None if (tags.is_some() || bare) && !defaults.default_regions.is_empty() => {
+            Some(known(&ALL_REGIONS, &defaults.default_regions)).filter(|r| !r.is_empty())
+        }
+        None => None,
     };
 
     let tags = match tags {
@@ -165,6 +325,8 @@ async fn handle_chat<'t>(
             (Some(_), Tags::BadTag(t)) => return Err(Error::BadTag(t.into())),
             _ => None,
         },
+        // Like the regions, the chat's default tags only complete a line without regions.
+        None if regions_absent || bare => Some(known(&ALL_TAGS, &defaults.default_tags)),
         None => Some(vec![]),
     };
 
@@ -176,10 +338,15 @@ async fn handle_chat<'t>(
                 .unwrap()
                 .contains_key(*region)
         }) {
+            if let Some(editor) = editor {
+                editor.require(Permission::Finalize).await?;
+            }
             let messages = &mut state.messages;
             let n_messages = messages.len();
+            let mut inserted = vec![];
             if !messages.is_empty() {
                 messages.iter_mut().for_each(|m| {
+                    m.saved_by = from;
                     m.regions = regions.iter().map(|&r| r.into()).collect();
                     m.tags = tags
                         .clone()
@@ -206,12 +373,13 @@ async fn handle_chat<'t>(
                 )
                 .await;
                 messages.clear();
-                r?;
+                inserted = r?;
             }
             return Ok(HandleChat::Saved {
                 n_messages,
                 regions,
                 tags: tags.unwrap_or_default(),
+                inserted,
             });
         }
     }
@@ -232,6 +400,9 @@ async fn handle_chat<'t>(
         chat_id: id,
         message_id,
         tags: vec![],
+        text: archived,
+        posted_by: from,
+        saved_by: None,
     });
     Ok(HandleChat::Remembered(message_id))
 }
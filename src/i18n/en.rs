@@ -0,0 +1,275 @@
+//! English messages.
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    ("error.no_regions", "No regions given 🗺❌"),
+    ("error.duration", "Can't parse the duration 🕒❌"),
+    ("error.bad_region", "⚠️‼️ Unknown region ‼️⚠️\n\"{region}\".\nMatches: {matches}"),
+    ("error.no_messages", "No messages match the query 🔎❌"),
+    ("error.bad_tag", "⚠️‼️ Unknown tag ‼️⚠️\n\"{tag}\". Valid tags: [ {tags} ]"),
+    ("error.db", "⚠️‼️ The database returned an error ‼️⚠️ 🧑‍💻\n{error}"),
+    ("error.unknown_command", "Unknown command /{name}"),
+    ("error.missing_argument", "Missing argument <{argument}> ❌\nUsage: {usage}"),
+    ("error.bad_argument", "Invalid argument <{argument}>: \"{value}\" ❌\nUsage: {usage}"),
+    ("error.extra_arguments", "Unexpected arguments: \"{value}\" ❌\nUsage: {usage}"),
+    ("error.db_driver", "{error}"),
+    ("error.privilege", "Role {current} is not allowed to {permission}"),
+    ("error.regions_out_of_scope", "Role {current} is not allowed to manage regions: {regions}"),
+    ("error.db_bad_tag", "Unknown tag \"{value}\""),
+    ("error.db_bad_region", "Unknown region \"{value}\""),
+    ("error.no_allowed_regions", "None of the given regions are open to you"),
+    ("error.no_subscription", "Subscription #{n} not found"),
+    ("error.no_digest", "Digest #{n} not found"),
+    ("error.no_trash_batch", "No deletion {id} in the trash"),
+    ("error.bad_invite", "The invite is invalid or already used"),
+    ("error.pending_access_request", "Your access request is already under review"),
+    ("error.no_access_request", "Access request {id} not found or already decided"),
+    ("permission.query", "search and subscribe to messages"),
+    ("permission.finalize", "save messages in groups"),
+    ("permission.manage_users", "manage users"),
+    ("permission.manage_region_users", "manage users of their regions"),
+    ("permission.manage_chats", "manage chats"),
+    ("permission.manage_messages", "delete and export stored messages"),
+    ("permission.view_stats", "view statistics"),
+    ("permission.view_audit", "view the audit log"),
+    ("arg.regions", "regions"),
+    ("arg.reason", "reason"),
+    ("arg.uses", "uses"),
+    ("arg.role", "role"),
+    ("arg.days_to_keep", "days to keep"),
+    ("arg.batch_id", "deletion id"),
+    ("arg.number", "number"),
+    ("arg.quiet_hours", "HH-HH or off"),
+    ("arg.time", "HH:MM"),
+    ("arg.query", "query"),
+    ("help.query", "Regions [hours ago] [number of hours (optional)] [tags]"),
+    ("help.unregistered", "Test echo bot\nRequest access: /request <regions> <reason>"),
+    ("start", "Hello world!"),
+    ("cmd.start", "Start using the bot"),
+    ("cmd.help", "List of commands"),
+    ("cmd.request", "Request access to regions"),
+    ("cmd.invite", "Create an invite link"),
+    ("cmd.list_users", "Users"),
+    ("cmd.add_user", "Add a user"),
+    ("cmd.del_user", "Delete a user"),
+    ("cmd.list_chats", "Chats"),
+    ("cmd.add_chat", "Add a chat"),
+    ("cmd.del_chat", "Delete a chat"),
+    ("cmd.listdb", "Messages of a day"),
+    ("cmd.deldb", "Delete a message"),
+    ("cmd.cleandb", "Delete old messages"),
+    ("cmd.trash", "Trash of deleted messages"),
+    ("cmd.restore", "Restore deleted messages"),
+    ("cmd.statdb", "Database statistics"),
+    ("cmd.add_user_regions", "Open regions to a user"),
+    ("cmd.del_user_regions", "Close regions to a user"),
+    ("cmd.add_admin_regions", "Hand regions to a regional admin"),
+    ("cmd.del_admin_regions", "Take regions from a regional admin"),
+    ("cmd.audit", "Audit log"),
+    ("cmd.subscribe", "Subscribe to new messages"),
+    ("cmd.subscriptions", "Subscriptions"),
+    ("cmd.unsubscribe", "Delete a subscription"),
+    ("cmd.pause", "Pause subscriptions"),
+    ("cmd.resume", "Resume subscriptions"),
+    ("cmd.quiet", "Quiet hours of a subscription"),
+    ("cmd.add_digest", "Create a digest"),
+    ("cmd.digests", "Digests"),
+    ("cmd.del_digest", "Delete a digest"),
+    ("cmd.count", "Count messages of a query"),
+    ("cmd.export", "Export messages to a file"),
+    ("cmd.delivery", "How messages are delivered"),
+    ("cmd.profile", "Profile: timezone, language, default regions"),
+    ("usage.start", "/start [invite]"),
+    ("usage.help", "/help"),
+    ("usage.request", "/request <regions> <reason>"),
+    ("usage.invite", "/invite [Admin|RegionalAdmin|Editor|Auditor] <regions> [uses, 1 by default] [term, e.g. 7d]"),
+    ("usage.list_users", "/list_users [group=<role>] [region=<region>] [name=<name>]"),
+    ("usage.add_user", "/add_user <id> [Admin|RegionalAdmin|Editor|Auditor] [term, e.g. 30d or 12h]"),
+    ("usage.del_user", "/del_user <id>"),
+    ("usage.list_chats", "/list_chats [region=<region>] [name=<title>]"),
+    ("usage.add_chat", "/add_chat <id>"),
+    ("usage.del_chat", "/del_chat <id>"),
+    ("usage.listdb", "/listdb <DD.MM.YY> [OFFSET, from the profile by default] [json|csv|html]"),
+    ("usage.deldb", "/deldb <id>"),
+    ("usage.cleandb", "/cleandb <days to keep>"),
+    ("usage.trash", "/trash"),
+    ("usage.restore", "/restore <deletion id>"),
+    ("usage.statdb", "/statdb [days, 7 by default] [OFFSET, from the profile by default]"),
+    ("usage.add_user_regions", "/add_user_regions <id> [term, e.g. 30d or 12h] <regions separated by spaces or a country>"),
+    ("usage.del_user_regions", "/del_user_regions <id> <regions separated by spaces or a country>"),
+    ("usage.add_admin_regions", "/add_admin_regions <id> <regions separated by spaces or a country>"),
+    ("usage.del_admin_regions", "/del_admin_regions <id> <regions separated by spaces or a country>"),
+    ("usage.audit", "/audit [actor=<id>] [target=<id>] [from=DD.MM.YY] [to=DD.MM.YY]"),
+    ("usage.subscribe", "/subscribe <regions> [tags]"),
+    ("usage.subscriptions", "/subscriptions"),
+    ("usage.unsubscribe", "/unsubscribe <number>"),
+    ("usage.pause", "/pause [number]"),
+    ("usage.resume", "/resume [number]"),
+    ("usage.quiet", "/quiet <number> <HH-HH or off> [OFFSET, from the profile by default]"),
+    ("usage.add_digest", "/add_digest <HH:MM[,HH:MM]> [days, e.g. mon-fri] [OFFSET, from the profile by default] <regions> [tags]"),
+    ("usage.digests", "/digests"),
+    ("usage.del_digest", "/del_digest <number>"),
+    ("usage.count", "/count <query>"),
+    ("usage.export", "/export <json|csv|html> <query>"),
+    ("usage.delivery", "/delivery [forward|copy [footer]|default]"),
+    ("usage.profile", "/profile [tz <+HH:MM>|lang <ru|en>|regions <regions|off>]"),
+    ("schedule.weekdays", "mon,tue,wed,thu,fri,sat,sun"),
+    ("digest.header", "📰 Digest: {regions} ({from} — {to})"),
+    ("digest.empty", "No new messages"),
+    ("digest.end", "🏁 Digest"),
+    ("fail.command", "The command failed. Error: {error}"),
+    ("invite.redeemed", "✅ You are registered. Open regions: {regions}\n\n{help}"),
+    ("fail.create_invite", "Can't create the invite. Error: {error}"),
+    ("user.added", "Added user {id}"),
+    ("fail.add_user", "Can't add the user. Error: {error}"),
+    ("user.deleted", "Deleted user {id}"),
+    ("fail.delete_user", "Can't delete the user. Error: {error}"),
+    ("fail.update_user", "Can't update the user. Error: {error}"),
+    ("chats.empty", "No chats yet. Use /add_chat id to add a chat."),
+    ("chat.added", "Added chat {id}"),
+    ("fail.add_chat", "Can't add the chat. Error: {error}"),
+    ("chat.deleted", "Deleted chat {id}"),
+    ("fail.delete_chat", "Can't delete the chat. Error: {error}"),
+    ("listdb.bad_date", "Invalid date"),
+    ("listdb.caption", "Messages of {date}"),
+    ("region", "Region: {region}"),
+    ("trash.empty", "The trash is empty"),
+    ("trash.batch", "{batch} — {count} messages, deleted {trashed_at} ({trashed_by}), kept until {expires_at}"),
+    ("trash.restored", "♻️ Messages restored: {n}"),
+    ("fail.restore", "Can't restore the messages. Error: {error}"),
+    ("audit.empty", "No entries"),
+    ("stat", "Number of messages ({offset}).\nToday\t- {today}\nYesterday\t- {yesterday}\nDay before\t- {before_yesterday}\nWeek\t- {week}\nMonth\t- {month}\nEarlier\t- {earlier}\n"),
+    ("subscription.added", "🔔 Subscribed: {subscription}"),
+    ("fail.subscribe", "Can't subscribe. Error: {error}"),
+    ("subscriptions.empty", "No subscriptions. Use /subscribe <regions> [tags] to subscribe."),
+    ("subscription.deleted", "Deleted subscription #{n}"),
+    ("fail.unsubscribe", "Can't delete the subscription. Error: {error}"),
+    ("subscription.paused", "⏸ Subscription #{n} paused"),
+    ("subscription.resumed", "▶️ Subscription #{n} resumed"),
+    ("subscriptions.paused", "⏸ All subscriptions paused"),
+    ("subscriptions.resumed", "▶️ All subscriptions resumed"),
+    ("fail.update_subscription", "Can't update the subscription. Error: {error}"),
+    ("quiet.set", "🌙 Quiet hours of subscription #{n} set: messages arrive silently during them"),
+    ("quiet.off", "Quiet hours of subscription #{n} turned off"),
+    ("digest.added", "📰 Digest created: {digest}"),
+    ("fail.add_digest", "Can't create the digest. Error: {error}"),
+    ("digests.empty", "No digests. Use /add_digest to create one."),
+    ("digest.deleted", "Deleted digest #{n}"),
+    ("fail.delete_digest", "Can't delete the digest. Error: {error}"),
+    ("delivery.current", "Delivery: {delivery}"),
+    ("delivery.default", "Default delivery: {delivery}"),
+    ("delivery.reset", "Delivery reset to the default"),
+    ("fail.update_profile", "Can't update the profile. Error: {error}"),
+    ("export.caption", "Export: {query}"),
+    ("count.total", "🔎 Messages found: {total}"),
+    ("count.by_region", "By region:"),
+    ("count.by_tag", "By tag:"),
+    ("query.show", "✅ Show"),
+    ("button.cancel", "❌ Cancel"),
+    ("query.only", "Only {region} ({n})"),
+    ("query.confirm", "{count}\n\nShow the results? To narrow the query, send it again with fewer regions, a period or tags."),
+    ("query.stale", "The query is stale, send it again"),
+    ("cancelled", "Cancelled"),
+    ("delete.nothing", "No messages to delete"),
+    ("delete.confirm", "🗑 Messages to move to the trash: {count}\nPeriod: {first} — {last}\nConfirm within {timeout} s."),
+    ("delete.button", "🗑 Delete"),
+    ("delete.expired", "⌛ The confirmation timed out"),
+    ("delete.stale", "The confirmation is stale"),
+    ("delete.done", "🗑 Messages moved to the trash: {count}\nRestore until {expires_at}: /restore {batch}"),
+    ("fail.delete_messages", "Can't delete the messages. Error: {error}"),
+    ("invite.created", "🔗 https://t.me/{bot}?start={token}\nRole: {group}\nRegions: {regions}\nUses: {uses}"),
+    ("invite.expires", "Valid until: {expires_at}"),
+    ("audit.args", "arguments: {args}"),
+    ("audit.before", "before: {before}"),
+    ("audit.after", "after: {after}"),
+    ("profile.no_regions", "not set"),
+    ("profile.default_delivery", "{delivery} (default)"),
+    ("profile", "Timezone: {timezone}\nLanguage: {language}\nDefault regions: {regions}\nDelivery: {delivery}"),
+    ("delivery.forward", "forward"),
+    ("delivery.copy_footer", "copy with a footer"),
+    ("delivery.copy", "copy"),
+    ("fail.request_access", "Can't send the request. Error: {error}"),
+    ("access.request", "🔑 Access request from {name} ({id})\nRegions: {regions}\nReason: {reason}"),
+    ("access.sent", "📨 The request was sent to the admins"),
+    ("access.approve", "✅ Approve"),
+    ("access.approve_selected", "✅ Approve selected ({n})"),
+    ("access.deny", "❌ Deny"),
+    ("access.nothing_selected", "No regions selected"),
+    ("fail.decide_access", "Can't decide the request. Error: {error}"),
+    ("access.denied_admin", "❌ Request of {id} denied"),
+    ("access.approved_admin", "✅ Regions opened to {id}: {regions}"),
+    ("access.denied", "❌ Your access request was denied"),
+    ("access.approved", "✅ Your access request was approved. Open regions: {regions}"),
+    ("access.not_approved", "Not approved: {regions}"),
+    ("fail.save_cursor", "Can't save the results. Error: {error}"),
+    ("results.stale", "The results are stale, repeat the query"),
+    ("results.db_error", "The database returned an error"),
+    ("results.page", "🔎 Messages found: {total}. Page {page}/{pages}"),
+    ("results.progress", "⏳ Region: {region}\nSent: {sent}/{total}"),
+    ("results.stopped", "⛔ Stopped. Sent: {sent}/{total}"),
+    ("results.sent", "✅ Sent: {sent}/{total}"),
+    ("results.more", "More"),
+    ("results.remaining", "Messages left: {n}"),
+    ("results.end", "🏁 End of the results"),
+    ("results.stopping", "Stopping"),
+    ("results.finished", "Sending has already finished"),
+    ("results.starting", "⏳ Starting to send"),
+    ("results.stop", "Stop"),
+    ("subscription.new_messages", "🔔 New messages: {regions}"),
+    ("expiry.registration", "the bot — until {expires_at}"),
+    ("expiry.region", "{region} — until {expires_at}"),
+    ("expiry.user", "⏳ Your access expires soon:\n{expiring}"),
+    ("expiry.admin", "⏳ Access of user {id} expires soon:\n{expiring}"),
+    ("chat.nothing_to_save", "⚠️No messages to save⚠️"),
+    ("chat.saved", "Saved [{n}]\n{regions}{tags}"),
+    ("chat.remembered", "Got {n}"),
+    ("chat.ignored", "⚠️Ignored⚠️"),
+    ("list.users", "Users: {n}"),
+    ("list.chats", "Chats: {n}"),
+    ("list.empty", "Nothing found."),
+    ("list.page", "Page {page} of {pages}"),
+    ("list.unknown", "unknown"),
+    ("list.none", "none"),
+    ("list.user", "👤 {name} ({id})"),
+    ("list.role", "Role: {role}"),
+    ("list.regions", "Regions: {regions}"),
+    ("list.admin_regions", "Administers: {regions}"),
+    ("list.expires", "Access until: {expires_at}"),
+    ("list.chat", "💬 {title} ({id})"),
+    ("list.members", "Members: {members}"),
+    ("list.last_saved", "Last saved message: {last}"),
+    ("cmd.register", "Save messages of this chat"),
+    ("chat.registered", "The chat is registered, its messages can be saved now."),
+    ("chat.already_registered", "The chat is already registered."),
+    ("chat.bot_added", "The bot was added to the unregistered chat “{title}” (id {id}) by {name} ({by}). To save its messages, send /register in the chat or /add_chat {id}."),
+    ("chat.bot_removed", "The bot was removed from the chat “{title}” (id {id}) by {name} ({by}). Its messages are no longer saved."),
+    ("cmd.chat_settings", "Chat settings: default regions and tags, language"),
+    ("usage.chat_settings", "/chat_settings <id> [regions <regions|off>|tags <tags|off>|lang <ru|en|default>]"),
+    ("cmd.settings", "Chat settings"),
+    ("usage.settings", "/settings [regions <regions|off>|tags <tags|off>|lang <ru|en|default>]"),
+    ("arg.tags", "tags"),
+    ("chat.settings", "Chat “{title}” (id {id})\nDefault regions: {regions}\nDefault tags: {tags}\nLanguage: {language}"),
+    ("fail.chat_settings", "Can't change the chat settings. Error: {error}"),
+    ("error.no_chat", "Chat {id} is not registered"),
+    ("cmd.editors", "Editor statistics by day and region"),
+    ("usage.editors", "/editors [days, 7 by default] [OFFSET, from the profile by default]"),
+    ("arg.days", "days"),
+    ("editors", "Saved by editors over {days} days ({offset}):"),
+    ("editors.empty", "Editors saved nothing over {days} days."),
+    ("editors.editor", "✍️ {name} ({id}): {total}\nBy day: {days}\nBy region: {regions}"),
+    ("stat.period", "Over {days} days since {since}: {total}"),
+    ("stat.regions", "Regions:"),
+    ("stat.tags", "Tags:"),
+    ("stat.chats", "Chats:"),
+    ("stat.more", "…and {n} more"),
+    ("stat.idle", "Regions without messages: {regions}"),
+    ("cmd.charts", "Activity charts by tag and region"),
+    ("usage.charts", "/charts [days, 1 to 90, 30 by default] [OFFSET, from the profile by default]"),
+    ("charts.volume", "Messages per day over {days} days ({offset})"),
+    ("charts.heatmap", "Messages by region over {days} days ({offset})"),
+    ("charts.untagged", "untagged"),
+    ("charts.other", "other"),
+    ("charts.volume_caption", "📈 Messages in total: {total}"),
+    ("charts.heatmap_caption", "🗺 Regions by day, the darkest cell is {max} messages"),
+    ("charts.empty", "No messages over {days} days."),
+    ("charts.failed", "Can't draw the charts ❌"),
+];
@@ -0,0 +1,126 @@
+//! Message catalogue. Every user-facing string is looked up by key in the
+//! recipient's language at send time.
+
+use std::fmt::{Display, Write};
+
+pub use crate::db_utils::models::Language;
+
+mod en;
+mod ru;
+
+lazy_static::lazy_static! {
+    /// Language of replies in group chats.
+    pub static ref CHAT_LANGUAGE: Language = std::env::var("CHAT_LANGUAGE")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse CHAT_LANGUAGE as Language"))
+        .unwrap_or_default();
+}
+
+/// Values that are rendered through the catalogue instead of `Display`.
+pub trait Localize {
+    fn localize(&self, lang: Language) -> String;
+}
+
+fn catalogue(lang: Language) -> &'static [(&'static str, &'static str)] {
+    match lang {
+        Language::Ru => ru::MESSAGES,
+        Language::En => en::MESSAGES,
+    }
+}
+
+/// The `key` message in `lang`, `None` when no catalogue has it.
+pub fn lookup(lang: Language, key: &str) -> Option<&'static str> {
+    let find = |lang| {
+        catalogue(lang)
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, m)| *m)
+    };
+    find(lang).or_else(|| find(Language::default()))
+}
+
+/// The `key` message in `lang`, falling back to Russian and then to the key itself.
+pub fn get(lang: Language, key: &str) -> String {
+    lookup(lang, key).map(str::to_string).unwrap_or_else(|| {
+        log::warn!("No message for key {}", key);
+        key.to_string()
+    })
+}
+
+/// The `key` message in `lang` with `{name}` placeholders replaced by `args`.
+pub fn render(lang: Language, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    fill(&get(lang, key), args)
+}
+
+/// Replaces `{name}` placeholders of `template` in a single pass, so braces in the inserted
+/// values are kept as they are. Unknown placeholders are left in place.
+fn fill(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let placeholder = &rest[start + 1..];
+        let value = placeholder.find('}').and_then(|end| {
+            let name = &placeholder[..end];
+            args.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| (value, end + 1))
+        });
+        match value {
+            Some((value, len)) => {
+                let _ = write!(message, "{}", value);
+                rest = &placeholder[len..];
+            }
+            None => {
+                message.push('{');
+                rest = placeholder;
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// `tr!(lang, "key")` or `tr!(lang, "key", name = value, ...)`.
+macro_rules! tr {
+    ($lang:expr, $key:expr) => {
+        $crate::i18n::get($lang, $key)
+    };
+    // The `let` drops the argument temporaries, so the macro can be used across `.await`.
+    ($lang:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let message = $crate::i18n::render(
+            $lang,
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        );
+        message
+    }};
+}
+pub(crate) use tr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_placeholders() {
+        let args: &[(&str, &dyn Display)] = &[("name", &"Анна"), ("n", &3)];
+        assert_eq!(fill("{name}: {n} из {n}", args), "Анна: 3 из 3");
+        assert_eq!(fill("без аргументов", args), "без аргументов");
+    }
+
+    #[test]
+    fn keeps_inserted_text() {
+        let args: &[(&str, &dyn Display)] = &[("a", &"{b}"), ("b", &"B")];
+        assert_eq!(fill("{a} {b}", args), "{b} B");
+        let args: &[(&str, &dyn Display)] = &[("a", &"{a}")];
+        assert_eq!(fill("{a}{a}", args), "{a}{a}");
+    }
+
+    #[test]
+    fn keeps_unknown_braces() {
+        let args: &[(&str, &dyn Display)] = &[("a", &1)];
+        assert_eq!(fill("{x} {a} {", args), "{x} 1 {");
+        assert_eq!(fill("{{a}} }", args), "{1} }");
+    }
+}
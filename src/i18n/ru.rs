@@ -0,0 +1,275 @@
+//! Russian messages. Every key has to be here: other languages fall back to it.
+
+pub const MESSAGES: &[(&str, &str)] = &[
+    ("error.no_regions", "Не указаны регионы 🗺❌"),
+    ("error.duration", "Непонятная продолжительность 🕒❌"),
+    ("error.bad_region", "⚠️‼️ Непонятный регион ‼️⚠️\n\"{region}\".\nСовпадения: {matches}"),
+    ("error.no_messages", "По такому запросу нет сообщений 🔎❌"),
+    ("error.bad_tag", "⚠️‼️ Непонятный тег ‼️⚠️\n\"{tag}\". Допустимые теги: [ {tags} ]"),
+    ("error.db", "⚠️‼️ База данных вернула ошибку ‼️⚠️ 🧑‍💻\n{error}"),
+    ("error.unknown_command", "Неизвестная команда /{name}"),
+    ("error.missing_argument", "Не указан аргумент <{argument}> ❌\nИспользование: {usage}"),
+    ("error.bad_argument", "Непонятный аргумент <{argument}>: \"{value}\" ❌\nИспользование: {usage}"),
+    ("error.extra_arguments", "Лишние аргументы: \"{value}\" ❌\nИспользование: {usage}"),
+    ("error.db_driver", "{error}"),
+    ("error.privilege", "Роль {current} не позволяет {permission}"),
+    ("error.regions_out_of_scope", "Роль {current} не позволяет управлять регионами: {regions}"),
+    ("error.db_bad_tag", "Непонятный тег \"{value}\""),
+    ("error.db_bad_region", "Непонятный регион \"{value}\""),
+    ("error.no_allowed_regions", "Нет доступа ни к одному из указанных регионов"),
+    ("error.no_subscription", "Подписка №{n} не найдена"),
+    ("error.no_digest", "Дайджест №{n} не найден"),
+    ("error.no_trash_batch", "В корзине нет удаления {id}"),
+    ("error.bad_invite", "Приглашение недействительно или уже использовано"),
+    ("error.pending_access_request", "Ваш запрос доступа уже рассматривается"),
+    ("error.no_access_request", "Запрос доступа {id} не найден или уже рассмотрен"),
+    ("permission.query", "искать сообщения и подписываться на них"),
+    ("permission.finalize", "сохранять сообщения в группах"),
+    ("permission.manage_users", "управлять пользователями"),
+    ("permission.manage_region_users", "управлять пользователями своих регионов"),
+    ("permission.manage_chats", "управлять чатами"),
+    ("permission.manage_messages", "удалять и выгружать сообщения базы"),
+    ("permission.view_stats", "смотреть статистику"),
+    ("permission.view_audit", "смотреть журнал действий"),
+    ("arg.regions", "регионы"),
+    ("arg.reason", "причина"),
+    ("arg.uses", "использований"),
+    ("arg.role", "роль"),
+    ("arg.days_to_keep", "суток оставить"),
+    ("arg.batch_id", "id удаления"),
+    ("arg.number", "номер"),
+    ("arg.quiet_hours", "ЧЧ-ЧЧ или off"),
+    ("arg.time", "ЧЧ:ММ"),
+    ("arg.query", "запрос"),
+    ("help.query", "Регионы [часов назад] [количество часов (можно опустить)] [теги]"),
+    ("help.unregistered", "Тестовый эхо-бот\nЗапросить доступ: /request <регионы> <причина>"),
+    ("start", "Hello world!"),
+    ("cmd.start", "Начать работу с ботом"),
+    ("cmd.help", "Список команд"),
+    ("cmd.request", "Запросить доступ к регионам"),
+    ("cmd.invite", "Создать ссылку-приглашение"),
+    ("cmd.list_users", "Пользователи"),
+    ("cmd.add_user", "Добавить пользователя"),
+    ("cmd.del_user", "Удалить пользователя"),
+    ("cmd.list_chats", "Чаты"),
+    ("cmd.add_chat", "Добавить чат"),
+    ("cmd.del_chat", "Удалить чат"),
+    ("cmd.listdb", "Сообщения за день"),
+    ("cmd.deldb", "Удалить сообщение"),
+    ("cmd.cleandb", "Удалить старые сообщения"),
+    ("cmd.trash", "Корзина удалённых сообщений"),
+    ("cmd.restore", "Восстановить удалённые сообщения"),
+    ("cmd.statdb", "Статистика базы"),
+    ("cmd.add_user_regions", "Открыть регионы пользователю"),
+    ("cmd.del_user_regions", "Закрыть регионы пользователю"),
+    ("cmd.add_admin_regions", "Передать регионы региональному админу"),
+    ("cmd.del_admin_regions", "Забрать регионы у регионального админа"),
+    ("cmd.audit", "Журнал действий"),
+    ("cmd.subscribe", "Подписаться на новые сообщения"),
+    ("cmd.subscriptions", "Подписки"),
+    ("cmd.unsubscribe", "Удалить подписку"),
+    ("cmd.pause", "Приостановить подписки"),
+    ("cmd.resume", "Возобновить подписки"),
+    ("cmd.quiet", "Тихие часы подписки"),
+    ("cmd.add_digest", "Создать дайджест"),
+    ("cmd.digests", "Дайджесты"),
+    ("cmd.del_digest", "Удалить дайджест"),
+    ("cmd.count", "Посчитать сообщения по запросу"),
+    ("cmd.export", "Выгрузить сообщения файлом"),
+    ("cmd.delivery", "Способ доставки сообщений"),
+    ("cmd.profile", "Профиль: часовой пояс, язык, регионы по умолчанию"),
+    ("usage.start", "/start [приглашение]"),
+    ("usage.help", "/help"),
+    ("usage.request", "/request <регионы> <причина>"),
+    ("usage.invite", "/invite [Admin|RegionalAdmin|Editor|Auditor] <регионы> [использований, по умолчанию 1] [срок, например 7д]"),
+    ("usage.list_users", "/list_users [group=<роль>] [region=<регион>] [name=<имя>]"),
+    ("usage.add_user", "/add_user <id> [Admin|RegionalAdmin|Editor|Auditor] [срок, например 30д или 12ч]"),
+    ("usage.del_user", "/del_user <id>"),
+    ("usage.list_chats", "/list_chats [region=<регион>] [name=<название>]"),
+    ("usage.add_chat", "/add_chat <id>"),
+    ("usage.del_chat", "/del_chat <id>"),
+    ("usage.listdb", "/listdb <DD.MM.YY> [OFFSET, по умолчанию из профиля] [json|csv|html]"),
+    ("usage.deldb", "/deldb <id>"),
+    ("usage.cleandb", "/cleandb <суток оставить>"),
+    ("usage.trash", "/trash"),
+    ("usage.restore", "/restore <id удаления>"),
+    ("usage.statdb", "/statdb [дней, по умолчанию 7] [OFFSET, по умолчанию из профиля]"),
+    ("usage.add_user_regions", "/add_user_regions <id> [срок, например 30д или 12ч] <регионы через пробел или страна>"),
+    ("usage.del_user_regions", "/del_user_regions <id> <регионы через пробел или страна>"),
+    ("usage.add_admin_regions", "/add_admin_regions <id> <регионы через пробел или страна>"),
+    ("usage.del_admin_regions", "/del_admin_regions <id> <регионы через пробел или страна>"),
+    ("usage.audit", "/audit [actor=<id>] [target=<id>] [from=DD.MM.YY] [to=DD.MM.YY]"),
+    ("usage.subscribe", "/subscribe <регионы> [теги]"),
+    ("usage.subscriptions", "/subscriptions"),
+    ("usage.unsubscribe", "/unsubscribe <номер>"),
+    ("usage.pause", "/pause [номер]"),
+    ("usage.resume", "/resume [номер]"),
+    ("usage.quiet", "/quiet <номер> <ЧЧ-ЧЧ или off> [OFFSET, по умолчанию из профиля]"),
+    ("usage.add_digest", "/add_digest <ЧЧ:ММ[,ЧЧ:ММ]> [дни, например пн-пт] [OFFSET, по умолчанию из профиля] <регионы> [теги]"),
+    ("usage.digests", "/digests"),
+    ("usage.del_digest", "/del_digest <номер>"),
+    ("usage.count", "/count <запрос>"),
+    ("usage.export", "/export <json|csv|html> <запрос>"),
+    ("usage.delivery", "/delivery [forward|copy [footer]|default]"),
+    ("usage.profile", "/profile [tz <+HH:MM>|lang <ru|en>|regions <регионы|off>]"),
+    ("schedule.weekdays", "пн,вт,ср,чт,пт,сб,вс"),
+    ("digest.header", "📰 Дайджест: {regions} ({from} — {to})"),
+    ("digest.empty", "Новых сообщений нет"),
+    ("digest.end", "🏁 Дайджест"),
+    ("fail.command", "Не получилось выполнить команду. Ошибка: {error}"),
+    ("invite.redeemed", "✅ Вы зарегистрированы. Открыты регионы: {regions}\n\n{help}"),
+    ("fail.create_invite", "Не получилось создать приглашение. Ошибка: {error}"),
+    ("user.added", "Добавил пользователя с id {id}"),
+    ("fail.add_user", "Не получилось добавить пользователя. Ошибка: {error}"),
+    ("user.deleted", "Удалил пользователя с id {id}"),
+    ("fail.delete_user", "Не получилось удалить пользователя. Ошибка: {error}"),
+    ("fail.update_user", "Не получилось изменить пользователя. Ошибка: {error}"),
+    ("chats.empty", "Пока не добавлено чатов. Используйте /add_chat id, чтобы добавить чат."),
+    ("chat.added", "Добавил чат с id {id}"),
+    ("fail.add_chat", "Не получилось добавить чат. Ошибка: {error}"),
+    ("chat.deleted", "Удалил чат с id {id}"),
+    ("fail.delete_chat", "Не получилось удалить чат. Ошибка: {error}"),
+    ("listdb.bad_date", "Неправильная дата"),
+    ("listdb.caption", "Сообщения за {date}"),
+    ("region", "Регион: {region}"),
+    ("trash.empty", "Корзина пуста"),
+    ("trash.batch", "{batch} — {count} сообщ., удалено {trashed_at} ({trashed_by}), хранится до {expires_at}"),
+    ("trash.restored", "♻️ Восстановлено сообщений: {n}"),
+    ("fail.restore", "Не получилось восстановить сообщения. Ошибка: {error}"),
+    ("audit.empty", "Записей нет"),
+    ("stat", "Количество сообщений ({offset}).\nСегодня\t- {today}\nВчера\t- {yesterday}\nПозавчера\t- {before_yesterday}\nНеделя\t- {week}\nМесяц\t- {month}\nРанее\t- {earlier}\n"),
+    ("subscription.added", "🔔 Подписка оформлена: {subscription}"),
+    ("fail.subscribe", "Не получилось подписаться. Ошибка: {error}"),
+    ("subscriptions.empty", "Подписок нет. Используйте /subscribe <регионы> [теги], чтобы подписаться."),
+    ("subscription.deleted", "Удалил подписку №{n}"),
+    ("fail.unsubscribe", "Не получилось удалить подписку. Ошибка: {error}"),
+    ("subscription.paused", "⏸ Подписка №{n} приостановлена"),
+    ("subscription.resumed", "▶️ Подписка №{n} возобновлена"),
+    ("subscriptions.paused", "⏸ Все подписки приостановлены"),
+    ("subscriptions.resumed", "▶️ Все подписки возобновлены"),
+    ("fail.update_subscription", "Не получилось изменить подписку. Ошибка: {error}"),
+    ("quiet.set", "🌙 Тихие часы для подписки №{n} установлены: в это время сообщения приходят без звука"),
+    ("quiet.off", "Тихие часы для подписки №{n} отключены"),
+    ("digest.added", "📰 Дайджест создан: {digest}"),
+    ("fail.add_digest", "Не получилось создать дайджест. Ошибка: {error}"),
+    ("digests.empty", "Дайджестов нет. Используйте /add_digest, чтобы создать дайджест."),
+    ("digest.deleted", "Удалил дайджест №{n}"),
+    ("fail.delete_digest", "Не получилось удалить дайджест. Ошибка: {error}"),
+    ("delivery.current", "Доставка: {delivery}"),
+    ("delivery.default", "Доставка по умолчанию: {delivery}"),
+    ("delivery.reset", "Доставка сброшена на настройку по умолчанию"),
+    ("fail.update_profile", "Не получилось изменить профиль. Ошибка: {error}"),
+    ("export.caption", "Экспорт: {query}"),
+    ("count.total", "🔎 Найдено сообщений: {total}"),
+    ("count.by_region", "По регионам:"),
+    ("count.by_tag", "По тегам:"),
+    ("query.show", "✅ Показать"),
+    ("button.cancel", "❌ Отмена"),
+    ("query.only", "Только {region} ({n})"),
+    ("query.confirm", "{count}\n\nПоказать результаты? Чтобы сузить запрос, отправьте его заново с меньшим числом регионов, периодом или тегами."),
+    ("query.stale", "Запрос устарел, повторите его"),
+    ("cancelled", "Отменено"),
+    ("delete.nothing", "Нет сообщений для удаления"),
+    ("delete.confirm", "🗑 Будет перемещено в корзину сообщений: {count}\nПериод: {first} — {last}\nПодтвердите в течение {timeout} с."),
+    ("delete.button", "🗑 Удалить"),
+    ("delete.expired", "⌛ Время подтверждения истекло"),
+    ("delete.stale", "Подтверждение устарело"),
+    ("delete.done", "🗑 Перемещено в корзину сообщений: {count}\nВосстановить до {expires_at}: /restore {batch}"),
+    ("fail.delete_messages", "Не получилось удалить сообщения. Ошибка: {error}"),
+    ("invite.created", "🔗 https://t.me/{bot}?start={token}\nРоль: {group}\nРегионы: {regions}\nИспользований: {uses}"),
+    ("invite.expires", "Действует до: {expires_at}"),
+    ("audit.args", "аргументы: {args}"),
+    ("audit.before", "до: {before}"),
+    ("audit.after", "после: {after}"),
+    ("profile.no_regions", "не заданы"),
+    ("profile.default_delivery", "{delivery} (по умолчанию)"),
+    ("profile", "Часовой пояс: {timezone}\nЯзык: {language}\nРегионы по умолчанию: {regions}\nДоставка: {delivery}"),
+    ("delivery.forward", "пересылка"),
+    ("delivery.copy_footer", "копия с подписью"),
+    ("delivery.copy", "копия"),
+    ("fail.request_access", "Не получилось отправить запрос. Ошибка: {error}"),
+    ("access.request", "🔑 Запрос доступа от {name} ({id})\nРегионы: {regions}\nПричина: {reason}"),
+    ("access.sent", "📨 Запрос отправлен администраторам"),
+    ("access.approve", "✅ Одобрить"),
+    ("access.approve_selected", "✅ Одобрить выбранные ({n})"),
+    ("access.deny", "❌ Отклонить"),
+    ("access.nothing_selected", "Не выбрано ни одного региона"),
+    ("fail.decide_access", "Не получилось рассмотреть запрос. Ошибка: {error}"),
+    ("access.denied_admin", "❌ Запрос {id} отклонён"),
+    ("access.approved_admin", "✅ Пользователю {id} открыты регионы: {regions}"),
+    ("access.denied", "❌ Запрос доступа отклонён"),
+    ("access.approved", "✅ Запрос доступа одобрен. Открыты регионы: {regions}"),
+    ("access.not_approved", "Не одобрены: {regions}"),
+    ("fail.save_cursor", "Не получилось сохранить результаты. Ошибка: {error}"),
+    ("results.stale", "Результаты устарели, повторите запрос"),
+    ("results.db_error", "База данных вернула ошибку"),
+    ("results.page", "🔎 Найдено сообщений: {total}. Страница {page}/{pages}"),
+    ("results.progress", "⏳ Регион: {region}\nОтправлено: {sent}/{total}"),
+    ("results.stopped", "⛔ Остановлено. Отправлено: {sent}/{total}"),
+    ("results.sent", "✅ Отправлено: {sent}/{total}"),
+    ("results.more", "Ещё"),
+    ("results.remaining", "Осталось сообщений: {n}"),
+    ("results.end", "🏁 Результаты по запросу"),
+    ("results.stopping", "Останавливаю"),
+    ("results.finished", "Отправка уже завершена"),
+    ("results.starting", "⏳ Начинаю отправку"),
+    ("results.stop", "Стоп"),
+    ("subscription.new_messages", "🔔 Новые сообщения: {regions}"),
+    ("expiry.registration", "бот — до {expires_at}"),
+    ("expiry.region", "{region} — до {expires_at}"),
+    ("expiry.user", "⏳ Скоро закончится доступ:\n{expiring}"),
+    ("expiry.admin", "⏳ Скоро закончится доступ пользователя {id}:\n{expiring}"),
+    ("chat.nothing_to_save", "⚠️Нет сообщений для сохранения⚠️"),
+    ("chat.saved", "Сохранено [{n}]\n{regions}{tags}"),
+    ("chat.remembered", "Принял {n}"),
+    ("chat.ignored", "⚠️Проигнорированно⚠️"),
+    ("list.users", "Пользователи: {n}"),
+    ("list.chats", "Чаты: {n}"),
+    ("list.empty", "Ничего не найдено."),
+    ("list.page", "Страница {page} из {pages}"),
+    ("list.unknown", "неизвестно"),
+    ("list.none", "нет"),
+    ("list.user", "👤 {name} ({id})"),
+    ("list.role", "Роль: {role}"),
+    ("list.regions", "Регионы: {regions}"),
+    ("list.admin_regions", "Администрирует: {regions}"),
+    ("list.expires", "Доступ до: {expires_at}"),
+    ("list.chat", "💬 {title} ({id})"),
+    ("list.members", "Участников: {members}"),
+    ("list.last_saved", "Последнее сохранённое сообщение: {last}"),
+    ("cmd.register", "Сохранять сообщения этого чата"),
+    ("chat.registered", "Чат зарегистрирован, теперь его сообщения можно сохранять."),
+    ("chat.already_registered", "Чат уже зарегистрирован."),
+    ("chat.bot_added", "Бота добавили в незарегистрированный чат «{title}» (id {id}), добавил {name} ({by}). Чтобы сохранять его сообщения, отправьте /register в чате или /add_chat {id}."),
+    ("chat.bot_removed", "Бота удалили из чата «{title}» (id {id}), удалил {name} ({by}). Сообщения из него больше не сохраняются."),
+    ("cmd.chat_settings", "Настройки чата: регионы и теги по умолчанию, язык"),
+    ("usage.chat_settings", "/chat_settings <id> [regions <регионы|off>|tags <теги|off>|lang <ru|en|default>]"),
+    ("cmd.settings", "Настройки чата"),
+    ("usage.settings", "/settings [regions <регионы|off>|tags <теги|off>|lang <ru|en|default>]"),
+    ("arg.tags", "теги"),
+    ("chat.settings", "Чат «{title}» (id {id})\nРегионы по умолчанию: {regions}\nТеги по умолчанию: {tags}\nЯзык: {language}"),
+    ("fail.chat_settings", "Не получилось изменить настройки чата. Ошибка: {error}"),
+    ("error.no_chat", "Чат {id} не зарегистрирован"),
+    ("cmd.editors", "Статистика редакторов по дням и регионам"),
+    ("usage.editors", "/editors [дней, по умолчанию 7] [OFFSET, по умолчанию из профиля]"),
+    ("arg.days", "дней"),
+    ("editors", "Сохранено редакторами за {days} дн. ({offset}):"),
+    ("editors.empty", "За {days} дн. редакторы ничего не сохранили."),
+    ("editors.editor", "✍️ {name} ({id}): {total}\nПо дням: {days}\nПо регионам: {regions}"),
+    ("stat.period", "За {days} дн. с {since}: {total}"),
+    ("stat.regions", "Регионы:"),
+    ("stat.tags", "Теги:"),
+    ("stat.chats", "Чаты:"),
+    ("stat.more", "…и ещё {n}"),
+    ("stat.idle", "Регионы без сообщений: {regions}"),
+    ("cmd.charts", "Графики активности по тегам и регионам"),
+    ("usage.charts", "/charts [дней, от 1 до 90, по умолчанию 30] [OFFSET, по умолчанию из профиля]"),
+    ("charts.volume", "Сообщений в день за {days} дн. ({offset})"),
+    ("charts.heatmap", "Сообщения по регионам за {days} дн. ({offset})"),
+    ("charts.untagged", "без тега"),
+    ("charts.other", "другие"),
+    ("charts.volume_caption", "📈 Всего сообщений: {total}"),
+    ("charts.heatmap_caption", "🗺 Регионы по дням, самая тёмная клетка — {max} сообщ."),
+    ("charts.empty", "За {days} дн. сообщений нет."),
+    ("charts.failed", "Не удалось построить графики ❌"),
+];
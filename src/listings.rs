@@ -0,0 +1,446 @@
+//! Paginated `/list_users` and `/list_chats` listings.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use mongodb::Client;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+use crate::callbacks::answer_callback;
+use crate::db_utils::{
+    models::{Profile, User as UserRecord, UserGroup},
+    user::User,
+};
+use crate::delivery::profile_for;
+use crate::i18n::{tr, Localize};
+use crate::sender::{self, Priority};
+
+lazy_static::lazy_static! {
+    static ref LIST_PAGE_SIZE: usize = std::env::var("LIST_PAGE_SIZE")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse LIST_PAGE_SIZE as usize"))
+        .unwrap_or(10);
+    /// The last listing sent to every chat and its token.
+    static ref LISTINGS: Mutex<HashMap<i64, (ObjectId, Arc<Listing>)>> = Mutex::new(HashMap::new());
+}
+
+pub const LIST_PREFIX: &str = "list";
+/// Bot API lookups made at once while a page is rendered.
+const LOOKUPS: usize = 5;
+
+/// Filter of `/list_users` and `/list_chats`. `name` matches a part of a username, a name
+/// or a chat title regardless of case.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ListFilter {
+    pub group: Option<UserGroup>,
+    pub region: Option<String>,
+    pub name: Option<String>,
+}
+
+impl ListFilter {
+    fn matches_name(&self, names: &[&str]) -> bool {
+        match &self.name {
+            Some(name) => {
+                let name = name.to_lowercase();
+                names.iter().any(|n| n.to_lowercase().contains(&name))
+            }
+            None => true,
+        }
+    }
+}
+
+/// A header repeated on every page and the entries.
+struct Listing {
+    header: String,
+    entries: Vec<Entry>,
+}
+
+/// A listed user or chat. Names come from the Bot API when the entry's page is shown,
+/// unless filtering by name needed them before.
+enum Entry {
+    User(UserRecord, Option<UserNames>),
+    Chat {
+        id: i64,
+        last: Option<DateTime<Utc>>,
+        names: Option<ChatNames>,
+    },
+}
+
+/// A user's username and full name.
+type UserNames = (Option<String>, String);
+/// A chat's title and username.
+type ChatNames = (String, Option<String>);
+
+/// Sends the first page of users matching `filter`.
+pub async fn users(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    user: &User,
+    filter: &ListFilter,
+    profile: &Profile,
+) {
+    let lang = profile.language;
+    let users = match user
+        .list_users(filter.group.iter().cloned().collect())
+        .await
+    {
+        Ok(users) => users,
+        Err(e) => return send_text(bot, chat_id, e.localize(lang), None).await,
+    };
+
+    let users = users.into_iter().filter(|u| {
+        let in_region = |r: &String| Some(r) == filter.region.as_ref();
+        filter.region.is_none()
+            || u.allowed_regions.iter().any(in_region)
+            || u.admin_regions.iter().any(in_region)
+    });
+    let entries = match filter.name {
+        Some(_) => {
+            futures::stream::iter(users)
+                .map(|u| async move {
+                    let names = user_names(bot, u.id).await;
+                    (u, names)
+                })
+                .buffered(LOOKUPS)
+                .filter(|(_, (username, name))| {
+                    let names = [username.as_deref().unwrap_or_default(), name.as_str()];
+                    futures::future::ready(filter.matches_name(&names))
+                })
+                .map(|(u, names)| Entry::User(u, Some(names)))
+                .collect::<Vec<_>>()
+                .await
+        }
+        None => users.map(|u| Entry::User(u, None)).collect(),
+    };
+
+    let header = tr!(lang, "list.users", n = entries.len());
+    start(bot, chat_id, header, entries, profile).await;
+}
+
+/// Sends the first page of chats matching `filter`, most recently active first.
+pub async fn chats(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    user: &User,
+    filter: &ListFilter,
+    profile: &Profile,
+) {
+    let lang = profile.language;
+    let (chats, last) = match (
+        user.list_chats().await,
+        user.last_ingestions(filter.region.as_deref()).await,
+    ) {
+        (Ok(chats), Ok(last)) => (chats, last),
+        (Err(e), _) | (_, Err(e)) => return send_text(bot, chat_id, e.localize(lang), None).await,
+    };
+    if chats.is_empty() {
+        return send_text(bot, chat_id, tr!(lang, "chats.empty"), None).await;
+    }
+
+    let mut chats = chats
+        .into_iter()
+        .filter(|id| filter.region.is_none() || last.contains_key(id))
+        .map(|id| (id, last.get(&id).copied()))
+        .collect::<Vec<_>>();
+    chats.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let entries = match filter.name {
+        Some(_) => {
+            futures::stream::iter(chats)
+                .map(|(id, last)| async move {
+                    let names = chat_names(bot, id).await;
+                    (id, last, names)
+                })
+                .buffered(LOOKUPS)
+                .filter(|(_, _, (title, username))| {
+                    let names = [title.as_str(), username.as_deref().unwrap_or_default()];
+                    futures::future::ready(filter.matches_name(&names))
+                })
+                .map(|(id, last, names)| Entry::Chat {
+                    id,
+                    last,
+                    names: Some(names),
+                })
+                .collect::<Vec<_>>()
+                .await
+        }
+        None => chats
+            .into_iter()
+            .map(|(id, last)| Entry::Chat {
+                id,
+                last,
+                names: None,
+            })
+            .collect(),
+    };
+
+    let header = tr!(lang, "list.chats", n = entries.len());
+    start(bot, chat_id, header, entries, profile).await;
+}
+
+/// Handles a press of a page button with data `list:<token>:<page>`.
+pub async fn callback(
+    cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>,
+    client: &Client,
+    data: &str,
+) {
+    let mut split = data.split(':').skip(1);
+    let token = split.next().and_then(|t| t.parse::<ObjectId>().ok());
+    let page = split.next().and_then(|p| p.parse::<usize>().ok());
+    let message = match &cx.update.message {
+        Some(m) => m,
+        None => return answer_callback(cx, None).await,
+    };
+
+    let profile = profile_for(client, cx.update.from.id).await;
+    let listing = LISTINGS
+        .lock()
+        .unwrap()
+        .get(&message.chat.id)
+        .filter(|(t, _)| Some(*t) == token)
+        .map(|(token, listing)| (*token, Arc::clone(listing)))
+        .zip(page);
+    let ((token, listing), page) = match listing {
+        Some(listing) => listing,
+        None => {
+            let text = tr!(profile.language, "results.stale");
+            return answer_callback(cx, Some(&text)).await;
+        }
+    };
+    answer_callback(cx, None).await;
+
+    let (text, pages) = render_page(&cx.requester, &listing, page, &profile).await;
+    let keyboard = keyboard(token, page, pages);
+    if let Err(e) = sender::send(message.chat.id, Priority::Interactive, || {
+        let edit = cx
+            .requester
+            .edit_message_text(message.chat.id, message.id, text.as_str());
+        match &keyboard {
+            Some(k) => edit.reply_markup(k.clone()),
+            None => edit,
+        }
+    })
+    .await
+    {
+        log::error!("Error while editing a listing: {}", e);
+    }
+}
+
+/// Remembers `entries` as the listing of `chat_id` and sends its first page.
+async fn start(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    header: String,
+    entries: Vec<Entry>,
+    profile: &Profile,
+) {
+    let lang = profile.language;
+    if entries.is_empty() {
+        return send_text(bot, chat_id, tr!(lang, "list.empty"), None).await;
+    }
+    let token = ObjectId::new();
+    let listing = Arc::new(Listing { header, entries });
+    LISTINGS
+        .lock()
+        .unwrap()
+        .insert(chat_id, (token, Arc::clone(&listing)));
+    let (text, pages) = render_page(bot, &listing, 0, profile).await;
+    send_text(bot, chat_id, text, keyboard(token, 0, pages)).await;
+}
+
+/// Text of page `page` and the number of pages. Only the entries of the page are looked up.
+async fn render_page(
+    bot: &AutoSend<Bot>,
+    listing: &Listing,
+    page: usize,
+    profile: &Profile,
+) -> (String, usize) {
+    let pages = listing.entries.len().div_ceil(*LIST_PAGE_SIZE);
+    let page = page.min(pages.saturating_sub(1));
+    let start = (page * *LIST_PAGE_SIZE).min(listing.entries.len());
+    let end = (start + *LIST_PAGE_SIZE).min(listing.entries.len());
+    let mut entries = Vec::new();
+    for chunk in listing.entries[start..end].chunks(LOOKUPS) {
+        let formatted = chunk.iter().map(|e| format_entry(bot, e, profile));
+        entries.extend(futures::future::join_all(formatted).await);
+    }
+
+    let mut text = listing.header.clone();
+    for entry in entries {
+        text.push_str("\n\n");
+        text.push_str(&entry);
+    }
+    if pages > 1 {
+        text.push_str("\n\n");
+        let lang = profile.language;
+        text.push_str(&tr!(lang, "list.page", page = page + 1, pages = pages));
+    }
+    (text, pages)
+}
+
+async fn format_entry(bot: &AutoSend<Bot>, entry: &Entry, profile: &Profile) -> String {
+    match entry {
+        Entry::User(user, names) => {
+            let (username, name) = match names {
+                Some(names) => names.clone(),
+                None => user_names(bot, user.id).await,
+            };
+            format_user(user, username, name, profile)
+        }
+        Entry::Chat { id, last, names } => {
+            let (title, username) = match names {
+                Some(names) => names.clone(),
+                None => chat_names(bot, *id).await,
+            };
+            let members = match sender::lookup(|| bot.get_chat_member_count(*id)).await {
+                Ok(n) => Some(n),
+                Err(e) => {
+                    log::warn!("Can't get member count of chat {}. Error: {}", id, e);
+                    None
+                }
+            };
+            format_chat(*id, title, username, members, *last, profile)
+        }
+    }
+}
+
+async fn user_names(bot: &AutoSend<Bot>, id: i64) -> UserNames {
+    match sender::lookup(|| bot.get_chat(id)).await {
+        Ok(chat) => (
+            chat.username().map(|u| format!("@{}", u)),
+            [chat.first_name(), chat.last_name()]
+                .iter()
+                .flatten()
+                .copied()
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        Err(e) => {
+            log::warn!("Can't get chat of user {}. Error: {}", id, e);
+            (None, String::new())
+        }
+    }
+}
+
+async fn chat_names(bot: &AutoSend<Bot>, id: i64) -> ChatNames {
+    match sender::lookup(|| bot.get_chat(id)).await {
+        Ok(chat) => (
+            chat.title().unwrap_or_default().to_string(),
+            chat.username().map(|u| format!("@{}", u)),
+        ),
+        Err(e) => {
+            log::warn!("Can't get chat {}. Error: {}", id, e);
+            (String::new(), None)
+        }
+    }
+}
+
+fn keyboard(token: ObjectId, page: usize, pages: usize) -> Option<InlineKeyboardMarkup> {
+    let button = |label: &str, page: usize| {
+        InlineKeyboardButton::callback(
+            label.to_string(),
+            format!("{}:{}:{}", LIST_PREFIX, token.to_hex(), page),
+        )
+    };
+    let mut row = Vec::new();
+    if page > 0 {
+        row.push(button("◀️", page - 1));
+    }
+    if page + 1 < pages {
+        row.push(button("▶️", page + 1));
+    }
+    (!row.is_empty()).then(|| InlineKeyboardMarkup::default().append_row(row))
+}
+
+async fn send_text(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    text: String,
+    keyboard: Option<InlineKeyboardMarkup>,
+) {
+    if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+        let send = bot.send_message(chat_id, text.as_str());
+        match &keyboard {
+            Some(k) => send.reply_markup(k.clone()),
+            None => send,
+        }
+    })
+    .await
+    {
+        log::error!("Error while sending a listing: {}", e);
+    }
+}
+
+fn format_user(
+    user: &UserRecord,
+    username: Option<String>,
+    name: String,
+    profile: &Profile,
+) -> String {
+    let lang = profile.language;
+    let name = match (username, name.is_empty()) {
+        (Some(username), true) => username,
+        (Some(username), false) => format!("{} — {}", username, name),
+        (None, false) => name,
+        (None, true) => tr!(lang, "list.unknown"),
+    };
+    let regions = match user.allowed_regions.is_empty() {
+        true => tr!(lang, "list.none"),
+        false => user.allowed_regions.join(", "),
+    };
+    let mut lines = vec![
+        tr!(lang, "list.user", name = name, id = user.id),
+        tr!(lang, "list.role", role = user.group),
+        tr!(lang, "list.regions", regions = regions),
+    ];
+    if !user.admin_regions.is_empty() {
+        let regions = user.admin_regions.join(", ");
+        lines.push(tr!(lang, "list.admin_regions", regions = regions));
+    }
+    if let Some(expiry) = &user.expiry {
+        let expires_at = format_time(expiry.expires_at, profile);
+        lines.push(tr!(lang, "list.expires", expires_at = expires_at));
+    }
+    lines.join("\n")
+}
+
+fn format_chat(
+    id: i64,
+    title: String,
+    username: Option<String>,
+    members: Option<u32>,
+    last: Option<DateTime<Utc>>,
+    profile: &Profile,
+) -> String {
+    let lang = profile.language;
+    let title = match (title.is_empty(), username) {
+        (false, Some(username)) => format!("{} {}", title, username),
+        (false, None) => title,
+        (true, Some(username)) => username,
+        (true, None) => tr!(lang, "list.unknown"),
+    };
+    let members = members
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| tr!(lang, "list.unknown"));
+    let last = last
+        .map(|t| format_time(t, profile))
+        .unwrap_or_else(|| tr!(lang, "list.none"));
+    [
+        tr!(lang, "list.chat", title = title, id = id),
+        tr!(lang, "list.members", members = members),
+        tr!(lang, "list.last_saved", last = last),
+    ]
+    .join("\n")
+}
+
+fn format_time(time: DateTime<Utc>, profile: &Profile) -> String {
+    time.with_timezone(&profile.offset())
+        .format("%d.%m.%y %H:%M")
+        .to_string()
+}
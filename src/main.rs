@@ -7,27 +7,44 @@ use group_handlers::Chat;
 use mongodb::{options::ClientOptions, Client};
 use private_handlers::Private;
 use std::sync::RwLock;
-use teloxide::{prelude::*, types::MessageKind, RequestError};
+use teloxide::{
+    dispatching::dialogue::InMemStorageError, prelude::*, types::MessageKind, RequestError,
+};
 use tokio::sync::Mutex;
 
 use derive_more::From;
 use teloxide::macros::Transition;
 
+mod access;
+mod callbacks;
+mod charts;
+mod chats;
+mod commands;
 mod common;
 mod db_utils;
+mod delivery;
 mod error;
+mod expiry;
+mod export;
 mod group_handlers;
+mod i18n;
+mod listings;
 mod private_handlers;
+mod scheduler;
+mod sender;
+mod subscriptions;
 
 #[derive(Clone)]
 pub struct Echo;
 
 #[teloxide(subtransition)]
 async fn echo(_: Echo, cx: TransitionIn<AutoSend<Bot>>, _: String) -> TransitionOut<Dialogue> {
-    while let Err(teloxide::RequestError::RetryAfter(secs)) =
-        cx.answer(cx.update.text().unwrap_or("Echo")).await
+    if let Err(e) = sender::send(cx.chat_id(), sender::Priority::Interactive, || {
+        cx.answer(cx.update.text().unwrap_or("Echo"))
+    })
+    .await
     {
-        tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await;
+        log::error!("Error while sending a string: {}", e);
     }
     next(Echo)
 }
@@ -56,7 +73,8 @@ lazy_static::lazy_static! {
     pub static ref ALL_TAGS: RwLock<HashSet<&'static str>> = RwLock::new(HashSet::new());
     pub static ref ALL_REGIONS: RwLock<HashSet<&'static str>> = RwLock::new(HashSet::new());
     pub static ref ALLIAS_REGIONS: RwLock<HashMap<&'static str, &'static str>> = RwLock::new(HashMap::new());
-    pub static ref ALL_CHATS: tokio::sync::RwLock<HashSet<i64>> = tokio::sync::RwLock::new(HashSet::new());
+    /// The bot's own user, fetched once at startup.
+    pub static ref BOT_USER: RwLock<Option<teloxide::types::User>> = RwLock::new(None);
 }
 
 async fn run() {
@@ -142,14 +160,10 @@ async fn run() {
             .write()
             .map_err(|e| log::error!("Can't lock ALL_TAGS. Error: {}", e))
             .unwrap();
-        let mut chats = ALL_CHATS.write().await;
-        db_utils::get_chats(mongo_client)
+        chats::CHATS
+            .load(mongo_client)
             .await
-            .expect("Can't access chats. Bad response from server.")
-            .into_iter()
-            .for_each(|c| {
-                chats.insert(c);
-            });
+            .expect("Can't access chats. Bad response from server.");
         db_utils::get_tags(mongo_client)
             .await
             .expect("Can't access tags. Bad response from server.")
@@ -167,91 +181,159 @@ async fn run() {
 
     let bot = Bot::from_env().auto_send();
 
-    teloxide::dialogues_repl(bot, move |cx, dialogue: Dialogue| async move {
-        let chat = match cx.requester.get_chat(cx.chat_id()).await {
-            Ok(c) => c,
-            Err(e) => {
-                log::error!("Can't get chat from context. Error: {}", e);
-                return next::<_, _, RequestError>(dialogue)
-                    .map_err(|e| log::error!("Error while skipping message: {}", e))
-                    .unwrap();
-            }
-        };
-        let text = cx.update.text();
-        let private = chat.is_private();
-        let chat_in_table = ALL_CHATS.read().await.contains(&cx.chat_id());
-        let default = next::<_, _, RequestError>(Dialogue::from(Echo))
-            .map_err(|_| unreachable!())
-            .unwrap();
+    tokio::spawn(sender::run());
+    {
+        let me = sender::lookup(|| bot.get_me())
+            .await
+            .expect("Can't get the bot user. Bad response from server.");
+        *BOT_USER
+            .write()
+            .map_err(|e| log::error!("Can't lock BOT_USER. Error: {}", e))
+            .unwrap() = Some(me.user);
+    }
+    tokio::spawn(commands::register_menus(
+        bot.clone(),
+        Arc::clone(mongo_client),
+    ));
+    tokio::spawn(scheduler::run(bot.clone(), Arc::clone(mongo_client)));
+    tokio::spawn(expiry::run(bot.clone(), Arc::clone(mongo_client)));
 
-        match cx.update.kind {
-            MessageKind::Common(_) => {}
-            MessageKind::Migrate(m) => {
-                log::trace!(
-                    "Super chat created: \"{}\". Migrate from: {}. Migrate to: {}",
-                    chat.title().unwrap_or_default(),
-                    m.migrate_from_chat_id,
-                    m.migrate_to_chat_id
-                );
-                if let Err(e) = db_utils::migrate_chat(
-                    mongo_client,
+    Dispatcher::new(bot)
+        .messages_handler(DialogueDispatcher::new(
+            move |DialogueWithCx { cx, dialogue }: DialogueWithCx<
+                AutoSend<Bot>,
+                Message,
+                Dialogue,
+                InMemStorageError,
+            >| async move {
+                let dialogue = dialogue.expect("std::convert::Infallible");
+                handle_message(cx, dialogue, mongo_client).await
+            },
+        ))
+        .callback_queries_handler(move |rx| callbacks::handle(rx, Arc::clone(mongo_client)))
+        .my_chat_members_handler(move |rx| chats::handle_membership(rx, Arc::clone(mongo_client)))
+        .setup_ctrlc_handler()
+        .dispatch()
+        .await;
+}
+
+async fn handle_message(
+    cx: UpdateWithCx<AutoSend<Bot>, Message>,
+    dialogue: Dialogue,
+    mongo_client: &'static Arc<Client>,
+) -> DialogueStage<Dialogue> {
+    let chat = match sender::lookup(|| cx.requester.get_chat(cx.chat_id())).await {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("Can't get chat from context. Error: {}", e);
+            return next::<_, _, RequestError>(dialogue)
+                .map_err(|e| log::error!("Error while skipping message: {}", e))
+                .unwrap();
+        }
+    };
+    let text = cx.update.text();
+    let private = chat.is_private();
+    let chat_in_table = chats::CHATS.contains(cx.chat_id()).await;
+    let default = next::<_, _, RequestError>(Dialogue::from(Echo))
+        .map_err(|_| unreachable!())
+        .unwrap();
+
+    match &cx.update.kind {
+        MessageKind::Common(_) => {}
+        MessageKind::NewChatMembers(_)
+        | MessageKind::GroupChatCreated(_)
+        | MessageKind::SupergroupChatCreated(_) => {
+            group_handlers::notify_added(&cx, mongo_client).await;
+            return next::<_, _, RequestError>(dialogue)
+                .map_err(|e| log::error!("Error while skipping message: {}", e))
+                .unwrap();
+        }
+        MessageKind::Migrate(m) => {
+            log::trace!(
+                "Super chat created: \"{}\". Migrate from: {}. Migrate to: {}",
+                chat.title().unwrap_or_default(),
+                m.migrate_from_chat_id,
+                m.migrate_to_chat_id
+            );
+            if let Err(e) = chats::CHATS
+                .migrate(mongo_client, m.migrate_from_chat_id, m.migrate_to_chat_id)
+                .await
+            {
+                log::error!(
+                    "Can't migrate chat from {} to {}. Error: {}",
                     m.migrate_from_chat_id,
                     m.migrate_to_chat_id,
+                    e
                 )
-                .await
-                {
-                    log::error!(
-                        "Can't migrate chat from {} to {}. Error: {}",
-                        m.migrate_from_chat_id,
-                        m.migrate_to_chat_id,
-                        e
-                    )
-                }
-                return next::<_, _, RequestError>(dialogue)
-                    .map_err(|e| log::error!("Error while skipping message: {}", e))
-                    .unwrap();
-            }
-            m => {
-                log::debug!("Unhandlable message: {:?}", m);
-                return next::<_, _, RequestError>(dialogue)
-                    .map_err(|e| log::error!("Error while skipping message: {}", e))
-                    .unwrap();
             }
+            return next::<_, _, RequestError>(dialogue)
+                .map_err(|e| log::error!("Error while skipping message: {}", e))
+                .unwrap();
+        }
+        m => {
+            log::debug!("Unhandlable message: {:?}", m);
+            return next::<_, _, RequestError>(dialogue)
+                .map_err(|e| log::error!("Error while skipping message: {}", e))
+                .unwrap();
         }
+    }
 
-        if private {
-            let (username, id) = match cx.update.from() {
-                Some(u) => (u.username.as_ref().map(|s| s.as_str()).unwrap_or(""), u.id),
+    if private {
+        let (username, id) = match cx.update.from() {
+            Some(u) => (u.username.as_ref().map(|s| s.as_str()).unwrap_or(""), u.id),
+            None => {
+                log::error!("Can't access `from` from update");
+                ("", 0)
+            }
+        };
+        log::info!(
+            "Username: \"{}\". User id: {}. Text: \"{}\"",
+            username,
+            id,
+            text.unwrap_or_default()
+        );
+    } else {
+        log::info!(
+            "Chat title: \"{}\". Chat id: {}. Text: \"{}\"",
+            chat.title().unwrap_or_default(),
+            chat.id,
+            text.unwrap_or_default()
+        );
+    }
+    if !private {
+        if let Some((name, args)) = group_handlers::command(text) {
+            match name {
+                "register" => group_handlers::register(&cx, mongo_client).await,
+                _ => group_handlers::settings(&cx, mongo_client, args).await,
+            }
+            return next::<_, _, RequestError>(dialogue)
+                .map_err(|e| log::error!("Error while skipping message: {}", e))
+                .unwrap();
+        }
+    }
+    match (private, &dialogue) {
+        (true, Dialogue::Echo(_)) => Dialogue::from(Private::new(
+            Arc::clone(mongo_client),
+            match cx.update.from() {
+                Some(u) => u.id,
                 None => {
-                    log::error!("Can't access `from` from update");
-                    ("", 0)
+                    log::error!("Can't access `from` from update, terminating...");
+                    std::process::exit(1)
                 }
-            };
-            log::info!(
-                "Username: \"{}\". User id: {}. Text: \"{}\"",
-                username,
-                id,
-                text.unwrap_or_default()
-            );
-        } else {
-            log::info!(
-                "Chat title: \"{}\". Chat id: {}. Text: \"{}\"",
-                chat.title().unwrap_or_default(),
-                chat.id,
-                text.unwrap_or_default()
-            );
-        }
-        match (private, &dialogue) {
-            (true, Dialogue::Echo(_)) => Dialogue::from(Private::new(
-                Arc::clone(mongo_client),
-                match cx.update.from() {
-                    Some(u) => u.id,
-                    None => {
-                        log::error!("Can't access `from` from update, terminating...");
-                        std::process::exit(1)
-                    }
-                },
-            ))
+            },
+        ))
+        .react(cx, String::new())
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Error while reacting to an update [{file}/{line}]: {err}",
+                err = e,
+                file = file!(),
+                line = line!(),
+            )
+        })
+        .unwrap_or(default),
+        (false, Dialogue::Echo(_)) if !chat_in_table => dialogue
             .react(cx, String::new())
             .await
             .map_err(|e| {
@@ -263,7 +345,8 @@ async fn run() {
                 )
             })
             .unwrap_or(default),
-            (false, Dialogue::Echo(_)) if !chat_in_table => dialogue
+        (false, Dialogue::Echo(_)) if chat_in_table => {
+            Dialogue::from(Chat::new(Arc::clone(mongo_client)))
                 .react(cx, String::new())
                 .await
                 .map_err(|e| {
@@ -274,59 +357,44 @@ async fn run() {
                         line = line!(),
                     )
                 })
-                .unwrap_or(default),
-            (false, Dialogue::Echo(_)) if chat_in_table => {
-                Dialogue::from(Chat::new(Arc::clone(mongo_client)))
-                    .react(cx, String::new())
-                    .await
-                    .map_err(|e| {
-                        log::error!(
-                            "Error while reacting to an update [{file}/{line}]: {err}",
-                            err = e,
-                            file = file!(),
-                            line = line!(),
-                        )
-                    })
-                    .unwrap_or(default)
-            }
-            (true, Dialogue::Private(_)) => dialogue
-                .react(cx, String::new())
-                .await
-                .map_err(|e| {
-                    log::error!(
-                        "Error while reacting to an update [{file}/{line}]: {err}",
-                        err = e,
-                        file = file!(),
-                        line = line!(),
-                    )
-                })
-                .unwrap_or(default),
-            (false, Dialogue::Chat(_)) if chat_in_table => dialogue
-                .react(cx, String::new())
-                .await
-                .map_err(|e| {
-                    log::error!(
-                        "Error while reacting to an update [{file}/{line}]: {err}",
-                        err = e,
-                        file = file!(),
-                        line = line!(),
-                    )
-                })
-                .unwrap_or(default),
-            (false, Dialogue::Chat(_)) if !chat_in_table => Dialogue::from(Echo)
-                .react(cx, String::new())
-                .await
-                .map_err(|e| {
-                    log::error!(
-                        "Error while reacting to an update [{file}/{line}]: {err}",
-                        err = e,
-                        file = file!(),
-                        line = line!(),
-                    )
-                })
-                .unwrap_or(default),
-            _ => unreachable!(),
+                .unwrap_or(default)
         }
-    })
-    .await;
+        (true, Dialogue::Private(_)) => dialogue
+            .react(cx, String::new())
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "Error while reacting to an update [{file}/{line}]: {err}",
+                    err = e,
+                    file = file!(),
+                    line = line!(),
+                )
+            })
+            .unwrap_or(default),
+        (false, Dialogue::Chat(_)) if chat_in_table => dialogue
+            .react(cx, String::new())
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "Error while reacting to an update [{file}/{line}]: {err}",
+                    err = e,
+                    file = file!(),
+                    line = line!(),
+                )
+            })
+            .unwrap_or(default),
+        (false, Dialogue::Chat(_)) if !chat_in_table => Dialogue::from(Echo)
+            .react(cx, String::new())
+            .await
+            .map_err(|e| {
+                log::error!(
+                    "Error while reacting to an update [{file}/{line}]: {err}",
+                    err = e,
+                    file = file!(),
+                    line = line!(),
+                )
+            })
+            .unwrap_or(default),
+        _ => unreachable!(),
+    }
 }
@@ -1,26 +1,68 @@
-use crate::ALL_CHATS;
 use bson::oid::ObjectId;
-use chrono::Duration;
+use chrono::{Duration, TimeZone};
 use mongodb::Client;
-use std::{collections::BTreeMap, str::FromStr, sync::Arc};
-use teloxide::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
 
 lazy_static::lazy_static! {
+    /// Rows of every breakdown in /statdb.
+    static ref STAT_TOP: usize = std::env::var("STAT_TOP")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse STAT_TOP as usize"))
+        .unwrap_or(10);
+    static ref CONFIRM_THRESHOLD: usize = std::env::var("CONFIRM_THRESHOLD")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse CONFIRM_THRESHOLD as usize"))
+        .unwrap_or(50);
+    static ref DELETE_CONFIRM_TIMEOUT: u64 = std::env::var("DELETE_CONFIRM_TIMEOUT")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse DELETE_CONFIRM_TIMEOUT as u64"))
+        .unwrap_or(60);
+    /// Seconds a query preview waits for a button press.
+    static ref QUERY_CONFIRM_TIMEOUT: u64 = std::env::var("QUERY_CONFIRM_TIMEOUT")
+        .ok()
+        .map(|s| s.parse().expect("Can't parse QUERY_CONFIRM_TIMEOUT as u64"))
+        .unwrap_or(600);
+    static ref PENDING_DELETIONS: tokio::sync::Mutex<HashMap<i64, PendingDeletion>>
+        = tokio::sync::Mutex::new(HashMap::new());
+    static ref PENDING_QUERIES: tokio::sync::Mutex<HashMap<i64, PendingQuery>>
+        = tokio::sync::Mutex::new(HashMap::new());
     static ref GET_REGEX: regex::Regex
-        = regex::Regex::new(r"^(?P<regions>([\p{L}-]{2,}\s*)+([\p{L}-]{2,})?)?(?P<since>\s+\d+)?(?P<duration>\s+\d+)?\s*(?P<tags>(\p{L}\s+)*\p{L}$)?$")
-            .expect("Cant create a regex");
-    static ref CMD_REGEX: regex::Regex
-        = regex::Regex::new(r"^/(?P<start>start$)|(?P<help>help$)|(?P<list_users>list_users$)|(?P<add_user>add_user\s+-?\d+(\s+Admin)?$)|(?P<del_user>del_user\s+-?\d+$)|(?P<add_user_regions>add_user_regions\s+-?\d+(\s+\w+)*$)|(?P<del_user_regions>del_user_regions\s+-?\d+(\s+\w+)*$)|(?P<list_chats>list_chats$)|(?P<add_chat>add_chat\s+-?\d+$)|(?P<del_chat>del_chat\s+-?\d+$)|(?P<listdb>listdb(\s+\d{2}\.\d{2}\.\d{2}(\s+[+\-]\d{2}:\d{2})?)?$)|(?P<deldb>deldb\s+[0-9a-zA-Z]{24}$)|(?P<cleandb>cleandb\s+\d+$)|(?P<statdb>statdb(\s+[+\-]\d{2}:\d{2})?$)")
+        = regex::Regex::new(r"^(?P<regions>([\p{L}-]{2,}\s*)+([\p{L}-]{2,})?)?(?P<since>\s*\d+)?(?P<duration>\s+\d+)?\s*(?P<tags>(\p{L}\s+)*\p{L}$)?$")
             .expect("Cant create a regex");
 }
 
+use crate::charts;
+use crate::chats::{self, CHATS};
+use crate::i18n::{tr, Language, Localize};
 use crate::{
+    access,
+    callbacks::answer_callback,
+    commands::{self, Command, DeliverySetting, ProfileSetting},
     common::*,
-    db_utils::{self, models::UserGroup},
-    error::Error,
-    Dialogue,
+    db_utils::{
+        self,
+        models::{
+            AuditEntry, Chat, DbStat, Deletion, EditorStat, Expiry, Invite, Permission, Profile,
+        },
+    },
+    delivery,
+    error::{CommandError, Error},
+    export, listings, scheduler,
+    sender::{self, Priority},
+    Dialogue, ALL_REGIONS,
 };
 
+pub const QUERY_PREFIX: &str = "query";
+pub const DELETE_PREFIX: &str = "delete";
+const PREVIEW_REGION_BUTTONS: usize = 6;
+
 #[derive(Clone)]
 pub struct Private(db_utils::user::User);
 
@@ -45,399 +87,626 @@ async fn private_impl(
     _: String,
 ) -> TransitionOut<Dialogue> {
     let text = cx.update.text();
+    let profile = state.0.profile().await.unwrap_or_default();
+    let lang = profile.language;
 
-    if let Some(c) = CMD_REGEX.captures(text.unwrap_or_default()) {
-        if let Some(_) = c.name("start").map(|m| m.as_str()) {
-            match state.0.start() {
-                Ok(s) => send_str(&cx, s).await,
-                Err(e) => send_str(&cx, e.to_string().as_str()).await,
+    match text.and_then(|t| Command::parse(t, profile.offset())) {
+        Some(Ok(command)) => {
+            run_command(&state, &cx, command, &profile).await;
+            return next(state);
+        }
+        Some(Err(CommandError::Unknown(_))) => {
+            match state.0.help(lang).await {
+                Ok(s) => send_str(&cx, s.as_str()).await,
+                Err(e) => send_str(&cx, e.localize(lang).as_str()).await,
             }
-        } else if let Some(_) = c.name("help").map(|m| m.as_str()) {
-            match state.0.help().await {
-                Ok(s) => send_str(&cx, s).await,
-                Err(e) => send_str(&cx, e.to_string().as_str()).await,
+            return next(state);
+        }
+        Some(Err(e)) => {
+            send_str(&cx, e.localize(lang).as_str()).await;
+            return next(state);
+        }
+        None => {}
+    }
+
+    let group = state.0.get_group().await;
+    match group {
+        Ok(g) if !g.has(Permission::Query) => {
+            send_str(&cx, text.unwrap_or_default()).await;
+            return next(state);
+        }
+        Err(e) => {
+            let text = tr!(lang, "fail.command", error = e.localize(lang));
+            send_str(&cx, text.as_str()).await;
+            return next(state);
+        }
+        _ => {}
+    }
+
+    let messages = match preview_or_query(&state.0, text.unwrap_or_default(), &profile).await {
+        Ok(messages) => messages,
+        Err(e) => _Message::Error(e.localize(lang)),
+    };
+    match messages {
+        _Message::Message(messages) => {
+            delivery::deliver(
+                &cx.requester,
+                &state.0.client,
+                cx.chat_id(),
+                state.0.id,
+                messages,
+            )
+            .await;
+        }
+        _Message::Preview(filter, count) => {
+            send_preview(&cx.requester, cx.chat_id(), state.0.id, filter, count, lang).await;
+        }
+        _Message::Error(string) => {
+            if let Err(e) = sender::send(cx.chat_id(), Priority::Interactive, || {
+                cx.reply_to(string.as_str())
+            })
+            .await
+            {
+                log::error!("Error while sending a string: {}", e);
             }
-        } else if let Some(_) = c.name("list_users").map(|m| m.as_str()) {
-            match state.0.list_users(vec![]).await {
-                Ok(users) => {
-                    for user in users {
-                        let s = serde_json::to_string_pretty(&user).unwrap_or_default();
-                        send_str(&cx, s.as_str()).await;
-                    }
+        }
+    }
+
+    next(state)
+}
+
+async fn run_command(
+    state: &Private,
+    cx: &TransitionIn<AutoSend<Bot>>,
+    command: Command,
+    profile: &Profile,
+) {
+    let offset = profile.offset();
+    let lang = profile.language;
+    match command {
+        Command::Start(None) => match state.0.start(lang) {
+            Ok(s) => send_str(cx, s.as_str()).await,
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::Start(Some(token)) => match state.0.redeem_invite(&token).await {
+            Ok(invite) => {
+                let group = state.0.get_group().await.unwrap_or(invite.group);
+                commands::set_menu(&cx.requester, state.0.id, Some(&group), lang).await;
+                let r = tr!(
+                    lang,
+                    "invite.redeemed",
+                    regions = invite.regions.join(", "),
+                    help = commands::help(&group, lang)
+                );
+                send_str(cx, r.as_str()).await;
+            }
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::Help => match state.0.help(lang).await {
+            Ok(s) => send_str(cx, s.as_str()).await,
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::Request { regions, reason } => {
+            let name = cx.update.from().map(|u| u.full_name()).unwrap_or_default();
+            let r = access::request(&cx.requester, &state.0, &name, regions, reason, lang).await;
+            send_str(cx, r.as_str()).await;
+        }
+        Command::Invite {
+            group,
+            regions,
+            uses,
+            term,
+        } => {
+            let r = match state.0.create_invite(group, regions, uses, term).await {
+                Ok(invite) => {
+                    let bot_name = bot_user().username.unwrap_or_default();
+                    format_invite(&invite, &bot_name, offset, lang)
                 }
-                Err(e) => send_str(&cx, e.to_string().as_str()).await,
+                Err(e) => tr!(lang, "fail.create_invite", error = e.localize(lang)),
+            };
+            send_str(cx, r.as_str()).await;
+        }
+        Command::ListUsers(filter) => {
+            listings::users(&cx.requester, cx.chat_id(), &state.0, &filter, profile).await
+        }
+        Command::AddUser { id, group, term } => {
+            let r = state
+                .0
+                .add_user(db_utils::models::User {
+                    id,
+                    group: group.clone(),
+                    allowed_regions: Vec::new(),
+                    admin_regions: Vec::new(),
+                    profile: Default::default(),
+                    expiry: term.map(|t| Expiry::after(t, state.0.id)),
+                    grants: Vec::new(),
+                })
+                .await;
+            if r.is_ok() {
+                let user_lang = delivery::profile_for(&state.0.client, id).await.language;
+                commands::set_menu(&cx.requester, id, Some(&group), user_lang).await;
             }
-        } else if let Some(add_user) = c.name("add_user").map(|m| m.as_str()) {
-            let id = add_user
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-            let group = db_utils::models::UserGroup::from_str(
-                add_user.split_whitespace().nth(2).unwrap_or("Registered"),
-            )
-            .unwrap_or(db_utils::models::UserGroup::Registered);
-
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                let r = state
-                    .0
-                    .add_user(db_utils::models::User { id, group, allowed_regions: Vec::new() })
-                    .await
-                    .map(|_| format!("Добавил пользователя с id {}", id))
-                    .unwrap_or_else(|e| {
-                        format!("не получилось добавить пользователя. Ошибка: {}", e)
-                    });
-                send_str(&cx, r.as_str()).await;
+            let r = r
+                .map(|_| tr!(lang, "user.added", id = id))
+                .unwrap_or_else(|e| tr!(lang, "fail.add_user", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::DelUser(id) => {
+            let r = state.0.delete_user(id).await;
+            if r.is_ok() {
+                commands::set_menu(&cx.requester, id, None, lang).await;
             }
-        } else if let Some(del_user) = c.name("del_user").map(|m| m.as_str()) {
-            let id = del_user
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                let r = state
-                    .0
-                    .delete_user(id)
-                    .await
-                    .map(|_| format!("Удалил пользователя с id {}", id))
-                    .unwrap_or_else(|e| {
-                        format!("не получилось удалить пользователя. Ошибка: {}", e)
-                    });
-                send_str(&cx, r.as_str()).await;
+            let r = r
+                .map(|_| tr!(lang, "user.deleted", id = id))
+                .unwrap_or_else(|e| tr!(lang, "fail.delete_user", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::AddUserRegions { id, term, regions } => {
+            if let Err(e) = state.0.add_user_regions(id, regions, term).await {
+                let text = tr!(lang, "fail.update_user", error = e.localize(lang));
+                send_str(cx, text.as_str()).await;
             }
-        } else if let Some(add_user_regions) = c.name("add_user_regions").map(|m| m.as_str()) {
-            let mut split = add_user_regions.split_whitespace();
-            let id = split
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-            let regs = split.next().unwrap();
-            let len = add_user_regions.len()
-                - (regs.as_ptr() as usize - add_user_regions.as_ptr() as usize);
-            let regions = unsafe {
-                std::str::from_utf8_unchecked(std::slice::from_raw_parts(regs.as_ptr(), len))
-            };
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                match extract_regions(regions) {
-                    Regions::Regions(r) | Regions::Country(r) => {
-                        if let Err(e) = state
-                            .0
-                            .add_user_regions(id, r.iter().map(|&s| s.into()).collect())
-                            .await
-                        {
-                            send_str(
-                                &cx,
-                                format!("Не получилось изменить пользователя. Ошибка: {}", e)
-                                    .as_str(),
-                            )
-                            .await;
-                        }
-                    }
-                    Regions::BadRegion { region, matches } => {
-                        let error = Error::BadRegion {
-                            region: region.into(),
-                            matches,
-                        };
-                        send_str(&cx, error.to_string().as_str()).await;
-                    }
-                }
+        }
+        Command::DelUserRegions { id, regions } => {
+            if let Err(e) = state.0.del_user_regions(id, regions).await {
+                let text = tr!(lang, "fail.update_user", error = e.localize(lang));
+                send_str(cx, text.as_str()).await;
+            }
+        }
+        Command::AddAdminRegions { id, regions } => {
+            if let Err(e) = state.0.add_admin_regions(id, regions).await {
+                let text = tr!(lang, "fail.update_user", error = e.localize(lang));
+                send_str(cx, text.as_str()).await;
+            }
+        }
+        Command::DelAdminRegions { id, regions } => {
+            if let Err(e) = state.0.del_admin_regions(id, regions).await {
+                let text = tr!(lang, "fail.update_user", error = e.localize(lang));
+                send_str(cx, text.as_str()).await;
             }
-        } else if let Some(del_user_regions) = c.name("del_user_regions").map(|m| m.as_str()) {
-            let mut split = del_user_regions.split_whitespace();
-            let id = split
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-            let regs = split.next().unwrap();
-            let len = del_user_regions.len()
-                - (regs.as_ptr() as usize - del_user_regions.as_ptr() as usize);
-            let regions = unsafe {
-                std::str::from_utf8_unchecked(std::slice::from_raw_parts(regs.as_ptr(), len))
+        }
+        Command::ListChats(filter) => {
+            listings::chats(&cx.requester, cx.chat_id(), &state.0, &filter, profile).await
+        }
+        Command::AddChat(id) => {
+            let title = match sender::lookup(|| cx.requester.get_chat(id)).await {
+                Ok(chat) => chat.title().map(str::to_string),
+                Err(e) => {
+                    log::warn!("Can't get chat {}. Error: {}", id, e);
+                    None
+                }
             };
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                match extract_regions(regions) {
-                    Regions::Regions(r) | Regions::Country(r) => {
-                        if let Err(e) = state
-                            .0
-                            .del_user_regions(id, r.iter().map(|&s| s.into()).collect())
-                            .await
-                        {
-                            send_str(
-                                &cx,
-                                format!("Не получилось изменить пользователя. Ошибка: {}", e)
-                                    .as_str(),
-                            )
-                            .await;
-                        }
-                    }
-                    Regions::BadRegion { region, matches } => {
-                        let error = Error::BadRegion {
-                            region: region.into(),
-                            matches,
-                        };
-                        send_str(&cx, error.to_string().as_str()).await;
-                    }
+            let r = match CHATS.add(&state.0, Chat::new(id, title, state.0.id)).await {
+                Ok(()) => tr!(lang, "chat.added", id = id),
+                Err(e) => tr!(lang, "fail.add_chat", error = e.localize(lang)),
+            };
+            send_str(cx, r.as_str()).await;
+        }
+        Command::DelChat(id) => {
+            let r = CHATS
+                .delete(&state.0, id)
+                .await
+                .map(|_| tr!(lang, "chat.deleted", id = id))
+                .unwrap_or_else(|e| tr!(lang, "fail.delete_chat", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::ChatSettings { id, setting } => {
+            let r = chats::change_settings(&state.0, id, setting, lang).await;
+            send_str(cx, r.as_str()).await;
+        }
+        Command::ListDb {
+            date,
+            offset,
+            format,
+        } => {
+            let date =
+                date.unwrap_or_else(|| chrono::Utc::now().with_timezone(&offset).date_naive());
+            let start = match date
+                .and_hms_opt(0, 0, 0)
+                .and_then(|d| offset.from_local_datetime(&d).single())
+            {
+                Some(start) => start.with_timezone(&chrono::Utc),
+                None => return send_str(cx, tr!(lang, "listdb.bad_date").as_str()).await,
+            };
+            let end = start + chrono::Duration::days(1);
+            match state
+                .0
+                .list_messages(vec![], vec![], Some(start), Some(end))
+                .await
+            {
+                Ok(messages) if format.is_some() => {
+                    export::send_export(
+                        &cx.requester,
+                        cx.chat_id(),
+                        &messages,
+                        format.unwrap(),
+                        offset,
+                        tr!(lang, "listdb.caption", date = date.format("%d.%m.%y")).as_str(),
+                    )
+                    .await
                 }
-            }
-        } else if let Some(_) = c.name("list_chats").map(|m| m.as_str()) {
-            match state.0.list_chats().await {
-                Ok(chats) => {
-                    if chats.is_empty() {
-                        send_str(&cx, "Пока не добавлено чатов. Используйте /add_chat id, чтобы добавить чат.").await;
+                Ok(messages) => {
+                    let profile = delivery::profile_for(&state.0.client, state.0.id).await;
+                    let mut msgs = BTreeMap::<String, Vec<db_utils::models::Message>>::new();
+                    messages.iter().for_each(|m| {
+                        m.regions
+                            .iter()
+                            .for_each(|r| msgs.entry(r.clone()).or_default().push(m.clone()))
+                    });
+                    for (region, messages) in msgs.iter().filter(|(r, _)| r.as_str() != "РФ") {
+                        send_str(cx, tr!(lang, "region", region = region).as_str()).await;
+                        send_messages(cx, messages.clone(), true, &profile).await;
                     }
-                    for chat in chats {
-                        send_str(&cx, chat.to_string().as_str()).await;
+                    for messages in msgs.get(&"РФ".to_string()) {
+                        send_str(cx, tr!(lang, "region", region = "РФ").as_str()).await;
+                        send_messages(cx, messages.clone(), true, &profile).await;
                     }
                 }
-                Err(e) => send_str(&cx, e.to_string().as_str()).await,
+                Err(e) => send_str(cx, e.localize(lang).as_str()).await,
             }
-        } else if let Some(add_user) = c.name("add_chat").map(|m| m.as_str()) {
-            let id = add_user
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                ALL_CHATS.write().await.insert(id);
-                let r = state
-                    .0
-                    .add_chat(id)
-                    .await
-                    .map(|_| format!("Добавил чат с id {}", id))
-                    .unwrap_or_else(|e| format!("Не получилось добавить чат. Ошибка: {}", e));
-                send_str(&cx, r.as_str()).await;
+        }
+        Command::DelDb(id) => ask_deletion(state, cx, Deletion::Message(id), profile).await,
+        Command::CleanDb(days) => {
+            let before = chrono::Utc::now()
+                .checked_sub_signed(chrono::Duration::days(days as i64))
+                .unwrap_or_else(chrono::Utc::now);
+            ask_deletion(state, cx, Deletion::Before(before), profile).await
+        }
+        Command::Trash => match state.0.list_trash().await {
+            Ok(batches) if batches.is_empty() => {
+                send_str(cx, tr!(lang, "trash.empty").as_str()).await
             }
-        } else if let Some(del_user) = c.name("del_chat").map(|m| m.as_str()) {
-            let id = del_user
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i64>()
-                .unwrap_or_default();
-            if id == 0 {
-                send_str(&cx, "Непонятный id").await;
-            } else {
-                let r = state
-                    .0
-                    .delete_chat(id)
-                    .await
-                    .map(|_| format!("Удалил чат с id {}", id))
-                    .unwrap_or_else(|e| format!("не получилось удалить чат. Ошибка: {}", e));
-                send_str(&cx, r.as_str()).await;
+            Ok(batches) => {
+                let list = batches
+                    .iter()
+                    .map(|b| {
+                        tr!(
+                            lang,
+                            "trash.batch",
+                            batch = b.batch,
+                            count = b.count,
+                            trashed_at =
+                                b.trashed_at.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+                            trashed_by = b.trashed_by,
+                            expires_at =
+                                b.expires_at.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                send_str(cx, list.as_str()).await;
             }
-        } else if let Some(listdb) = c.name("listdb").map(|m| m.as_str()) {
-            let mut it = listdb.split_whitespace();
-            let date = it
-                .nth(1)
-                .map(|d| d.to_string())
-                .unwrap_or(chrono::Local::today().format("%d.%m.%y").to_string());
-            let zone = it.nth(0).unwrap_or("+03:00");
-
-            let date = format!("{} 00:00:00.000 {}", date, zone);
-            let date = chrono::DateTime::parse_from_str(date.as_str(), "%d.%m.%y %H:%M:%S%.3f %:z")
-                .map(|d| d.with_timezone(&chrono::Utc));
-            if let Ok(date) = date {
-                let start = date;
-                let end = match start.checked_add_signed(chrono::Duration::days(1)) {
-                    Some(d) => d,
-                    None => {
-                        log::error!("Error while substracting duration, paniking...",);
-                        panic!();
-                    }
-                };
-                match state
-                    .0
-                    .list_messages(vec![], vec![], Some(start), Some(end))
-                    .await
-                {
-                    Ok(messages) => {
-                        let mut msgs = BTreeMap::<String, Vec<db_utils::models::Message>>::new();
-                        messages.iter().for_each(|m| {
-                            m.regions
-                                .iter()
-                                .for_each(|r| msgs.entry(r.clone()).or_default().push(m.clone()))
-                        });
-                        for (region, messages) in msgs.iter().filter(|(r, _)| r.as_str() != "РФ")
-                        {
-                            send_str(&cx, format!("Регион: {}", region).as_str()).await;
-                            send_messages(&cx, messages.clone(), true).await;
-                        }
-                        for messages in msgs.get(&"РФ".to_string()) {
-                            send_str(&cx, format!("Регион: РФ").as_str()).await;
-                            send_messages(&cx, messages.clone(), true).await;
-                        }
-                    }
-                    Err(e) => send_str(&cx, e.to_string().as_str()).await,
-                }
-            } else {
-                send_str(&cx, "Неправильная дата").await;
-            }
-        } else if let Some(deldb) = c.name("deldb").map(|m| m.as_str()) {
-            let id = deldb
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<ObjectId>();
-            if let Ok(id) = id {
-                let r = state
-                    .0
-                    .delete_message(id)
-                    .await
-                    .map(|_| format!("Удалил сообщение с id {}", id))
-                    .unwrap_or_else(|e| format!("Не получилось удалить сообщение. Ошибка: {}", e));
-                send_str(&cx, r.as_str()).await;
-            } else {
-                send_str(&cx, "Непонятный id").await;
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::Restore(batch) => {
+            let r = state
+                .0
+                .restore(batch)
+                .await
+                .map(|n| tr!(lang, "trash.restored", n = n))
+                .unwrap_or_else(|e| tr!(lang, "fail.restore", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::Audit(filter) => match state.0.audit_log(filter).await {
+            Ok(entries) if entries.is_empty() => {
+                send_str(cx, tr!(lang, "audit.empty").as_str()).await
             }
-        } else if let Some(cleandb) = c.name("cleandb").map(|m| m.as_str()) {
-            let days = cleandb
-                .split_whitespace()
-                .nth(1)
-                .unwrap_or_default()
-                .parse::<i32>();
-            if let Ok(days) = days {
-                let before = chrono::Utc::now()
-                    .checked_sub_signed(chrono::Duration::days(days as i64))
-                    .unwrap_or_else(chrono::Utc::now);
-                let r = state
-                    .0
-                    .delete_messages_period(None, Some(before))
-                    .await
-                    .map(|_| format!("Удалил все сообщения до {}", before))
-                    .unwrap_or_else(|e| format!("Не получилось удалить сообщения. Ошибка: {}", e));
-                send_str(&cx, r.as_str()).await;
-            } else {
-                send_str(&cx, "Непонятное количество дней").await;
+            Ok(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|e| format_audit_entry(e, offset, lang))
+                    .collect::<Vec<_>>();
+                send_paragraphs(&cx.requester, cx.chat_id(), &entries).await;
             }
-        } else if let Some(statdb) = c.name("statdb").map(|m| m.as_str()) {
-            let zone = statdb.split_whitespace().nth(1).unwrap_or("+03:00");
-            let hours = zone
-                .split(':')
-                .nth(0)
-                .unwrap_or("+03")
-                .parse::<i32>()
-                .map_err(|e| log::error!("Error while parsing integer: {}", e))
-                .unwrap();
-            let minuts = zone
-                .split(':')
-                .nth(1)
-                .unwrap_or("00")
-                .parse::<i32>()
-                .map_err(|e| log::error!("Error while parsing integer: {}", e))
-                .unwrap();
-            let secs = hours * 60 * 60 + hours.signum() * minuts * 60;
-            let offset = chrono::FixedOffset::east(secs);
-
-            let msg = match state.0.stat(offset).await {
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::StatDb { days, offset } => {
+            let all_regions = ALL_REGIONS
+                .read()
+                .map_err(|e| log::error!("Can't lock ALL_REGIONS. Error: {}", e))
+                .unwrap()
+                .clone();
+            match state.0.stat(days, offset, &all_regions).await {
                 Ok(stat) => {
-                    format!(
-                        "Количество сообщений ({}).\n\
-                    Сегодня\t- {}\n\
-                    Вчера\t- {}\n\
-                    Позавчера\t- {}\n\
-                    Неделя\t- {}\n\
-                    Месяц\t- {}\n\
-                    Ранее\t- {}\n\
-                    ",
-                        zone,
-                        stat.today,
-                        stat.yesterday,
-                        stat.before_yesterday,
-                        stat.week,
-                        stat.month,
-                        stat.earlier,
-                    )
+                    let paragraphs = format_stat(&stat, days, offset, lang).await;
+                    send_paragraphs(&cx.requester, cx.chat_id(), &paragraphs).await
                 }
                 Err(e) => {
-                    format!("Не получилось выполнить команду. Ошибка: {}", e)
+                    let msg = tr!(lang, "fail.command", error = e.localize(lang));
+                    send_str(cx, msg.as_str()).await
                 }
-            };
-
-            send_str(&cx, msg.as_str()).await;
+            }
         }
-        return next(state);
-    } else if text.unwrap_or_default().starts_with('/') {
-        match state.0.help().await {
-            Ok(s) => send_str(&cx, s).await,
-            Err(e) => send_str(&cx, e.to_string().as_str()).await,
+        Command::Editors { days, offset } => match state.0.editor_stats(days, offset).await {
+            Ok(stats) if stats.is_empty() => {
+                send_str(cx, tr!(lang, "editors.empty", days = days).as_str()).await
+            }
+            Ok(stats) => {
+                let mut paragraphs = vec![tr!(lang, "editors", days = days, offset = offset)];
+                for stat in stats {
+                    let name = match sender::lookup(|| cx.requester.get_chat(stat.editor)).await {
+                        Ok(chat) => chat
+                            .username()
+                            .map(|u| format!("@{}", u))
+                            .or_else(|| chat.first_name().map(str::to_string)),
+                        Err(e) => {
+                            log::warn!("Can't get chat of user {}. Error: {}", stat.editor, e);
+                            None
+                        }
+                    };
+                    paragraphs.push(format_editor_stat(&stat, name, lang));
+                }
+                send_paragraphs(&cx.requester, cx.chat_id(), &paragraphs).await;
+            }
+            Err(e) => {
+                send_str(
+                    cx,
+                    tr!(lang, "fail.command", error = e.localize(lang)).as_str(),
+                )
+                .await
+            }
+        },
+        Command::Charts { days, offset } => match state.0.daily_activity(days, offset).await {
+            Ok(activity) if activity.tags.is_empty() => {
+                send_str(cx, tr!(lang, "charts.empty", days = days).as_str()).await
+            }
+            Ok(activity) => {
+                charts::send_charts(&cx.requester, cx.chat_id(), activity, offset, lang).await
+            }
+            Err(e) => {
+                send_str(
+                    cx,
+                    tr!(lang, "fail.command", error = e.localize(lang)).as_str(),
+                )
+                .await
+            }
+        },
+        Command::Subscribe { regions, tags } => {
+            let r = state
+                .0
+                .subscribe(regions, tags)
+                .await
+                .map(|s| {
+                    tr!(
+                        lang,
+                        "subscription.added",
+                        subscription = format_subscription(&s)
+                    )
+                })
+                .unwrap_or_else(|e| tr!(lang, "fail.subscribe", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
         }
-        return next(state);
-    }
-
-    let group = state.0.get_group().await;
-    match group {
-        Ok(g) if g == UserGroup::Unregistered => {
-            send_str(&cx, text.unwrap_or_default()).await;
-            return next(state);
+        Command::Subscriptions => match state.0.list_subscriptions().await {
+            Ok(subscriptions) if subscriptions.is_empty() => {
+                send_str(cx, tr!(lang, "subscriptions.empty").as_str()).await
+            }
+            Ok(subscriptions) => {
+                let list = subscriptions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| format!("{}. {}", i + 1, format_subscription(s)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                send_str(cx, list.as_str()).await;
+            }
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::Unsubscribe(n) => {
+            let r = state
+                .0
+                .unsubscribe(n)
+                .await
+                .map(|_| tr!(lang, "subscription.deleted", n = n))
+                .unwrap_or_else(|e| tr!(lang, "fail.unsubscribe", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
         }
-        Err(e) => {
-            send_str(
-                &cx,
-                format!("Не получилось выполнить команду. Ошибка: {}", e).as_str(),
-            )
-            .await;
-            return next(state);
+        Command::Pause(n) | Command::Resume(n) => {
+            let paused = matches!(command, Command::Pause(_));
+            let r = state
+                .0
+                .set_subscriptions_paused(n, paused)
+                .await
+                .map(|_| match (n, paused) {
+                    (Some(n), true) => tr!(lang, "subscription.paused", n = n),
+                    (Some(n), false) => tr!(lang, "subscription.resumed", n = n),
+                    (None, true) => tr!(lang, "subscriptions.paused"),
+                    (None, false) => tr!(lang, "subscriptions.resumed"),
+                })
+                .unwrap_or_else(|e| {
+                    tr!(lang, "fail.update_subscription", error = e.localize(lang))
+                });
+            send_str(cx, r.as_str()).await;
         }
-        _ => {}
-    }
-
-    enum _Message {
-        Message(BTreeMap<String, Vec<db_utils::models::Message>>),
-        Error(String),
-    }
-    let messages = match handle_private(&state, text.unwrap_or_default()).await {
-        Ok(messages) => _Message::Message(messages),
-        Err(e) => _Message::Error(e.to_string()),
-    };
-    match messages {
-        _Message::Message(messages) => {
-            for messages in messages.get(&"РФ".to_string()) {
-                send_str(&cx, format!("Регион: РФ").as_str()).await;
-                send_messages(&cx, messages.clone(), false).await;
+        Command::Quiet { n, quiet_hours } => {
+            let r = state
+                .0
+                .set_quiet_hours(n, quiet_hours)
+                .await
+                .map(|_| match quiet_hours {
+                    Some(_) => tr!(lang, "quiet.set", n = n),
+                    None => tr!(lang, "quiet.off", n = n),
+                })
+                .unwrap_or_else(|e| {
+                    tr!(lang, "fail.update_subscription", error = e.localize(lang))
+                });
+            send_str(cx, r.as_str()).await;
+        }
+        Command::AddDigest {
+            schedule,
+            regions,
+            tags,
+        } => {
+            let r = state
+                .0
+                .add_digest(regions, tags, schedule)
+                .await
+                .map(|d| tr!(lang, "digest.added", digest = format_digest(&d, lang)))
+                .unwrap_or_else(|e| tr!(lang, "fail.add_digest", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::Digests => match state.0.list_digests().await {
+            Ok(digests) if digests.is_empty() => {
+                send_str(cx, tr!(lang, "digests.empty").as_str()).await
             }
-            for (region, messages) in messages.iter().filter(|(r, _)| r.as_str() != "РФ") {
-                send_str(&cx, format!("Регион: {}", region).as_str()).await;
-                send_messages(&cx, messages.clone(), false).await;
+            Ok(digests) => {
+                let list = digests
+                    .iter()
+                    .enumerate()
+                    .map(|(i, d)| format!("{}. {}", i + 1, format_digest(d, lang)))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                send_str(cx, list.as_str()).await;
             }
-            while let Err(teloxide::RequestError::RetryAfter(secs)) =
-                cx.reply_to("🏁 Результаты по запросу").await
-            {
-                tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await;
+            Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+        },
+        Command::DelDigest(n) => {
+            let r = state
+                .0
+                .delete_digest(n)
+                .await
+                .map(|_| tr!(lang, "digest.deleted", n = n))
+                .unwrap_or_else(|e| tr!(lang, "fail.delete_digest", error = e.localize(lang)));
+            send_str(cx, r.as_str()).await;
+        }
+        Command::Delivery(setting) => {
+            let r = match setting {
+                DeliverySetting::Show => state.0.delivery().await.map(|d| match d {
+                    Some(d) => tr!(
+                        lang,
+                        "delivery.current",
+                        delivery = format_delivery(d, lang)
+                    ),
+                    None => tr!(
+                        lang,
+                        "delivery.default",
+                        delivery = format_delivery(delivery::default_delivery(), lang)
+                    ),
+                }),
+                DeliverySetting::Default => state
+                    .0
+                    .set_delivery(None)
+                    .await
+                    .map(|_| tr!(lang, "delivery.reset")),
+                DeliverySetting::Set(delivery) => {
+                    state.0.set_delivery(Some(delivery)).await.map(|_| {
+                        tr!(
+                            lang,
+                            "delivery.current",
+                            delivery = format_delivery(delivery, lang)
+                        )
+                    })
+                }
+            };
+            send_str(cx, r.unwrap_or_else(|e| e.localize(lang)).as_str()).await;
+        }
+        Command::Profile(ProfileSetting::Show) => {
+            send_str(cx, format_profile(profile).as_str()).await
+        }
+        Command::Profile(setting) => {
+            let mut updated = profile.clone();
+            match setting {
+                ProfileSetting::Show => {}
+                ProfileSetting::Timezone(tz) => updated.timezone = Some(tz.local_minus_utc()),
+                ProfileSetting::Language(language) => updated.language = language,
+                ProfileSetting::Regions(regions) => updated.default_regions = regions,
             }
+            let r = match state.0.set_profile(updated.clone()).await {
+                Ok(()) => {
+                    if updated.language != lang {
+                        let group = state.0.get_group().await.ok();
+                        let menu_lang = updated.language;
+                        commands::set_menu(&cx.requester, state.0.id, group.as_ref(), menu_lang)
+                            .await;
+                    }
+                    format_profile(&updated)
+                }
+                Err(e) => tr!(lang, "fail.update_profile", error = e.localize(lang)),
+            };
+            send_str(cx, r.as_str()).await;
         }
-        _Message::Error(string) => {
-            while let Err(teloxide::RequestError::RetryAfter(secs)) =
-                cx.reply_to(string.as_str()).await
-            {
-                tokio::time::sleep(std::time::Duration::from_secs(secs as u64)).await;
+        Command::Count(query) => {
+            let r = match parse_query(state.0.id, query.as_str(), &profile.default_regions) {
+                Ok(filter) => db_utils::count_messages(&state.0.client, &filter)
+                    .await
+                    .map(|count| (filter, count))
+                    .map_err(Error::from),
+                Err(e) => Err(e),
+            };
+            match r {
+                Ok((filter, count)) => {
+                    let chat_id = cx.chat_id();
+                    send_preview(&cx.requester, chat_id, state.0.id, filter, count, lang).await
+                }
+                Err(e) => send_str(cx, e.localize(lang).as_str()).await,
+            }
+        }
+        Command::Export { format, query } => {
+            let r = match parse_query(state.0.id, query.as_str(), &profile.default_regions) {
+                Ok(filter) => export_messages(&state.0, filter).await,
+                Err(e) => Err(e),
+            };
+            match r {
+                Ok(mut messages) => {
+                    messages.sort_by_key(|m| m.timestamp);
+                    export::send_export(
+                        &cx.requester,
+                        cx.chat_id(),
+                        &messages,
+                        format,
+                        offset,
+                        tr!(lang, "export.caption", query = query).as_str(),
+                    )
+                    .await
+                }
+                Err(e) => send_str(cx, e.localize(lang).as_str()).await,
             }
         }
     }
+}
 
-    next(state)
+/// Asks for a confirmation instead of running the query when it matches more than
+/// `CONFIRM_THRESHOLD` messages.
+async fn preview_or_query(
+    user: &db_utils::user::User,
+    text: &str,
+    profile: &Profile,
+) -> Result<_Message, Error> {
+    let filter = parse_query(user.id, text, &profile.default_regions)?;
+    let count = db_utils::count_messages(&user.client, &filter).await?;
+    if count.total > *CONFIRM_THRESHOLD {
+        return Ok(_Message::Preview(filter, count));
+    }
+    Ok(_Message::Message(handle_private(user, filter).await?))
 }
 
-async fn handle_private(
-    state: &Private,
+enum _Message {
+    Message(BTreeMap<String, Vec<db_utils::models::Message>>),
+    Preview(
+        db_utils::models::MessageFilter,
+        db_utils::models::MessageCount,
+    ),
+    Error(String),
+}
+
+/// A deletion waiting for the admin to confirm it.
+struct PendingDeletion {
+    token: ObjectId,
+    deletion: Deletion,
+}
+
+/// A query waiting for the user to confirm, narrow or cancel it.
+struct PendingQuery {
+    token: ObjectId,
+    filter: db_utils::models::MessageFilter,
+    regions: Vec<String>,
+}
+
+/// Queries without regions use `default_regions` from the user's profile.
+fn parse_query(
+    user_id: i64,
     text: &str,
-) -> Result<BTreeMap<String, Vec<db_utils::models::Message>>, Error> {
+    default_regions: &[String],
+) -> Result<db_utils::models::MessageFilter, Error> {
     let (regions, since, duration, tags) = match GET_REGEX.captures(text) {
         Some(c) => (
             c.name("regions").map(|r| r.as_str()),
@@ -466,7 +735,10 @@ async fn handle_private(
                 });
             }
         },
-        _ => return Err(Error::NoRegions),
+        None if !default_regions.is_empty() && GET_REGEX.is_match(text) => {
+            default_regions.iter().map(String::as_str).collect()
+        }
+        None => return Err(Error::NoRegions),
     };
 
     let since = match since {
@@ -491,84 +763,525 @@ async fn handle_private(
         None => vec![],
     };
 
-    let filter = db_utils::models::MessageFilter {
-        user_id: state.0.id,
+    Ok(db_utils::models::MessageFilter {
+        user_id,
         period,
         regions: regions.iter().map(|r| r.to_string()).collect(),
         tags: tags.iter().map(|t| t.to_string()).collect(),
-    };
+    })
+}
 
-    let messages = db_utils::get_messages(&state.0.client, filter).await?;
+async fn query_messages(
+    user: &db_utils::user::User,
+    filter: db_utils::models::MessageFilter,
+) -> Result<Vec<db_utils::models::Message>, Error> {
+    let messages = db_utils::get_messages(&user.client, filter.clone()).await?;
+    non_empty(messages, &filter)
+}
 
+/// Like [`query_messages`], but leaves the user's latest requests as they are.
+async fn export_messages(
+    user: &db_utils::user::User,
+    filter: db_utils::models::MessageFilter,
+) -> Result<Vec<db_utils::models::Message>, Error> {
+    let messages = db_utils::find_messages(&user.client, &filter).await?;
+    non_empty(messages, &filter)
+}
+
+fn non_empty(
+    messages: HashSet<db_utils::models::Message>,
+    filter: &db_utils::models::MessageFilter,
+) -> Result<Vec<db_utils::models::Message>, Error> {
     if messages.is_empty() {
         return Err(Error::NoMessages {
-            regions: regions.iter().map(|&i| i.into()).collect(),
-            period,
-            tags: tags.iter().map(|&i| i.into()).collect(),
+            regions: filter.regions.iter().map(|i| i.into()).collect(),
+            period: filter.period,
+            tags: filter.tags.iter().map(|i| i.into()).collect(),
         });
     }
 
-    let mut res = BTreeMap::<String, Vec<db_utils::models::Message>>::new();
-    messages.iter().for_each(|m| {
-        m.regions
-            .iter()
-            .for_each(|r| res.entry(r.clone()).or_default().push(m.clone()))
-    });
+    Ok(messages.into_iter().collect())
+}
 
-    struct Tag(&'static str);
-    impl std::cmp::Ord for Tag {
-        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-            let order = ['ч', 'п', 'у', 'с'];
-            let o = other.0.chars().next().unwrap_or('ч');
-            let s = self.0.chars().next().unwrap_or('ч');
+async fn handle_private(
+    user: &db_utils::user::User,
+    filter: db_utils::models::MessageFilter,
+) -> Result<BTreeMap<String, Vec<db_utils::models::Message>>, Error> {
+    Ok(group_by_region(query_messages(user, filter).await?))
+}
 
-            let s = order
-                .iter()
-                .enumerate()
-                .find(|(_, &c)| c == s)
-                .map(|(w, _)| w)
-                .unwrap_or_default();
+fn format_count(count: &db_utils::models::MessageCount, lang: Language) -> String {
+    let mut s = tr!(lang, "count.total", total = count.total);
+    if !count.regions.is_empty() {
+        s += &format!("\n{}", tr!(lang, "count.by_region"));
+        for (region, n) in &count.regions {
+            s += format!("\n  {} — {}", region, n).as_str();
+        }
+    }
+    if !count.tags.is_empty() {
+        s += &format!("\n{}", tr!(lang, "count.by_tag"));
+        for (tag, n) in &count.tags {
+            s += format!("\n  {} — {}", tag, n).as_str();
+        }
+    }
+    s
+}
 
-            let o = order
-                .iter()
-                .enumerate()
-                .find(|(_, &c)| c == o)
-                .map(|(w, _)| w)
-                .unwrap_or_default();
+/// Sends the breakdown of a query with buttons to run it, run it for a single region or cancel it.
+/// The buttons are removed if none is pressed within `QUERY_CONFIRM_TIMEOUT` seconds.
+async fn send_preview(
+    bot: &AutoSend<Bot>,
+    chat_id: i64,
+    user_id: i64,
+    filter: db_utils::models::MessageFilter,
+    count: db_utils::models::MessageCount,
+    lang: Language,
+) {
+    if count.total == 0 {
+        send_text(bot, chat_id, tr!(lang, "error.no_messages").as_str()).await;
+        return;
+    }
 
-            s.cmp(&o)
+    let regions = count
+        .regions
+        .iter()
+        .take(PREVIEW_REGION_BUTTONS)
+        .map(|(r, _)| r.clone())
+        .collect::<Vec<_>>();
+    let token = ObjectId::new();
+    let data = |action: &str| format!("{}:{}:{}", QUERY_PREFIX, token.to_hex(), action);
+    let mut keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(tr!(lang, "query.show"), data("show")),
+        InlineKeyboardButton::callback(tr!(lang, "button.cancel"), data("cancel")),
+    ]);
+    if regions.len() > 1 {
+        let buttons = count
+            .regions
+            .iter()
+            .take(PREVIEW_REGION_BUTTONS)
+            .enumerate()
+            .map(|(i, (region, n))| {
+                InlineKeyboardButton::callback(
+                    tr!(lang, "query.only", region = region, n = n),
+                    data(&format!("region:{}", i)),
+                )
+            })
+            .collect::<Vec<_>>();
+        for row in buttons.chunks(2) {
+            keyboard = keyboard.append_row(row.to_vec());
         }
     }
 
-    impl std::cmp::Eq for Tag {}
+    PENDING_QUERIES.lock().await.insert(
+        user_id,
+        PendingQuery {
+            token,
+            filter,
+            regions,
+        },
+    );
+
+    let text = tr!(lang, "query.confirm", count = format_count(&count, lang));
+    let message = match sender::send(chat_id, Priority::Interactive, || {
+        bot.send_message(chat_id, text.as_str())
+            .reply_markup(keyboard.clone())
+    })
+    .await
+    {
+        Ok(m) => m,
+        Err(e) => return log::error!("Error while sending a string: {}", e),
+    };
 
-    impl std::cmp::PartialOrd for Tag {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            Some(self.cmp(other))
+    let bot = bot.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(*QUERY_CONFIRM_TIMEOUT)).await;
+        let mut pending = PENDING_QUERIES.lock().await;
+        if pending.get(&user_id).map(|p| p.token) == Some(token) {
+            pending.remove(&user_id);
+            drop(pending);
+            delivery::remove_keyboard(&bot, &message).await;
         }
+    });
+}
+
+/// Handles the buttons of [`send_preview`]: `query:<token>:show`, `query:<token>:cancel` and
+/// `query:<token>:region:<n>`. Buttons of an older preview are stale.
+pub async fn confirm_query(
+    cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>,
+    client: &Arc<Client>,
+    data: &str,
+) {
+    let user_id = cx.update.from.id;
+    let message = match &cx.update.message {
+        Some(m) => m,
+        None => return answer_callback(cx, None).await,
+    };
+    let lang = delivery::profile_for(client, user_id).await.language;
+    let mut split = data.split(':').skip(1);
+    let token = split.next().and_then(|t| t.parse::<ObjectId>().ok());
+    let pending = {
+        let mut pending = PENDING_QUERIES.lock().await;
+        match pending.get(&user_id) {
+            Some(p) if Some(p.token) == token => pending.remove(&user_id),
+            _ => None,
+        }
+    };
+    let pending = match pending {
+        Some(p) => p,
+        None => {
+            delivery::remove_keyboard(&cx.requester, message).await;
+            return answer_callback(cx, Some(&tr!(lang, "query.stale"))).await;
+        }
+    };
+    delivery::remove_keyboard(&cx.requester, message).await;
+
+    let mut filter = pending.filter;
+    match (
+        split.next(),
+        split.next().and_then(|i| i.parse::<usize>().ok()),
+    ) {
+        (Some("show"), _) => {}
+        (Some("region"), Some(i)) if i < pending.regions.len() => {
+            filter.regions = vec![pending.regions[i].clone()];
+        }
+        _ => return answer_callback(cx, Some(&tr!(lang, "cancelled"))).await,
     }
+    answer_callback(cx, None).await;
 
-    impl std::cmp::PartialEq for Tag {
-        fn eq(&self, other: &Self) -> bool {
-            self == other
+    let user = db_utils::user::User::new(user_id, Arc::clone(client));
+    match handle_private(&user, filter).await {
+        Ok(messages) => {
+            delivery::deliver(&cx.requester, client, message.chat.id, user_id, messages).await
         }
+        Err(e) => send_text(&cx.requester, message.chat.id, e.localize(lang).as_str()).await,
     }
-    res.iter_mut().for_each(|(_, messages)| {
-        messages.sort_by(|a, b| {
-            use std::cmp::Ordering;
-            let date = a.timestamp.cmp(&b.timestamp);
-            let tag = a
-                .tags
-                .get(0)
-                .cloned()
-                .unwrap_or_default()
-                .cmp(&b.tags.get(0).cloned().unwrap_or_default());
-            match (tag, date) {
-                (Ordering::Equal, d) => d,
-                (t, _) => t,
+}
+
+/// Shows what a deletion would remove and waits `DELETE_CONFIRM_TIMEOUT` seconds for a confirmation.
+async fn ask_deletion(
+    state: &Private,
+    cx: &TransitionIn<AutoSend<Bot>>,
+    deletion: Deletion,
+    profile: &Profile,
+) {
+    let lang = profile.language;
+    let preview = match state.0.preview_deletion(deletion).await {
+        Ok(p) => p,
+        Err(e) => return send_str(cx, e.localize(lang).as_str()).await,
+    };
+    let (first, last) = match (preview.first, preview.last) {
+        (Some(first), Some(last)) if preview.count > 0 => (first, last),
+        _ => return send_str(cx, tr!(lang, "delete.nothing").as_str()).await,
+    };
+
+    let offset = profile.offset();
+    let text = tr!(
+        lang,
+        "delete.confirm",
+        count = preview.count,
+        first = first.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+        last = last.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+        timeout = *DELETE_CONFIRM_TIMEOUT,
+    );
+    let token = ObjectId::new();
+    let keyboard = InlineKeyboardMarkup::default().append_row(vec![
+        InlineKeyboardButton::callback(
+            tr!(lang, "delete.button"),
+            format!("{}:{}:yes", DELETE_PREFIX, token.to_hex()),
+        ),
+        InlineKeyboardButton::callback(
+            tr!(lang, "button.cancel"),
+            format!("{}:{}:no", DELETE_PREFIX, token.to_hex()),
+        ),
+    ]);
+    PENDING_DELETIONS
+        .lock()
+        .await
+        .insert(state.0.id, PendingDeletion { token, deletion });
+
+    let chat_id = cx.chat_id();
+    let message = match sender::send(chat_id, Priority::Interactive, || {
+        cx.requester
+            .send_message(chat_id, text.as_str())
+            .reply_markup(keyboard.clone())
+    })
+    .await
+    {
+        Ok(m) => m,
+        Err(e) => return log::error!("Error while sending a string: {}", e),
+    };
+
+    let bot = cx.requester.clone();
+    let user_id = state.0.id;
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(*DELETE_CONFIRM_TIMEOUT)).await;
+        let mut pending = PENDING_DELETIONS.lock().await;
+        if pending.get(&user_id).map(|p| p.token) == Some(token) {
+            pending.remove(&user_id);
+            drop(pending);
+            let text = tr!(lang, "delete.expired");
+            if let Err(e) = sender::send(chat_id, Priority::Interactive, || {
+                bot.edit_message_text(chat_id, message.id, text.as_str())
+            })
+            .await
+            {
+                log::error!("Error while editing a confirmation: {}", e);
             }
-        })
+        }
     });
+}
+
+/// Handles the buttons of [`ask_deletion`]: `delete:<token>:yes` and `delete:<token>:no`.
+pub async fn confirm_deletion(
+    cx: &UpdateWithCx<AutoSend<Bot>, CallbackQuery>,
+    client: &Arc<Client>,
+    data: &str,
+) {
+    let user_id = cx.update.from.id;
+    let message = match &cx.update.message {
+        Some(m) => m,
+        None => return answer_callback(cx, None).await,
+    };
+    let mut split = data.split(':').skip(1);
+    let token = split.next().and_then(|t| t.parse::<ObjectId>().ok());
+    let confirmed = split.next() == Some("yes");
+    let profile = delivery::profile_for(client, user_id).await;
+    let lang = profile.language;
+
+    let pending = {
+        let mut pending = PENDING_DELETIONS.lock().await;
+        match pending.get(&user_id) {
+            Some(p) if Some(p.token) == token => pending.remove(&user_id),
+            _ => None,
+        }
+    };
+    let pending = match pending {
+        Some(p) => p,
+        None => return answer_callback(cx, Some(&tr!(lang, "delete.stale"))).await,
+    };
+    delivery::remove_keyboard(&cx.requester, message).await;
+    if !confirmed {
+        return answer_callback(cx, Some(&tr!(lang, "cancelled"))).await;
+    }
+    answer_callback(cx, None).await;
+
+    let offset = profile.offset();
+    let user = db_utils::user::User::new(user_id, Arc::clone(client));
+    let r = user
+        .delete_messages(pending.deletion)
+        .await
+        .map(|b| {
+            tr!(
+                lang,
+                "delete.done",
+                count = b.count,
+                expires_at = b.expires_at.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+                batch = b.batch
+            )
+        })
+        .unwrap_or_else(|e| tr!(lang, "fail.delete_messages", error = e.localize(lang)));
+    send_text(&cx.requester, message.chat.id, r.as_str()).await;
+}
 
-    Ok(res)
+fn format_invite(
+    invite: &Invite,
+    bot_name: &str,
+    offset: chrono::FixedOffset,
+    lang: Language,
+) -> String {
+    let mut s = tr!(
+        lang,
+        "invite.created",
+        bot = bot_name,
+        token = invite.token,
+        group = invite.group,
+        regions = invite.regions.join(", "),
+        uses = invite.uses_left
+    );
+    if let Some(expires_at) = invite.expires_at {
+        let expires_at = expires_at.with_timezone(&offset).format("%d.%m.%y %H:%M");
+        s += &format!("\n{}", tr!(lang, "invite.expires", expires_at = expires_at));
+    }
+    s
+}
+
+fn format_audit_entry(entry: &AuditEntry, offset: chrono::FixedOffset, lang: Language) -> String {
+    let mut s = format!(
+        "{} · {} · {}",
+        entry
+            .timestamp
+            .with_timezone(&offset)
+            .format("%d.%m.%y %H:%M:%S"),
+        entry.actor,
+        entry.action,
+    );
+    if let Some(target) = entry.target {
+        s += &format!(" → {}", target);
+    }
+    if !entry.args.is_empty() {
+        s += &format!("\n{}", tr!(lang, "audit.args", args = entry.args));
+    }
+    if let Some(before) = &entry.before {
+        s += &format!("\n{}", tr!(lang, "audit.before", before = before));
+    }
+    if let Some(after) = &entry.after {
+        s += &format!("\n{}", tr!(lang, "audit.after", after = after));
+    }
+    s
+}
+
+/// Totals for fixed periods, then the top regions, tags and chats of the last `days` days
+/// and the regions without messages.
+async fn format_stat(
+    stat: &DbStat,
+    days: u32,
+    offset: chrono::FixedOffset,
+    lang: Language,
+) -> Vec<String> {
+    let top = |title: String, counts: Vec<(String, usize)>| {
+        let mut lines = vec![title];
+        lines.extend(
+            counts
+                .iter()
+                .take(*STAT_TOP)
+                .map(|(name, n)| format!("{} — {}", name, n)),
+        );
+        if counts.len() > *STAT_TOP {
+            lines.push(tr!(lang, "stat.more", n = counts.len() - *STAT_TOP));
+        }
+        lines.join("\n")
+    };
+
+    let mut chats = Vec::with_capacity(stat.chats.len());
+    for &(id, n) in &stat.chats {
+        let title = CHATS.title(id).await.unwrap_or_else(|| id.to_string());
+        chats.push((title, n));
+    }
+    let since = stat.since.with_timezone(&offset).format("%d.%m.%y");
+    let mut paragraphs = vec![
+        tr!(
+            lang,
+            "stat",
+            offset = offset,
+            today = stat.today,
+            yesterday = stat.yesterday,
+            before_yesterday = stat.before_yesterday,
+            week = stat.week,
+            month = stat.month,
+            earlier = stat.earlier,
+        ),
+        tr!(
+            lang,
+            "stat.period",
+            days = days,
+            since = since,
+            total = stat.total
+        ),
+        top(tr!(lang, "stat.regions"), stat.regions.clone()),
+        top(tr!(lang, "stat.tags"), stat.tags.clone()),
+        top(tr!(lang, "stat.chats"), chats),
+    ];
+    if !stat.idle_regions.is_empty() {
+        let regions = stat.idle_regions.join(", ");
+        paragraphs.push(tr!(lang, "stat.idle", regions = regions));
+    }
+    paragraphs
+}
+
+fn format_editor_stat(stat: &EditorStat, name: Option<String>, lang: Language) -> String {
+    let days = stat
+        .days
+        .iter()
+        .map(|(day, n)| format!("{} — {}", day.format("%d.%m"), n))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let regions = stat
+        .regions
+        .iter()
+        .map(|(region, n)| format!("{} — {}", region, n))
+        .collect::<Vec<_>>()
+        .join(", ");
+    tr!(
+        lang,
+        "editors.editor",
+        name = name.unwrap_or_else(|| stat.editor.to_string()),
+        id = stat.editor,
+        total = stat.total,
+        days = days,
+        regions = regions
+    )
+}
+
+fn format_profile(profile: &Profile) -> String {
+    let lang = profile.language;
+    let regions = match profile.default_regions.is_empty() {
+        true => tr!(lang, "profile.no_regions"),
+        false => profile.default_regions.join(", "),
+    };
+    let delivery = match profile.delivery {
+        Some(d) => format_delivery(d, lang),
+        None => tr!(
+            lang,
+            "profile.default_delivery",
+            delivery = format_delivery(delivery::default_delivery(), lang)
+        ),
+    };
+    tr!(
+        lang,
+        "profile",
+        timezone = profile.offset(),
+        language = lang,
+        regions = regions,
+        delivery = delivery
+    )
+}
+
+fn format_delivery(delivery: db_utils::models::Delivery, lang: Language) -> String {
+    match delivery {
+        db_utils::models::Delivery {
+            mode: db_utils::models::DeliveryMode::Forward,
+            ..
+        } => tr!(lang, "delivery.forward"),
+        db_utils::models::Delivery {
+            attribution: true, ..
+        } => tr!(lang, "delivery.copy_footer"),
+        _ => tr!(lang, "delivery.copy"),
+    }
+}
+
+fn format_digest(digest: &db_utils::models::Digest, lang: Language) -> String {
+    let mut s = format!(
+        "{} — {}",
+        scheduler::format_schedule(&digest.schedule, lang),
+        digest.regions.join(", ")
+    );
+    if !digest.tags.is_empty() {
+        s += format!(" [{}]", digest.tags.join(", ")).as_str();
+    }
+    s
+}
+
+fn format_subscription(subscription: &db_utils::models::Subscription) -> String {
+    let mut s = subscription.regions.join(", ");
+    if !subscription.tags.is_empty() {
+        s += format!(" [{}]", subscription.tags.join(", ")).as_str();
+    }
+    if let Some(q) = subscription.quiet_hours {
+        let offset = chrono::FixedOffset::east_opt(q.offset)
+            .map(|o| o.to_string())
+            .unwrap_or_default();
+        s += format!(
+            " 🌙 {:02}:{:02}–{:02}:{:02} ({})",
+            q.from / 60,
+            q.from % 60,
+            q.to / 60,
+            q.to % 60,
+            offset
+        )
+        .as_str();
+    }
+    if subscription.paused {
+        s += " ⏸";
+    }
+    s
 }
@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use bson::oid::ObjectId;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Offset, TimeZone, Utc};
+use mongodb::Client;
+use teloxide::prelude::*;
+
+use crate::common::{group_by_region, send_grouped, send_text};
+use crate::db_utils::{
+    self,
+    models::{Digest, DigestRun, Schedule},
+};
+use crate::delivery::profile_for;
+use crate::i18n::{tr, Language};
+use strum::IntoEnumIterator;
+
+lazy_static::lazy_static! {
+    /// Digests being sent, so a long run isn't started again on the next tick.
+    static ref RUNNING: Mutex<HashSet<ObjectId>> = Mutex::new(HashSet::new());
+}
+
+/// Short weekday names in `lang`, Monday first.
+fn weekdays(lang: Language) -> Vec<String> {
+    tr!(lang, "schedule.weekdays")
+        .split(',')
+        .map(String::from)
+        .collect()
+}
+
+/// Runs due digests once a minute, each in its own task. A digest whose runs were missed
+/// while the bot was down is run once, covering the whole period since its last run.
+pub async fn run(bot: AutoSend<Bot>, client: Arc<Client>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        let digests = match db_utils::list_digests(&client, None).await {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("Can't access digests. Error: {}", e);
+                continue;
+            }
+        };
+        let now = Utc::now();
+        for digest in digests {
+            let scheduled_for = match due_time(&digest.schedule, digest.last_run, now) {
+                Some(t) => t,
+                None => continue,
+            };
+            if !RUNNING.lock().unwrap().insert(digest._id) {
+                continue;
+            }
+            let (bot, client) = (bot.clone(), Arc::clone(&client));
+            tokio::spawn(async move {
+                let id = digest._id;
+                run_digest(&bot, &client, digest, scheduled_for, now).await;
+                RUNNING.lock().unwrap().remove(&id);
+            });
+        }
+    }
+}
+
+/// Sends the digest's messages saved since its last run. The run is recorded, and
+/// `last_run` moved, only after everything was sent, so an interrupted run is repeated.
+async fn run_digest(
+    bot: &AutoSend<Bot>,
+    client: &Client,
+    digest: Digest,
+    scheduled_for: DateTime<Utc>,
+    now: DateTime<Utc>,
+) {
+    let allowed = match db_utils::get_allowed_regions(client, digest.user_id).await {
+        Ok(a) => a,
+        Err(e) => {
+            return log::error!(
+                "Can't access allowed regions of {}. Error: {}",
+                digest.user_id,
+                e
+            )
+        }
+    };
+    let messages = match db_utils::get_digest_messages(
+        client,
+        &digest,
+        &allowed,
+        digest.last_run,
+        now,
+    )
+    .await
+    {
+        Ok(m) => m,
+        Err(e) => return log::error!("Can't access digest {}. Error: {}", digest._id, e),
+    };
+
+    log::info!(
+        "Digest {} for user {}: {} messages",
+        digest._id,
+        digest.user_id,
+        messages.len()
+    );
+
+    let profile = profile_for(client, digest.user_id).await;
+    let lang = profile.language;
+    let offset = FixedOffset::east_opt(digest.schedule.offset).unwrap_or_else(|| Utc.fix());
+    let header = tr!(
+        lang,
+        "digest.header",
+        regions = digest.regions.join(", "),
+        from = digest
+            .last_run
+            .with_timezone(&offset)
+            .format("%d.%m.%y %H:%M"),
+        to = now.with_timezone(&offset).format("%d.%m.%y %H:%M"),
+    );
+    let n_messages = messages.len();
+    if messages.is_empty() {
+        let text = format!("{}\n{}", header, tr!(lang, "digest.empty"));
+        send_text(bot, digest.user_id, text.as_str()).await;
+    } else {
+        send_text(bot, digest.user_id, header.as_str()).await;
+        send_grouped(bot, digest.user_id, &group_by_region(messages), &profile).await;
+        send_text(bot, digest.user_id, tr!(lang, "digest.end").as_str()).await;
+    }
+
+    let run = DigestRun {
+        digest_id: digest._id,
+        user_id: digest.user_id,
+        scheduled_for,
+        ran_at: now,
+        caught_up: now - scheduled_for > Duration::minutes(2),
+        n_messages,
+    };
+    if let Err(e) = db_utils::insert_digest_run(client, &run).await {
+        log::error!("Can't save run of digest {}. Error: {}", digest._id, e);
+    }
+}
+
+/// The latest scheduled instant in `(after, now]`, if any.
+fn due_time(
+    schedule: &Schedule,
+    after: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let offset = FixedOffset::east_opt(schedule.offset)?;
+    let today = now.with_timezone(&offset).date_naive();
+    let mut times = schedule.times.clone();
+    times.sort_unstable_by(|a, b| b.cmp(a));
+
+    for days_back in 0..8 {
+        let date = today - Duration::days(days_back);
+        if !schedule.weekdays.is_empty()
+            && !schedule
+                .weekdays
+                .contains(&date.weekday().num_days_from_monday())
+        {
+            continue;
+        }
+        for &time in &times {
+            let at = offset
+                .from_local_datetime(&date.and_hms_opt(time / 60, time % 60, 0)?)
+                .single()?
+                .with_timezone(&Utc);
+            if at <= now {
+                return (at > after).then_some(at);
+            }
+        }
+    }
+    None
+}
+
+/// Parses a schedule like `9:00,18:00` with optional days like `пн-пт` or `sat,sun`.
+pub fn parse_schedule(times: &str, days: Option<&str>, offset: FixedOffset) -> Option<Schedule> {
+    let times = times
+        .split(',')
+        .map(|t| {
+            let (h, m) = t.split_once(':')?;
+            let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+            (h < 24 && m < 60).then(|| h * 60 + m)
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let weekdays = parse_weekdays(days.unwrap_or_default())?;
+
+    Some(Schedule {
+        times,
+        weekdays,
+        offset: offset.local_minus_utc(),
+    })
+}
+
+/// Parses days like `пн-пт` or `sat,sun` in any language into weekday numbers, Monday being 0.
+pub fn parse_weekdays(days: &str) -> Option<Vec<u32>> {
+    let names = Language::iter().map(weekdays).collect::<Vec<_>>();
+    let day = |d: &str| {
+        names
+            .iter()
+            .find_map(|w| w.iter().position(|w| w == d))
+            .map(|d| d as u32)
+    };
+    let mut weekdays = Vec::new();
+    for part in days.split(',').filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (day(from)?, day(to)?);
+                let mut d = from;
+                loop {
+                    weekdays.push(d);
+                    if d == to {
+                        break;
+                    }
+                    d = (d + 1) % 7;
+                }
+            }
+            None => weekdays.push(day(part)?),
+        }
+    }
+    weekdays.sort_unstable();
+    weekdays.dedup();
+    Some(weekdays)
+}
+
+pub fn format_schedule(schedule: &Schedule, lang: Language) -> String {
+    let mut s = schedule
+        .times
+        .iter()
+        .map(|t| format!("{:02}:{:02}", t / 60, t % 60))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if !schedule.weekdays.is_empty() {
+        let names = weekdays(lang);
+        let days = schedule
+            .weekdays
+            .iter()
+            .filter_map(|&d| names.get(d as usize).cloned())
+            .collect::<Vec<_>>();
+        s += format!(" ({})", days.join(", ")).as_str();
+    }
+    if let Some(offset) = FixedOffset::east_opt(schedule.offset) {
+        s += format!(" {}", offset).as_str();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    /// A schedule in UTC+3.
+    fn schedule(times: &[u32], weekdays: &[u32]) -> Schedule {
+        Schedule {
+            times: times.to_vec(),
+            weekdays: weekdays.to_vec(),
+            offset: 3 * 3600,
+        }
+    }
+
+    #[test]
+    fn due_time_is_the_latest_passed_time() {
+        let s = schedule(&[18 * 60, 9 * 60], &[]);
+        let now = utc("2026-10-19T16:00:00Z");
+        let after = utc("2026-10-19T00:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-19T15:00:00Z")));
+        let now = utc("2026-10-19T10:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-19T06:00:00Z")));
+    }
+
+    #[test]
+    fn due_time_is_none_after_the_run() {
+        let s = schedule(&[9 * 60], &[]);
+        let now = utc("2026-10-19T07:00:00Z");
+        assert_eq!(due_time(&s, utc("2026-10-19T06:00:00Z"), now), None);
+        assert_eq!(due_time(&s, utc("2026-10-19T06:30:00Z"), now), None);
+    }
+
+    #[test]
+    fn due_time_looks_at_previous_days() {
+        let s = schedule(&[9 * 60], &[]);
+        let now = utc("2026-10-19T05:00:00Z");
+        let after = utc("2026-10-18T00:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-18T06:00:00Z")));
+    }
+
+    #[test]
+    fn missed_runs_are_caught_up_once() {
+        let s = schedule(&[9 * 60], &[]);
+        let now = utc("2026-10-19T07:00:00Z");
+        let after = utc("2026-10-10T00:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-19T06:00:00Z")));
+    }
+
+    #[test]
+    fn due_time_skips_other_weekdays() {
+        // Sunday, October 18; the last weekday run was on Friday.
+        let s = schedule(&[9 * 60], &[0, 1, 2, 3, 4]);
+        let now = utc("2026-10-18T07:00:00Z");
+        let after = utc("2026-10-15T12:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-16T06:00:00Z")));
+        let after = utc("2026-10-16T06:00:00Z");
+        assert_eq!(due_time(&s, after, now), None);
+    }
+
+    #[test]
+    fn due_time_uses_the_schedule_offset() {
+        let s = schedule(&[30], &[]);
+        // 00:30 on October 19 in UTC+3 is 21:30 on October 18 in UTC.
+        let now = utc("2026-10-18T22:00:00Z");
+        let after = utc("2026-10-18T12:00:00Z");
+        assert_eq!(due_time(&s, after, now), Some(utc("2026-10-18T21:30:00Z")));
+    }
+
+    #[test]
+    fn parses_schedules() {
+        let offset = FixedOffset::east_opt(3 * 3600).unwrap();
+        let s = parse_schedule("9:00,18:30", Some("пн-пт"), offset).unwrap();
+        assert_eq!(s.times, vec![540, 1110]);
+        assert_eq!(s.weekdays, vec![0, 1, 2, 3, 4]);
+        assert_eq!(s.offset, 3 * 3600);
+
+        let s = parse_schedule("07:05", None, offset).unwrap();
+        assert_eq!(s.times, vec![425]);
+        assert!(s.weekdays.is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_schedules() {
+        let offset = FixedOffset::east_opt(0).unwrap();
+        assert!(parse_schedule("24:00", None, offset).is_none());
+        assert!(parse_schedule("9:60", None, offset).is_none());
+        assert!(parse_schedule("9", None, offset).is_none());
+        assert!(parse_schedule("9:00,", None, offset).is_none());
+        assert!(parse_schedule("9:00", Some("someday"), offset).is_none());
+    }
+
+    #[test]
+    fn parses_weekdays() {
+        assert_eq!(parse_weekdays("sat,sun"), Some(vec![5, 6]));
+        assert_eq!(parse_weekdays("пн,sun"), Some(vec![0, 6]));
+        assert_eq!(parse_weekdays("fri-mon"), Some(vec![0, 4, 5, 6]));
+        assert_eq!(parse_weekdays("ср-ср"), Some(vec![2]));
+        assert_eq!(parse_weekdays("mon,mon-tue"), Some(vec![0, 1]));
+        assert_eq!(parse_weekdays(""), Some(vec![]));
+        assert_eq!(parse_weekdays("mon-xyz"), None);
+    }
+}
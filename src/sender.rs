@@ -0,0 +1,307 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use teloxide::RequestError;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+/// Telegram allows about 30 messages per second overall, one message per second
+/// in a private chat and 20 messages per minute in a group.
+const GLOBAL_INTERVAL: Duration = Duration::from_millis(34);
+const PRIVATE_INTERVAL: Duration = Duration::from_secs(1);
+const GROUP_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_RETRIES: u32 = 5;
+
+/// Queue key of requests that aren't sent to a chat, like lookups. They are only held
+/// by the overall rate and by `RetryAfter`.
+pub const NO_CHAT: i64 = 0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Priority {
+    /// Replies to commands, queries and buttons.
+    Interactive = 0,
+    /// Forwarded and copied messages.
+    Bulk = 1,
+}
+
+enum Command {
+    Acquire {
+        chat_id: i64,
+        priority: Priority,
+        permit: oneshot::Sender<()>,
+    },
+    Hold {
+        chat_id: i64,
+        until: Instant,
+    },
+}
+
+struct Queue {
+    tx: mpsc::UnboundedSender<Command>,
+    rx: Mutex<Option<mpsc::UnboundedReceiver<Command>>>,
+}
+
+lazy_static::lazy_static! {
+    static ref QUEUE: Queue = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Queue { tx, rx: Mutex::new(Some(rx)) }
+    };
+}
+
+/// Sends `request` to `chat_id` through the outbound queue. The request is built anew
+/// for every attempt: after `RetryAfter` the chat is held for the given time, and
+/// network errors are retried with exponential backoff.
+pub async fn send<T, F, Fut>(
+    chat_id: i64,
+    priority: Priority,
+    request: F,
+) -> Result<T, RequestError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, RequestError>>,
+{
+    let mut retries = 0;
+    loop {
+        acquire(chat_id, priority).await;
+        match request().await {
+            Err(RequestError::RetryAfter(secs)) => {
+                let until = Instant::now() + Duration::from_secs(secs as u64);
+                let _ = QUEUE.tx.send(Command::Hold { chat_id, until });
+            }
+            Err(e @ (RequestError::NetworkError(_) | RequestError::Io(_)))
+                if retries < MAX_RETRIES =>
+            {
+                log::warn!("Retrying a request to {}. Error: {}", chat_id, e);
+                tokio::time::sleep(Duration::from_secs(1 << retries)).await;
+                retries += 1;
+            }
+            r => return r,
+        }
+    }
+}
+
+/// Makes a request that isn't sent to a chat, like a lookup or a callback answer, through
+/// the queue.
+pub async fn lookup<T, F, Fut>(request: F) -> Result<T, RequestError>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, RequestError>>,
+{
+    send(NO_CHAT, Priority::Interactive, request).await
+}
+
+async fn acquire(chat_id: i64, priority: Priority) {
+    let (permit, granted) = oneshot::channel();
+    let acquire = Command::Acquire {
+        chat_id,
+        priority,
+        permit,
+    };
+    if QUEUE.tx.send(acquire).is_ok() {
+        let _ = granted.await;
+    }
+}
+
+fn chat_interval(chat_id: i64) -> Duration {
+    match chat_id {
+        NO_CHAT => Duration::ZERO,
+        id if id < 0 => GROUP_INTERVAL,
+        _ => PRIVATE_INTERVAL,
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Selection {
+    /// The request at `index` of the `queue` may go now.
+    Grant { queue: usize, index: usize },
+    /// Nothing may go before the instant, `None` if nothing waits.
+    Wait(Option<Instant>),
+}
+
+/// Picks the request to send at `now` out of `queues`, in priority order. A request waits
+/// while its chat is held in `next_chat`, and all of them wait until `next_global`.
+fn select<P>(
+    queues: &[VecDeque<(i64, P)>],
+    next_chat: &HashMap<i64, Instant>,
+    next_global: Instant,
+    now: Instant,
+) -> Selection {
+    let ready = queues.iter().enumerate().find_map(|(q, jobs)| {
+        jobs.iter()
+            .position(|(chat_id, _)| next_chat.get(chat_id).is_none_or(|t| *t <= now))
+            .map(|i| (q, i))
+    });
+    match ready {
+        Some((queue, index)) if next_global <= now => Selection::Grant { queue, index },
+        Some(_) => Selection::Wait(Some(next_global)),
+        None => Selection::Wait(
+            queues
+                .iter()
+                .flatten()
+                .filter_map(|(chat_id, _)| next_chat.get(chat_id))
+                .min()
+                .copied(),
+        ),
+    }
+}
+
+/// Holds `chat_id` until `until`, a hold never shortens an earlier one.
+fn hold(next_chat: &mut HashMap<i64, Instant>, chat_id: i64, until: Instant) {
+    let t = next_chat.entry(chat_id).or_insert(until);
+    *t = (*t).max(until);
+}
+
+/// Hands out send permits. Interactive requests go before bulk ones, and requests
+/// to the same chat keep their order within a priority.
+pub async fn run() {
+    let mut rx = QUEUE
+        .rx
+        .lock()
+        .unwrap()
+        .take()
+        .expect("Send queue is already running");
+    let mut queues: [VecDeque<(i64, oneshot::Sender<()>)>; 2] = Default::default();
+    let mut next_chat = HashMap::<i64, Instant>::new();
+    let mut next_global = Instant::now();
+
+    loop {
+        let now = Instant::now();
+        let wake = match select(&queues, &next_chat, next_global, now) {
+            Selection::Grant { queue, index } => {
+                let (chat_id, permit) = queues[queue].remove(index).unwrap();
+                if permit.send(()).is_ok() {
+                    next_global = now + GLOBAL_INTERVAL;
+                    next_chat.insert(chat_id, now + chat_interval(chat_id));
+                }
+                continue;
+            }
+            Selection::Wait(wake) => wake,
+        };
+
+        let command = match wake {
+            Some(at) => tokio::select! {
+                c = rx.recv() => c,
+                _ = tokio::time::sleep_until(at) => continue,
+            },
+            None => rx.recv().await,
+        };
+        match command {
+            Some(Command::Acquire {
+                chat_id,
+                priority,
+                permit,
+            }) => queues[priority as usize].push_back((chat_id, permit)),
+            Some(Command::Hold { chat_id, until }) => hold(&mut next_chat, chat_id, until),
+            None => break,
+        }
+        if next_chat.len() > 10_000 {
+            next_chat.retain(|_, t| *t > now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queues(interactive: &[i64], bulk: &[i64]) -> [VecDeque<(i64, ())>; 2] {
+        [
+            interactive.iter().map(|&id| (id, ())).collect(),
+            bulk.iter().map(|&id| (id, ())).collect(),
+        ]
+    }
+
+    /// The hold a grant to `chat_id` at `at` leaves, as in `run`.
+    fn granted(chat_id: i64, at: Instant) -> HashMap<i64, Instant> {
+        HashMap::from([(chat_id, at + chat_interval(chat_id))])
+    }
+
+    #[test]
+    fn interactive_goes_first() {
+        let now = Instant::now();
+        let grant = select(&queues(&[2], &[1]), &HashMap::new(), now, now);
+        assert_eq!(grant, Selection::Grant { queue: 0, index: 0 });
+    }
+
+    #[test]
+    fn bulk_goes_when_interactive_chats_are_held() {
+        let now = Instant::now();
+        let held = granted(1, now);
+        let grant = select(&queues(&[1, 1], &[2]), &held, now, now);
+        assert_eq!(grant, Selection::Grant { queue: 1, index: 0 });
+    }
+
+    #[test]
+    fn held_chat_is_skipped() {
+        let now = Instant::now();
+        let grant = select(&queues(&[-5, 3, -5], &[]), &granted(-5, now), now, now);
+        assert_eq!(grant, Selection::Grant { queue: 0, index: 1 });
+    }
+
+    #[test]
+    fn global_interval_holds_everything() {
+        let now = Instant::now();
+        let next_global = now + GLOBAL_INTERVAL;
+        let wait = select(&queues(&[1], &[2]), &HashMap::new(), next_global, now);
+        assert_eq!(wait, Selection::Wait(Some(next_global)));
+    }
+
+    #[test]
+    fn private_chat_interval() {
+        let now = Instant::now();
+        let held = granted(7, now);
+        let wait = select(&queues(&[7], &[]), &held, now, now + PRIVATE_INTERVAL / 2);
+        assert_eq!(wait, Selection::Wait(Some(now + PRIVATE_INTERVAL)));
+        let grant = select(&queues(&[7], &[]), &held, now, now + PRIVATE_INTERVAL);
+        assert_eq!(grant, Selection::Grant { queue: 0, index: 0 });
+    }
+
+    #[test]
+    fn group_chat_interval() {
+        let now = Instant::now();
+        let held = granted(-7, now);
+        let wait = select(&queues(&[-7], &[]), &held, now, now + PRIVATE_INTERVAL);
+        assert_eq!(wait, Selection::Wait(Some(now + GROUP_INTERVAL)));
+    }
+
+    #[test]
+    fn no_chat_has_no_interval() {
+        let now = Instant::now();
+        let grant = select(&queues(&[NO_CHAT], &[]), &granted(NO_CHAT, now), now, now);
+        assert_eq!(grant, Selection::Grant { queue: 0, index: 0 });
+    }
+
+    #[test]
+    fn hold_waits_for_the_earliest_chat() {
+        let now = Instant::now();
+        let mut next_chat = HashMap::new();
+        hold(&mut next_chat, 1, now + Duration::from_secs(10));
+        hold(&mut next_chat, 2, now + Duration::from_secs(4));
+        let wait = select(&queues(&[1], &[2]), &next_chat, now, now);
+        assert_eq!(wait, Selection::Wait(Some(now + Duration::from_secs(4))));
+    }
+
+    #[test]
+    fn hold_is_never_shortened() {
+        let now = Instant::now();
+        let mut next_chat = granted(1, now);
+        hold(&mut next_chat, 1, now + Duration::from_secs(30));
+        hold(&mut next_chat, 1, now + Duration::from_secs(5));
+        let wait = select(
+            &queues(&[1], &[]),
+            &next_chat,
+            now,
+            now + Duration::from_secs(10),
+        );
+        assert_eq!(wait, Selection::Wait(Some(now + Duration::from_secs(30))));
+    }
+
+    #[test]
+    fn nothing_waits() {
+        let now = Instant::now();
+        let wait = select(&queues(&[], &[]), &granted(1, now), now, now);
+        assert_eq!(wait, Selection::Wait(None));
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{Timelike, Utc};
+use mongodb::Client;
+use teloxide::prelude::*;
+
+use crate::common::deliver_message;
+use crate::db_utils::{
+    self,
+    models::{Message, QuietHours, Subscription},
+};
+use crate::delivery::profile_for;
+use crate::i18n::tr;
+use crate::sender::{self, Priority};
+
+/// Forwards freshly saved `messages` to every subscriber whose subscription matches them.
+/// Messages matched only by subscriptions in their quiet hours are delivered silently.
+pub async fn notify(bot: AutoSend<Bot>, client: Arc<Client>, messages: Vec<Message>) {
+    let mut regions = messages
+        .iter()
+        .flat_map(|m| m.regions.iter().cloned())
+        .collect::<Vec<_>>();
+    regions.sort_unstable();
+    regions.dedup();
+
+    let subscriptions = match db_utils::find_subscriptions(&client, regions).await {
+        Ok(s) => s,
+        Err(e) => return log::error!("Can't access subscriptions. Error: {}", e),
+    };
+
+    let mut by_user = HashMap::<i64, Vec<Subscription>>::new();
+    for subscription in subscriptions {
+        by_user
+            .entry(subscription.user_id)
+            .or_default()
+            .push(subscription);
+    }
+
+    for (user_id, subscriptions) in by_user {
+        let allowed = match db_utils::get_allowed_regions(&client, user_id).await {
+            Ok(a) => a,
+            Err(e) => {
+                log::error!("Can't access allowed regions of {}. Error: {}", user_id, e);
+                continue;
+            }
+        };
+
+        let matches = |s: &Subscription, m: &Message| {
+            m.regions
+                .iter()
+                .any(|r| allowed.contains(r) && s.regions.contains(r))
+                && (s.tags.is_empty() || m.tags.iter().any(|t| s.tags.contains(t)))
+        };
+        let matched = messages
+            .iter()
+            .filter(|m| subscriptions.iter().any(|s| matches(s, m)))
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            continue;
+        }
+        let silent = subscriptions
+            .iter()
+            .filter(|s| matched.iter().any(|m| matches(s, m)))
+            .all(|s| is_quiet(s.quiet_hours));
+
+        let mut regions = matched
+            .iter()
+            .flat_map(|m| m.regions.iter())
+            .filter(|r| allowed.contains(*r))
+            .map(|r| r.as_str())
+            .collect::<Vec<_>>();
+        regions.sort_unstable();
+        regions.dedup();
+
+        let profile = profile_for(&client, user_id).await;
+        let text = tr!(
+            profile.language,
+            "subscription.new_messages",
+            regions = regions.join(", ")
+        );
+        if let Err(e) = sender::send(user_id, Priority::Bulk, || {
+            bot.send_message(user_id, text.as_str())
+                .disable_notification(silent)
+        })
+        .await
+        {
+            log::error!("Error while sending a string: {}", e);
+        }
+        for message in matched {
+            deliver_message(&bot, user_id, message, &profile, silent).await;
+        }
+    }
+}
+
+fn is_quiet(quiet_hours: Option<QuietHours>) -> bool {
+    let quiet_hours = match quiet_hours {
+        Some(q) => q,
+        None => return false,
+    };
+    let local = Utc::now() + chrono::Duration::seconds(quiet_hours.offset as i64);
+    let minute = local.hour() * 60 + local.minute();
+    if quiet_hours.from <= quiet_hours.to {
+        (quiet_hours.from..quiet_hours.to).contains(&minute)
+    } else {
+        minute >= quiet_hours.from || minute < quiet_hours.to
+    }
+}